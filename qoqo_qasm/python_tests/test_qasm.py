@@ -12,7 +12,7 @@
 
 import pytest
 import sys
-from qoqo_qasm import QasmBackend
+from qoqo_qasm import QasmBackend, QasmParseError, qasm_str_to_circuit
 from qoqo import Circuit
 from qoqo import operations as ops
 
@@ -26,5 +26,13 @@ def test_qasm() -> None:
     assert qasm
 
 
+def test_qasm_parse_error() -> None:
+    with pytest.raises(QasmParseError) as error:
+        qasm_str_to_circuit("OPENQASM 2.0;\nqreg q[1];\nx q[1];")
+    assert isinstance(error.value, ValueError)
+    assert error.value.line == 3
+    assert error.value.column == 3
+
+
 if __name__ == "__main__":
     pytest.main(sys.argv)
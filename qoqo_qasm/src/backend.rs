@@ -15,11 +15,12 @@ use pyo3::{
     prelude::*,
 };
 use std::path::Path;
+use std::str::FromStr;
 
 use qoqo::convert_into_circuit;
 use qoqo::CircuitWrapper;
 
-use roqoqo_qasm::Backend;
+use roqoqo_qasm::{Backend, EmitterOptions, TimeUnit};
 
 /// Backend to qoqo that produces QASM output which can be imported.
 ///
@@ -30,7 +31,7 @@ use roqoqo_qasm::Backend;
 /// user on whatever platform they see fit. QASM input is widely supported on various quantum
 /// computing platforms.
 #[pyclass(name = "QasmBackend", module = "qoqo_qasm")]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct QasmBackendWrapper {
     /// Internal storage of [roqoqo_qasm::Backend]
     pub internal: Backend,
@@ -42,18 +43,53 @@ impl QasmBackendWrapper {
     ///
     /// Args:
     ///     qubit_register_name (Optional[str]): The name of the qubit register.
+    ///     qasm_version (Optional[str]): The version of OpenQASM to use.
+    ///     measurement_assignment (Optional[bool]): Whether measurements are written as
+    ///         `ro[0] = measure q[0];` in OpenQASM 3.0. Defaults to True for 3.0Braket only.
+    ///     gate_modifiers (Optional[bool]): Whether controlled and inverse gates are written with
+    ///         the gate modifiers `ctrl @` and `inv @` in OpenQASM 3.0. Defaults to False.
+    ///     time_unit (Optional[str]): The unit of the `delay` durations written for PragmaSleep in
+    ///         OpenQASM 3.0, one of "ns", "us", "ms", "s" and "dt". Defaults to "s".
+    ///     dt (Optional[float]): The length of the sample time `dt` in seconds.
+    ///     minimal_header (Optional[bool]): Whether to include `stdgates.inc` or `qelib1.inc` and
+    ///         only define the gates the library does not provide. Defaults to False.
     ///
     /// Returns:
     ///     Self: The new QasmBackend intance.
     #[new]
-    #[pyo3(signature = (qubit_register_name=None, qasm_version=None))]
+    #[pyo3(signature = (qubit_register_name=None, qasm_version=None, measurement_assignment=None, gate_modifiers=None, time_unit=None, dt=None, minimal_header=None))]
     pub fn new(
         qubit_register_name: Option<String>,
         qasm_version: Option<String>,
+        measurement_assignment: Option<bool>,
+        gate_modifiers: Option<bool>,
+        time_unit: Option<String>,
+        dt: Option<f64>,
+        minimal_header: Option<bool>,
     ) -> PyResult<Self> {
+        let mut emitter_options = EmitterOptions::new();
+        if let Some(measurement_assignment) = measurement_assignment {
+            emitter_options = emitter_options.with_measurement_assignment(measurement_assignment);
+        }
+        if let Some(gate_modifiers) = gate_modifiers {
+            emitter_options = emitter_options.with_gate_modifiers(gate_modifiers);
+        }
+        if let Some(time_unit) = time_unit {
+            emitter_options = emitter_options.with_time_unit(
+                TimeUnit::from_str(&time_unit)
+                    .map_err(|x| PyValueError::new_err(format!("{x}")))?,
+            );
+        }
+        if let Some(dt) = dt {
+            emitter_options = emitter_options.with_dt(dt);
+        }
+        if let Some(minimal_header) = minimal_header {
+            emitter_options = emitter_options.with_minimal_header(minimal_header);
+        }
         Ok(Self {
             internal: Backend::new(qubit_register_name, qasm_version)
-                .map_err(|x| PyValueError::new_err(format!("{x}")))?,
+                .map_err(|x| PyValueError::new_err(format!("{x}")))?
+                .with_emitter_options(emitter_options),
         })
     }
 
@@ -118,10 +154,11 @@ impl QasmBackendWrapper {
     ///
     /// Raises:
     ///     PyFileNotFoundError: The file could not be opened.
-    ///     PyValueError: An error occurred while converting the file into a Circuit.
+    ///     QasmParseError: An error occurred while converting the file into a Circuit.
+    ///         Subclass of ValueError with the `line` and `column` of the problem.
     #[pyo3(text_signature = "($self, file)")]
     pub fn qasm_file_to_circuit(&self, file: &str) -> PyResult<CircuitWrapper> {
-        crate::qasm_file_to_circuit(file)
+        crate::qasm_file_to_circuit(file, None, None, self.internal.emitter_options().dt(), None)
     }
 
     /// Translates a QASM string into a qoqo Circuit instance.
@@ -133,9 +170,16 @@ impl QasmBackendWrapper {
     ///     Circuit: The Circuit that was read from the QASM file.
     ///
     /// Raises:
-    ///     PyValueError: An error occurred while converting the file into a Circuit.
+    ///     QasmParseError: An error occurred while converting the file into a Circuit.
+    ///         Subclass of ValueError with the `line` and `column` of the problem.
     #[pyo3(text_signature = "(input)")]
     pub fn qasm_str_to_circuit(&self, input: &str) -> PyResult<CircuitWrapper> {
-        crate::qasm_str_to_circuit(input)
+        crate::qasm_str_to_circuit(
+            input,
+            None,
+            None,
+            self.internal.emitter_options().dt(),
+            None,
+        )
     }
 }
@@ -36,11 +36,14 @@ mod parser;
 pub use parser::*;
 
 #[pymodule]
-fn qoqo_qasm(_py: Python, module: &Bound<PyModule>) -> PyResult<()> {
+fn qoqo_qasm(py: Python, module: &Bound<PyModule>) -> PyResult<()> {
     module.add_class::<QasmBackendWrapper>()?;
     module.add_function(wrap_pyfunction!(qasm_call_circuit, module)?)?;
     module.add_function(wrap_pyfunction!(qasm_call_operation, module)?)?;
     module.add_function(wrap_pyfunction!(qasm_file_to_circuit, module)?)?;
     module.add_function(wrap_pyfunction!(qasm_str_to_circuit, module)?)?;
+    module.add_function(wrap_pyfunction!(qasm_file_to_quantum_program, module)?)?;
+    module.add_function(wrap_pyfunction!(qasm_str_to_quantum_program, module)?)?;
+    module.add("QasmParseError", py.get_type::<QasmParseError>())?;
     Ok(())
 }
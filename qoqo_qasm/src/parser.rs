@@ -10,50 +10,243 @@
 // express or implied. See the License for the specific language governing permissions and
 // limitations under the License.
 
+use pyo3::create_exception;
 use pyo3::exceptions::PyFileNotFoundError;
 use pyo3::prelude::*;
 use pyo3::{exceptions::PyValueError, PyResult};
-use qoqo::CircuitWrapper;
+use qoqo::{CircuitWrapper, QuantumProgramWrapper};
+use std::collections::HashMap;
 use std::fs::File;
+use std::path::Path;
 
-use roqoqo_qasm::{file_to_circuit, string_to_circuit};
+use roqoqo_qasm::{
+    path_to_qasm_program_with_options, string_to_qasm_program_with_options, ParserOptions,
+};
+
+create_exception!(
+    qoqo_qasm,
+    QasmParseError,
+    PyValueError,
+    "Error raised when a QASM input cannot be translated into a Circuit.\n\nThe attributes `line` and `column` give the position of the problem in the input, they are None for problems of the input as a whole."
+);
+
+/// Converts a roqoqo-qasm parsing error into a Python QasmParseError.
+///
+/// The position of the error is set as the `line` and `column` attributes of the exception, or
+/// None for errors without a position.
+fn parse_error_to_py(error: roqoqo_qasm::QasmParseError) -> PyErr {
+    Python::with_gil(|py| {
+        let py_error = QasmParseError::new_err(error.to_string());
+        let value = py_error.value(py);
+        let position = (error.line() > 0).then(|| (error.line(), error.column()));
+        match value
+            .setattr("line", position.map(|(line, _)| line))
+            .and_then(|_| value.setattr("column", position.map(|(_, column)| column)))
+        {
+            Ok(_) => py_error,
+            Err(err) => err,
+        }
+    })
+}
+
+/// Collects the include search paths, input values, length of dt and inlining of gates into
+/// parser settings.
+fn parser_options(
+    include_paths: Option<Vec<String>>,
+    input_values: Option<HashMap<String, f64>>,
+    dt: Option<f64>,
+    inline_gates: Option<bool>,
+) -> ParserOptions {
+    let options = include_paths
+        .unwrap_or_default()
+        .into_iter()
+        .fold(ParserOptions::new(), |options, path| {
+            options.with_include_path(path)
+        });
+    let options = input_values
+        .unwrap_or_default()
+        .into_iter()
+        .fold(options, |options, (name, value)| {
+            options.with_input_value(name, value)
+        });
+    let options = match dt {
+        Some(dt) => options.with_dt(dt),
+        None => options,
+    };
+    options.with_inline_gates(inline_gates.unwrap_or_default())
+}
 
 /// Translates a QASM File to a Circuit.
 ///
+/// Included files are looked up in the directory of the including file and then in the
+/// include paths. Relative include paths are relative to the directory of the QASM file.
+///
 /// Args:
 ///     file (str): The path to the QASM file.
+///     include_paths (Optional[List[str]]): Directories searched for included files.
+///     input_values (Optional[Dict[str, float]]): Values of the parameters declared with `input`,
+///         parameters without a value default to 0.
+///     dt (Optional[float]): The length of the sample time `dt` in seconds, needed to read
+///         durations such as `delay[100dt] q[0];`.
+///     inline_gates (Optional[bool]): Whether calls of user-defined gates are replaced by the
+///         bodies of the gates, recursively. Defaults to False.
 ///
 /// Returns:
 ///     Circuit: The Circuit that was read from the QASM file.
 ///
 /// Raises:
 ///     PyFileNotFoundError: The file could not be opened.
-///     PyValueError: An error occurred while converting the file into a Circuit.
+///     QasmParseError: An error occurred while converting the file into a Circuit.
+///         Subclass of ValueError with the `line` and `column` of the problem.
 #[pyfunction]
-#[pyo3(text_signature = "(file)")]
-pub fn qasm_file_to_circuit(file: &str) -> PyResult<CircuitWrapper> {
-    let f = File::open(file)
+#[pyo3(
+    signature = (file, include_paths = None, input_values = None, dt = None, inline_gates = None),
+    text_signature = "(file, include_paths=None, input_values=None, dt=None, inline_gates=None)"
+)]
+pub fn qasm_file_to_circuit(
+    file: &str,
+    include_paths: Option<Vec<String>>,
+    input_values: Option<HashMap<String, f64>>,
+    dt: Option<f64>,
+    inline_gates: Option<bool>,
+) -> PyResult<CircuitWrapper> {
+    File::open(file)
         .map_err(|x| PyFileNotFoundError::new_err(format!("Error during File opening: {x}")))?;
 
-    let circuit = file_to_circuit(f).map_err(|x| PyValueError::new_err(format!("{x}")))?;
+    let circuit = path_to_qasm_program_with_options(
+        Path::new(file),
+        &parser_options(include_paths, input_values, dt, inline_gates),
+    )
+    .map_err(parse_error_to_py)?
+    .into_circuit();
 
     Ok(CircuitWrapper { internal: circuit })
 }
 
 /// Translates a QASM string into a qoqo Circuit instance.
 ///
+/// Included files are looked up in the working directory and then in the include paths.
+///
 /// Args:
 ///     input (str): The QASM string to translate.
+///     include_paths (Optional[List[str]]): Directories searched for included files.
+///     input_values (Optional[Dict[str, float]]): Values of the parameters declared with `input`,
+///         parameters without a value default to 0.
+///     dt (Optional[float]): The length of the sample time `dt` in seconds, needed to read
+///         durations such as `delay[100dt] q[0];`.
+///     inline_gates (Optional[bool]): Whether calls of user-defined gates are replaced by the
+///         bodies of the gates, recursively. Defaults to False.
 ///
 /// Returns:
 ///     Circuit: The Circuit that was read from the QASM file.
 ///
 /// Raises:
-///     PyValueError: An error occurred while converting the file into a Circuit.
+///     QasmParseError: An error occurred while converting the file into a Circuit.
+///         Subclass of ValueError with the `line` and `column` of the problem.
 #[pyfunction]
-#[pyo3(text_signature = "(input)")]
-pub fn qasm_str_to_circuit(input: &str) -> PyResult<CircuitWrapper> {
-    let circuit = string_to_circuit(input).map_err(|x| PyValueError::new_err(format!("{x}")))?;
+#[pyo3(
+    signature = (input, include_paths = None, input_values = None, dt = None, inline_gates = None),
+    text_signature = "(input, include_paths=None, input_values=None, dt=None, inline_gates=None)"
+)]
+pub fn qasm_str_to_circuit(
+    input: &str,
+    include_paths: Option<Vec<String>>,
+    input_values: Option<HashMap<String, f64>>,
+    dt: Option<f64>,
+    inline_gates: Option<bool>,
+) -> PyResult<CircuitWrapper> {
+    let circuit = string_to_qasm_program_with_options(
+        input,
+        &parser_options(include_paths, input_values, dt, inline_gates),
+    )
+    .map_err(parse_error_to_py)?
+    .into_circuit();
 
     Ok(CircuitWrapper { internal: circuit })
 }
+
+/// Translates a QASM File into a QuantumProgram reading out the classical registers.
+///
+/// The measurement is a ClassicalRegister measurement returning the registers declared as
+/// `output`, or all registers if the file declares no outputs. The parameters declared with
+/// `input` are the input parameters of the program, in the order of their declaration.
+///
+/// Args:
+///     file (str): The path to the QASM file.
+///     include_paths (Optional[List[str]]): Directories searched for included files.
+///     dt (Optional[float]): The length of the sample time `dt` in seconds, needed to read
+///         durations such as `delay[100dt] q[0];`.
+///     inline_gates (Optional[bool]): Whether calls of user-defined gates are replaced by the
+///         bodies of the gates, recursively. Defaults to False.
+///
+/// Returns:
+///     QuantumProgram: The QuantumProgram running the Circuit read from the QASM file.
+///
+/// Raises:
+///     PyFileNotFoundError: The file could not be opened.
+///     QasmParseError: An error occurred while converting the file, or it contains no measurement.
+///         Subclass of ValueError with the `line` and `column` of the problem, which are None
+///         for a missing measurement.
+#[pyfunction]
+#[pyo3(
+    signature = (file, include_paths = None, dt = None, inline_gates = None),
+    text_signature = "(file, include_paths=None, dt=None, inline_gates=None)"
+)]
+pub fn qasm_file_to_quantum_program(
+    file: &str,
+    include_paths: Option<Vec<String>>,
+    dt: Option<f64>,
+    inline_gates: Option<bool>,
+) -> PyResult<QuantumProgramWrapper> {
+    File::open(file)
+        .map_err(|x| PyFileNotFoundError::new_err(format!("Error during File opening: {x}")))?;
+
+    let program = path_to_qasm_program_with_options(
+        Path::new(file),
+        &parser_options(include_paths, None, dt, inline_gates),
+    )
+    .and_then(|program| program.into_quantum_program())
+    .map_err(parse_error_to_py)?;
+
+    Ok(QuantumProgramWrapper { internal: program })
+}
+
+/// Translates a QASM string into a QuantumProgram reading out the classical registers.
+///
+/// See `qasm_file_to_quantum_program` for the construction of the program.
+///
+/// Args:
+///     input (str): The QASM string to translate.
+///     include_paths (Optional[List[str]]): Directories searched for included files.
+///     dt (Optional[float]): The length of the sample time `dt` in seconds, needed to read
+///         durations such as `delay[100dt] q[0];`.
+///     inline_gates (Optional[bool]): Whether calls of user-defined gates are replaced by the
+///         bodies of the gates, recursively. Defaults to False.
+///
+/// Returns:
+///     QuantumProgram: The QuantumProgram running the Circuit read from the QASM string.
+///
+/// Raises:
+///     QasmParseError: An error occurred while converting the string, or it contains no
+///         measurement. Subclass of ValueError with the `line` and `column` of the problem,
+///         which are None for a missing measurement.
+#[pyfunction]
+#[pyo3(
+    signature = (input, include_paths = None, dt = None, inline_gates = None),
+    text_signature = "(input, include_paths=None, dt=None, inline_gates=None)"
+)]
+pub fn qasm_str_to_quantum_program(
+    input: &str,
+    include_paths: Option<Vec<String>>,
+    dt: Option<f64>,
+    inline_gates: Option<bool>,
+) -> PyResult<QuantumProgramWrapper> {
+    let program = string_to_qasm_program_with_options(
+        input,
+        &parser_options(include_paths, None, dt, inline_gates),
+    )
+    .and_then(|program| program.into_quantum_program())
+    .map_err(parse_error_to_py)?;
+
+    Ok(QuantumProgramWrapper { internal: program })
+}
@@ -1,6 +1,6 @@
 OPENQASM 2.0;
 creg c[2];
-qreg q[2];
+qreg q[3];
 
 rz(0.2) q[0];
 ry(0.3) q[1];
@@ -15,7 +15,7 @@ p(0.6) q[2];
 sx q[1];
 sxdg q[0];
 cx q[0],q[1];
-rxx(3.14159265359) q[1],q[2];
+rxx(pi/2) q[1],q[2];
 rxx(0.7) q[0],q[2];
 cy q[0],q[1];
 cz q[1],q[2];
@@ -90,6 +90,143 @@ fn test_circuit_to_qasm_str(qasm_version: &str, qubits: &str, bits: &str) {
     })
 }
 
+/// Test the measurement_assignment argument of the QasmBackend
+#[test_case("3.0", None, "measure q -> ro;\n"; "3.0")]
+#[test_case("3.0", Some(true), "ro = measure q;\n"; "3.0 assignment")]
+#[test_case("3.0Braket", None, "ro = measure q;\n"; "3.0Braket")]
+#[test_case("3.0Braket", Some(false), "measure q -> ro;\n"; "3.0Braket arrow")]
+fn test_circuit_to_qasm_str_measurement_assignment(
+    qasm_version: &str,
+    measurement_assignment: Option<bool>,
+    measurement: &str,
+) {
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 1, true);
+    circuit += PauliX::new(0);
+    circuit += PragmaRepeatedMeasurement::new("ro".to_string(), 1, None);
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let backend_type = py.get_type::<QasmBackendWrapper>();
+        let backendpy = backend_type
+            .call1((None::<String>, qasm_version, measurement_assignment))
+            .unwrap();
+        let circuitpy = circuitpy_from_circuitru(py, circuit);
+
+        let result: String = backendpy
+            .call_method1("circuit_to_qasm_str", (circuitpy,))
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(result.ends_with(measurement));
+    })
+}
+
+/// Test the gate_modifiers argument of the QasmBackend
+#[test_case("3.0", None, "cz q[0],q[1];\n"; "3.0")]
+#[test_case("3.0", Some(true), "ctrl @ z q[0],q[1];\n"; "3.0 modifiers")]
+#[test_case("2.0", Some(true), "cz q[0],q[1];\n"; "2.0 modifiers")]
+fn test_circuit_to_qasm_str_gate_modifiers(
+    qasm_version: &str,
+    gate_modifiers: Option<bool>,
+    gate_call: &str,
+) {
+    let mut circuit = Circuit::new();
+    circuit += ControlledPauliZ::new(0, 1);
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let backend_type = py.get_type::<QasmBackendWrapper>();
+        let backendpy = backend_type
+            .call1((None::<String>, qasm_version, None::<bool>, gate_modifiers))
+            .unwrap();
+        let circuitpy = circuitpy_from_circuitru(py, circuit);
+
+        let result: String = backendpy
+            .call_method1("circuit_to_qasm_str", (circuitpy,))
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(result.ends_with(gate_call));
+    })
+}
+
+/// Test the time_unit and dt arguments of the QasmBackend
+#[test_case(None, None, "delay[0.00000005s] q[0];\n"; "default")]
+#[test_case(Some("ns"), None, "delay[50ns] q[0];\n"; "nanoseconds")]
+#[test_case(Some("dt"), Some(0.5e-9), "delay[100dt] q[0];\n"; "dt")]
+fn test_circuit_to_qasm_str_time_unit(time_unit: Option<&str>, dt: Option<f64>, delay: &str) {
+    let mut circuit = Circuit::new();
+    circuit += PragmaSleep::new(vec![0], 5e-8.into());
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let backend_type = py.get_type::<QasmBackendWrapper>();
+        let backendpy = backend_type
+            .call1((
+                None::<String>,
+                "3.0",
+                None::<bool>,
+                None::<bool>,
+                time_unit,
+                dt,
+            ))
+            .unwrap();
+        let circuitpy = circuitpy_from_circuitru(py, circuit);
+
+        let result: String = backendpy
+            .call_method1("circuit_to_qasm_str", (circuitpy,))
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(result.ends_with(delay));
+    })
+}
+
+/// Test that an unknown time unit is rejected by the QasmBackend
+#[test]
+fn test_new_time_unit_error() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let backend_type = py.get_type::<QasmBackendWrapper>();
+        assert!(backend_type
+            .call1((None::<String>, "3.0", None::<bool>, None::<bool>, "min"))
+            .is_err());
+    })
+}
+
+/// Test the minimal_header argument of the QasmBackend
+#[test_case(None, "gate u3("; "full header")]
+#[test_case(Some(true), "include \"stdgates.inc\";"; "minimal header")]
+fn test_circuit_to_qasm_str_minimal_header(minimal_header: Option<bool>, header: &str) {
+    let mut circuit = Circuit::new();
+    circuit += Hadamard::new(0);
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let backend_type = py.get_type::<QasmBackendWrapper>();
+        let backendpy = backend_type
+            .call1((
+                None::<String>,
+                "3.0",
+                None::<bool>,
+                None::<bool>,
+                None::<String>,
+                None::<f64>,
+                minimal_header,
+            ))
+            .unwrap();
+        let circuitpy = circuitpy_from_circuitru(py, circuit);
+
+        let result: String = backendpy
+            .call_method1("circuit_to_qasm_str", (circuitpy,))
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(result.contains(header));
+    })
+}
+
 /// Test circuit_to_qasm_file on a simple Circuit
 #[test_case("2.0", "qreg qr[2]", "creg ro[2]"; "2.0")]
 #[test_case("3.0", "qubit[2] qr", "bit[2] ro"; "3.0")]
@@ -184,6 +184,37 @@ fn test_qasm_call_operation_different_braket(
     })
 }
 
+/// Test that measurements are written in the assignment form for 3.0Braket
+#[test_case(Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)), "measure q -> ro;", "ro = measure q;", "measure q -> ro;\npragma roqoqo PragmaSetNumberOfMeasurements 1 ro;"; "PragmaRepeatedMeasurement")]
+#[test_case(Operation::from(MeasureQubit::new(0, "ro".to_string(), 1)), "measure q[0] -> ro[1];", "ro[1] = measure q[0];", "measure q[0] -> ro[1];"; "MeasureQubit")]
+fn test_qasm_call_operation_measurement(
+    operation: Operation,
+    converted_2: &str,
+    converted_braket: &str,
+    converted_roqoqo: &str,
+) {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let new_op = convert_operation_to_pyobject(operation, py).unwrap();
+        assert_eq!(
+            qasm_call_operation(&new_op, "q", "2.0").unwrap(),
+            converted_2.to_string()
+        );
+        assert_eq!(
+            qasm_call_operation(&new_op, "q", "3.0Vanilla").unwrap(),
+            converted_2.to_string()
+        );
+        assert_eq!(
+            qasm_call_operation(&new_op, "q", "3.0Braket").unwrap(),
+            converted_braket.to_string()
+        );
+        assert_eq!(
+            qasm_call_operation(&new_op, "q", "3.0Roqoqo").unwrap(),
+            converted_roqoqo.to_string()
+        );
+    })
+}
+
 #[test_case(Operation::from(InputBit::new("other".to_string(), 0, false)), "other[0] = false;"; "InputBit")]
 fn test_qasm_call_operation_error_2_3(operation: Operation, converted_3: &str) {
     pyo3::prepare_freethreaded_python();
@@ -198,14 +229,12 @@ fn test_qasm_call_operation_error_2_3(operation: Operation, converted_3: &str) {
 }
 
 #[test_case(Operation::from(PragmaStopDecompositionBlock::new(vec![0,1])), "", "pragma roqoqo PragmaStopDecompositionBlock [0, 1];"; "PragmaStopDecompositionBlock")]
-#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), "", "pragma roqoqo PragmaStopParallelBlock [] 0e0;"; "PragmaStopParallelBlock")]
 #[test_case(Operation::from(PragmaSetNumberOfMeasurements::new(20, "ro".to_string())), "", "pragma roqoqo PragmaSetNumberOfMeasurements 20 ro;"; "PragmaSetNumberOfMeasurements")]
 #[test_case(Operation::from(PragmaStartDecompositionBlock::new(vec![0,1], HashMap::new())), "", "pragma roqoqo PragmaStartDecompositionBlock [0, 1] {};"; "PragmaStartDecompositionBlock")]
-#[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), "", "pragma roqoqo PragmaGetDensityMatrix test ;"; "PragmaGetDensityMatrix")]
-#[test_case(Operation::from(PragmaGetOccupationProbability::new("test".into(), None)), "", "pragma roqoqo PragmaGetOccupationProbability test ;"; "PragmaGetOccupationProbability")]
-#[test_case(Operation::from(PragmaGetPauliProduct::new(HashMap::new(), "test".into(), Circuit::new())), "", "pragma roqoqo PragmaGetPauliProduct {} test ;"; "PragmaGetPauliProduct")]
-#[test_case(Operation::from(PragmaGetStateVector::new("test".into(), None)), "", "pragma roqoqo PragmaGetStateVector test ;"; "PragmaGetStateVector")]
-#[test_case(Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)), "measure q -> ro;", "measure q -> ro;\npragma roqoqo PragmaSetNumberOfMeasurements 1 ro;"; "PragmaRepeatedMeasurement")]
+#[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), "", "pragma roqoqo PragmaGetDensityMatrix test;"; "PragmaGetDensityMatrix")]
+#[test_case(Operation::from(PragmaGetOccupationProbability::new("test".into(), None)), "", "pragma roqoqo PragmaGetOccupationProbability test;"; "PragmaGetOccupationProbability")]
+#[test_case(Operation::from(PragmaGetPauliProduct::new(HashMap::new(), "test".into(), Circuit::new())), "", "pragma roqoqo PragmaGetPauliProduct {} test;"; "PragmaGetPauliProduct")]
+#[test_case(Operation::from(PragmaGetStateVector::new("test".into(), None)), "", "pragma roqoqo PragmaGetStateVector test;"; "PragmaGetStateVector")]
 fn test_call_operation_different_2_roqoqo_3(
     operation: Operation,
     converted_2: &str,
@@ -233,8 +262,10 @@ fn test_call_operation_different_2_roqoqo_3(
     })
 }
 
-#[test_case(Operation::from(PragmaLoop::new(2.0.into(), Circuit::new() + PauliX::new(0))), "pragma roqoqo PragmaLoop 2e0 PauliX(PauliX { qubit: 0 })\n;", "for uint i in [0:2] {\n    x q[0];\n}", "x q[0];\nx q[0];\n", "x q[0];\nx q[0];\n"; "PragmaLoop")]
-#[test_case(Operation::from(PragmaSleep::new(vec![0,1], CalculatorFloat::from(0.3))), "pragma roqoqo PragmaSleep [0, 1] 3e-1;", "", "", "pragmasleep(3e-1) q[0];\npragmasleep(3e-1) q[1];"; "PragmaSleep")]
+#[test_case(Operation::from(PragmaLoop::new(2.0.into(), Circuit::new() + PauliX::new(0))), "pragma roqoqo PragmaLoop 2e0 {\n    x q[0];\n}", "for uint i in [0:1] {\n    x q[0];\n}", "x q[0];\nx q[0];\n", "x q[0];\nx q[0];\n"; "PragmaLoop")]
+#[test_case(Operation::from(PragmaSleep::new(vec![0,1], CalculatorFloat::from(0.3))), "pragma roqoqo PragmaSleep [0, 1] 3e-1;", "delay[0.3s] q[0],q[1];", "delay[0.3s] q[0],q[1];", "pragmasleep(3e-1) q[0];\npragmasleep(3e-1) q[1];"; "PragmaSleep")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), "pragma roqoqo PragmaStopParallelBlock [] 0e0;", "barrier q;", "", "barrier q;"; "PragmaStopParallelBlock")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![0, 2], CalculatorFloat::from(0.0))), "pragma roqoqo PragmaStopParallelBlock [0, 2] 0e0;", "barrier q[0],q[2];", "", "barrier q[0],q[2];"; "PragmaStopParallelBlock on qubits")]
 fn test_call_operation_error_different_all(
     operation: Operation,
     converted_3_roqoqo: &str,
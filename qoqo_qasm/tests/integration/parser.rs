@@ -12,12 +12,21 @@
 //
 //! Testing the qoqo-qasm parsing functions
 
-use pyo3::{exceptions::PyFileNotFoundError, prelude::*};
+use pyo3::{
+    exceptions::{PyFileNotFoundError, PyValueError},
+    prelude::*,
+};
 
 use qoqo::{operations::convert_operation_to_pyobject, CircuitWrapper};
-use roqoqo::{operations::*, Circuit};
+use roqoqo::measurements::ClassicalRegister;
+use roqoqo::{operations::*, Circuit, QuantumProgram};
 
-use qoqo_qasm::qasm_file_to_circuit;
+use qoqo_qasm::{
+    qasm_file_to_circuit, qasm_file_to_quantum_program, qasm_str_to_circuit,
+    qasm_str_to_quantum_program, QasmParseError,
+};
+
+use std::collections::HashMap;
 
 // helper functions
 fn circuitpy_from_circuitru(py: Python, circuit: Circuit) -> Bound<CircuitWrapper> {
@@ -36,7 +45,7 @@ fn circuitpy_from_circuitru(py: Python, circuit: Circuit) -> Bound<CircuitWrappe
 fn test_qasm_file_to_circuit_correct() {
     let file = std::env::current_dir().unwrap().join("tests/input.qasm");
 
-    let circuit = qasm_file_to_circuit(file.to_str().unwrap());
+    let circuit = qasm_file_to_circuit(file.to_str().unwrap(), None, None, None, None);
 
     assert!(circuit.is_ok());
 
@@ -62,7 +71,7 @@ fn test_qasm_file_to_circuit_correct() {
 fn test_qasm_file_to_circuit_file_error() {
     pyo3::prepare_freethreaded_python();
     Python::with_gil(|py| {
-        let result = qasm_file_to_circuit("test");
+        let result = qasm_file_to_circuit("test", None, None, None, None);
         assert!(result.is_err());
         assert!(result
             .as_ref()
@@ -74,3 +83,168 @@ fn test_qasm_file_to_circuit_file_error() {
             .is_instance_of::<PyFileNotFoundError>(py));
     })
 }
+
+/// Test that parsing errors are raised as QasmParseError with the position of the problem
+#[test]
+fn test_qasm_str_to_circuit_parse_error() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let result =
+            qasm_str_to_circuit("OPENQASM 2.0;\nqreg q[1];\nx q[1];", None, None, None, None);
+        let error = result.unwrap_err();
+        assert!(error.is_instance_of::<QasmParseError>(py));
+        assert!(error.is_instance_of::<PyValueError>(py));
+        let value = error.value(py);
+        assert_eq!(
+            value.getattr("line").unwrap().extract::<usize>().unwrap(),
+            3
+        );
+        assert_eq!(
+            value.getattr("column").unwrap().extract::<usize>().unwrap(),
+            3
+        );
+    })
+}
+
+/// Test that included files are looked up in the include paths
+#[test]
+fn test_qasm_str_to_circuit_include_paths() {
+    let include_directory = std::env::current_dir()
+        .unwrap()
+        .join("../roqoqo-qasm/tests/includes/lib");
+    let input = "OPENQASM 2.0;\ninclude \"search_lib.inc\";\nqreg q[2];\nentangle q[0], q[1];";
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let error = qasm_str_to_circuit(input, None, None, None, None).unwrap_err();
+        assert!(error.is_instance_of::<QasmParseError>(py));
+
+        let circuit = qasm_str_to_circuit(
+            input,
+            Some(vec![include_directory.to_str().unwrap().to_owned()]),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(circuit.internal.definitions().len(), 1);
+        assert_eq!(circuit.internal.operations().len(), 1);
+    })
+}
+
+/// Test that the values of input parameters are used for the InputSymbolic definitions
+#[test]
+fn test_qasm_str_to_circuit_input_values() {
+    let input = "OPENQASM 3.0;\ninput float theta;\nqubit[1] q;\nrx(theta) q[0];";
+
+    pyo3::prepare_freethreaded_python();
+    let circuit = qasm_str_to_circuit(
+        input,
+        None,
+        Some(HashMap::from([("theta".to_owned(), 0.5)])),
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        circuit.internal.definitions()[0],
+        Operation::from(InputSymbolic::new("theta".to_owned(), 0.5))
+    );
+
+    let circuit = qasm_str_to_circuit(input, None, None, None, None).unwrap();
+    assert_eq!(
+        circuit.internal.definitions()[0],
+        Operation::from(InputSymbolic::new("theta".to_owned(), 0.0))
+    );
+}
+
+/// Test that durations in units of dt are read with the given length of dt
+#[test]
+fn test_qasm_str_to_circuit_dt() {
+    let input = "OPENQASM 3.0;\nqubit[1] q;\ndelay[40dt] q[0];";
+
+    pyo3::prepare_freethreaded_python();
+    let circuit = qasm_str_to_circuit(input, None, None, Some(0.25e-9), None).unwrap();
+    assert_eq!(
+        circuit.internal[0],
+        Operation::from(PragmaSleep::new(vec![0], 1e-8.into()))
+    );
+
+    assert!(qasm_str_to_circuit(input, None, None, None, None).is_err());
+}
+
+/// Test that QASM input is translated into a QuantumProgram with the declared inputs as parameters
+#[test]
+fn test_qasm_str_to_quantum_program() {
+    let input = "OPENQASM 3.0;\ninput float theta;\nqubit[1] q;\nbit[1] ro;\nrx(theta) q[0];\nro[0] = measure q[0];";
+
+    pyo3::prepare_freethreaded_python();
+    let program = qasm_str_to_quantum_program(input, None, None, None).unwrap();
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_owned(), 1, true);
+    circuit += RotateX::new(0, "theta".into());
+    circuit += MeasureQubit::new(0, "ro".to_owned(), 0);
+    assert_eq!(
+        program.internal,
+        QuantumProgram::ClassicalRegister {
+            measurement: ClassicalRegister {
+                constant_circuit: None,
+                circuits: vec![circuit],
+            },
+            input_parameter_names: vec!["theta".to_owned()],
+        }
+    );
+
+    Python::with_gil(|py| {
+        let error =
+            qasm_str_to_quantum_program("OPENQASM 3.0;\nqubit[1] q;\nx q[0];", None, None, None)
+                .unwrap_err();
+        assert!(error.is_instance_of::<QasmParseError>(py));
+        // A missing measurement has no position in the input
+        assert!(error.value(py).getattr("line").unwrap().is_none());
+        assert!(error.value(py).getattr("column").unwrap().is_none());
+    });
+}
+
+/// Test that a QASM file is translated into a QuantumProgram
+#[test]
+fn test_qasm_file_to_quantum_program() {
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("../roqoqo-qasm/tests/input.qasm");
+
+    pyo3::prepare_freethreaded_python();
+    let program = qasm_file_to_quantum_program(file.to_str().unwrap(), None, None, None).unwrap();
+    match program.internal {
+        QuantumProgram::ClassicalRegister {
+            measurement,
+            input_parameter_names,
+        } => {
+            assert_eq!(measurement.circuits.len(), 1);
+            assert!(input_parameter_names.is_empty());
+        }
+        _ => panic!("Expected a ClassicalRegister QuantumProgram"),
+    }
+
+    Python::with_gil(|py| {
+        let error =
+            qasm_file_to_quantum_program("does_not_exist.qasm", None, None, None).unwrap_err();
+        assert!(error.is_instance_of::<PyFileNotFoundError>(py));
+    });
+}
+
+/// Test that calls of user-defined gates are inlined when requested
+#[test]
+fn test_qasm_str_to_circuit_inline_gates() {
+    let input = "OPENQASM 2.0;\nqreg q[2];\ngate bell a, b { h a; cx a, b; }\nbell q[0], q[1];";
+
+    pyo3::prepare_freethreaded_python();
+    let circuit = qasm_str_to_circuit(input, None, None, None, Some(true)).unwrap();
+    let mut expected = Circuit::new();
+    expected += Hadamard::new(0);
+    expected += CNOT::new(0, 1);
+    assert_eq!(circuit.internal, expected);
+
+    let circuit = qasm_str_to_circuit(input, None, None, None, None).unwrap();
+    assert_eq!(circuit.internal.definitions().len(), 1);
+}
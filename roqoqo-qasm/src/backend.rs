@@ -10,13 +10,15 @@
 // express or implied. See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::interface::modifier_form;
 use crate::{
-    call_operation, gate_definition, VariableGatherer, ALLOWED_OPERATIONS,
-    NO_DEFINITION_REQUIRED_OPERATIONS,
+    call_operation_with_options, gate_definition, gate_definition_with_options, EmitterOptions,
+    VariableGatherer, ALLOWED_OPERATIONS, NO_DEFINITION_REQUIRED_OPERATIONS,
 };
 use qoqo_calculator::CalculatorFloat;
 use roqoqo::operations::*;
 use roqoqo::{Circuit, RoqoqoBackendError};
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
@@ -24,17 +26,38 @@ use std::path::PathBuf;
 use std::str::FromStr;
 
 /// Checks for new declarations in the circuit.
+///
+/// The bodies of nested circuits are checked as well. GateDefinitions are skipped, they are
+/// written beforehand in the order given by [dependency_order].
 fn process_operation_circuit<'a>(
     circuit: impl Iterator<Item = &'a Operation>,
     qasm_version: QasmVersion,
+    options: &EmitterOptions,
     already_seen_declarations: &mut Vec<String>,
     declarations: &mut String,
 ) -> Result<(), RoqoqoBackendError> {
     for operation in circuit {
+        if let Operation::GateDefinition(_) = operation {
+            continue;
+        }
+        if let Some(body) = nested_circuit(operation) {
+            process_operation_circuit(
+                body.iter(),
+                qasm_version,
+                options,
+                already_seen_declarations,
+                declarations,
+            )?;
+        }
+        let operation = &defined_operation(operation, qasm_version, options);
         if !already_seen_declarations.contains(&operation.hqslang().to_string()) {
             already_seen_declarations.push(operation.hqslang().to_string());
-            declarations.push_str(&gate_definition(operation, qasm_version)?);
-            if !declarations.is_empty() {
+            let declaration = gate_definition_with_options(operation, qasm_version, options)?;
+            if is_included(&declaration, qasm_version, options) {
+                continue;
+            }
+            declarations.push_str(&declaration);
+            if !declaration.is_empty() {
                 declarations.push('\n');
             }
         }
@@ -42,6 +65,140 @@ fn process_operation_circuit<'a>(
     Ok(())
 }
 
+/// Returns whether a gate definition is provided by the library included in the minimal header.
+fn is_included(definition: &str, qasm_version: QasmVersion, options: &EmitterOptions) -> bool {
+    let Some((_, gates)) = options.minimal_header(qasm_version) else {
+        return false;
+    };
+    definition
+        .strip_prefix("gate ")
+        .and_then(|definition| definition.split(['(', ' ']).next())
+        .is_some_and(|name| gates.contains(&name))
+}
+
+/// Returns the circuit nested in an operation, if it has one.
+fn nested_circuit(operation: &Operation) -> Option<&Circuit> {
+    match operation {
+        Operation::GateDefinition(op) => Some(op.circuit()),
+        Operation::PragmaConditional(op) => Some(op.circuit()),
+        Operation::PragmaLoop(op) => Some(op.circuit()),
+        Operation::PragmaControlledCircuit(op) => Some(op.circuit()),
+        _ => None,
+    }
+}
+
+/// Collects the GateDefinitions of a circuit, including the ones nested in other operations.
+///
+/// Only the first definition of each gate is kept.
+fn collect_gate_definitions<'a>(
+    circuit: impl Iterator<Item = &'a Operation>,
+    gate_definitions: &mut Vec<&'a GateDefinition>,
+) {
+    for operation in circuit {
+        if let Operation::GateDefinition(gate_definition) = operation {
+            if !gate_definitions
+                .iter()
+                .any(|known| known.name() == gate_definition.name())
+            {
+                gate_definitions.push(gate_definition);
+            }
+        }
+        if let Some(body) = nested_circuit(operation) {
+            collect_gate_definitions(body.iter(), gate_definitions);
+        }
+    }
+}
+
+/// Collects the names of the gates called in a circuit, including the calls in nested loops,
+/// conditions and controlled circuits.
+///
+/// Calls in nested GateDefinitions are calls of the nested gate and are not collected.
+fn collect_called_gates<'a>(circuit: &'a Circuit, called_gates: &mut Vec<&'a str>) {
+    for operation in circuit.iter() {
+        match operation {
+            Operation::CallDefinedGate(call) => called_gates.push(call.gate_name()),
+            Operation::GateDefinition(_) => continue,
+            _ => {
+                if let Some(body) = nested_circuit(operation) {
+                    collect_called_gates(body, called_gates);
+                }
+            }
+        }
+    }
+}
+
+/// Orders GateDefinitions so that every gate is defined after the gates it calls.
+///
+/// Gates that do not depend on each other keep their order.
+///
+/// # Returns
+///
+/// * `Ok(Vec<&GateDefinition>)` - The GateDefinitions in dependency order.
+/// * `RoqoqoBackendError::GenericError` - A gate calls itself, directly or through other gates.
+fn dependency_order<'a>(
+    gate_definitions: &[&'a GateDefinition],
+) -> Result<Vec<&'a GateDefinition>, RoqoqoBackendError> {
+    /// Adds a definition after the definitions it depends on, `path` holds the gates being added.
+    fn visit<'a>(
+        gate_definition: &'a GateDefinition,
+        gate_definitions: &[&'a GateDefinition],
+        path: &mut Vec<&'a str>,
+        ordered: &mut Vec<&'a GateDefinition>,
+    ) -> Result<(), RoqoqoBackendError> {
+        if ordered
+            .iter()
+            .any(|known| known.name() == gate_definition.name())
+        {
+            return Ok(());
+        }
+        if let Some(position) = path.iter().position(|&name| name == gate_definition.name()) {
+            let mut cycle = path[position..].to_vec();
+            cycle.push(gate_definition.name());
+            return Err(RoqoqoBackendError::GenericError {
+                msg: format!(
+                    "GateDefinition {} is recursive: {}",
+                    gate_definition.name(),
+                    cycle.join(" -> ")
+                ),
+            });
+        }
+        path.push(gate_definition.name());
+        let mut called_gates: Vec<&str> = vec![];
+        collect_called_gates(gate_definition.circuit(), &mut called_gates);
+        for called_gate in called_gates {
+            if let Some(dependency) = gate_definitions
+                .iter()
+                .find(|known| known.name() == called_gate)
+            {
+                visit(dependency, gate_definitions, path, ordered)?;
+            }
+        }
+        path.pop();
+        ordered.push(gate_definition);
+        Ok(())
+    }
+
+    let mut ordered: Vec<&GateDefinition> = vec![];
+    for gate_definition in gate_definitions {
+        visit(gate_definition, gate_definitions, &mut vec![], &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+/// Returns the operation whose gate definition is needed to write an operation.
+///
+/// Operations written with gate modifiers only need the definition of the modified gate.
+fn defined_operation<'a>(
+    operation: &'a Operation,
+    qasm_version: QasmVersion,
+    options: &EmitterOptions,
+) -> Cow<'a, Operation> {
+    match modifier_form(operation) {
+        Some((_, _, gate)) if options.gate_modifiers(qasm_version) => Cow::Owned(gate),
+        _ => Cow::Borrowed(operation),
+    }
+}
+
 /// QASM backend to qoqo
 ///
 /// This backend to roqoqo produces QASM output which can be exported.
@@ -65,6 +222,8 @@ pub struct Backend {
     qubit_register_name: String,
     /// Which version of OpenQASM (2.0 or 3.0) to use
     qasm_version: QasmVersion,
+    /// Settings of the emitter
+    emitter_options: EmitterOptions,
 }
 
 impl Backend {
@@ -90,9 +249,25 @@ impl Backend {
         Ok(Self {
             qubit_register_name: qubit_reg,
             qasm_version: qasm_v,
+            emitter_options: EmitterOptions::default(),
         })
     }
 
+    /// Sets the settings used when writing QASM.
+    ///
+    /// # Arguments
+    ///
+    /// * `emitter_options` - The settings of the emitter.
+    pub fn with_emitter_options(mut self, emitter_options: EmitterOptions) -> Self {
+        self.emitter_options = emitter_options;
+        self
+    }
+
+    /// Returns the settings used when writing QASM.
+    pub fn emitter_options(&self) -> &EmitterOptions {
+        &self.emitter_options
+    }
+
     /// Translates an iterator over operations to a valid QASM string.
     ///
     ///
@@ -119,6 +294,7 @@ impl Backend {
             "CNOT".to_string(),
         ];
         let mut variable_gatherer = VariableGatherer::new();
+        let mut declared_inputs: Vec<String> = vec![];
 
         // Appending QASM version
         let mut qasm_string = String::from("OPENQASM ");
@@ -127,30 +303,69 @@ impl Backend {
             QasmVersion::V3point0(_) => qasm_string.push_str("3.0;\n\n"),
         }
 
-        // Appending definitions that are always needed (some depend on QASM version)
-        definitions.push_str("gate u3(theta,phi,lambda) q { U(theta,phi,lambda) q; }\n");
-        definitions.push_str("gate u2(phi,lambda) q { U(pi/2,phi,lambda) q; }\n");
-        definitions.push_str("gate u1(lambda) q { U(0,0,lambda) q; }\n");
-        definitions.push_str(&gate_definition(
-            &Operation::from(RotateX::new(0, CalculatorFloat::from(0.0))),
-            self.qasm_version,
-        )?);
-        definitions.push('\n');
-        definitions.push_str(&gate_definition(
-            &Operation::from(RotateY::new(0, CalculatorFloat::from(0.0))),
-            self.qasm_version,
-        )?);
-        definitions.push('\n');
-        definitions.push_str(&gate_definition(
-            &Operation::from(RotateZ::new(0, CalculatorFloat::from(0.0))),
-            self.qasm_version,
-        )?);
-        definitions.push('\n');
-        definitions.push_str(&gate_definition(
-            &Operation::from(CNOT::new(0, 1)),
-            self.qasm_version,
-        )?);
-        definitions.push_str("\n\n");
+        // Appending definitions that are always needed (some depend on QASM version), unless
+        // the minimal header includes them
+        if self
+            .emitter_options
+            .minimal_header(self.qasm_version)
+            .is_none()
+        {
+            definitions.push_str("gate u3(theta,phi,lambda) q { U(theta,phi,lambda) q; }\n");
+            definitions.push_str("gate u2(phi,lambda) q { U(pi/2,phi,lambda) q; }\n");
+            definitions.push_str("gate u1(lambda) q { U(0,0,lambda) q; }\n");
+            definitions.push_str(&gate_definition(
+                &Operation::from(RotateX::new(0, CalculatorFloat::from(0.0))),
+                self.qasm_version,
+            )?);
+            definitions.push('\n');
+            definitions.push_str(&gate_definition(
+                &Operation::from(RotateY::new(0, CalculatorFloat::from(0.0))),
+                self.qasm_version,
+            )?);
+            definitions.push('\n');
+            definitions.push_str(&gate_definition(
+                &Operation::from(RotateZ::new(0, CalculatorFloat::from(0.0))),
+                self.qasm_version,
+            )?);
+            definitions.push('\n');
+            definitions.push_str(&gate_definition(
+                &Operation::from(CNOT::new(0, 1)),
+                self.qasm_version,
+            )?);
+            definitions.push_str("\n\n");
+        }
+
+        // Appending the GateDefinitions, each after the gates it calls
+        let circuit: Vec<&Operation> = circuit.collect();
+        let mut gate_definitions: Vec<&GateDefinition> = vec![];
+        collect_gate_definitions(circuit.iter().copied(), &mut gate_definitions);
+        for gate_definition in dependency_order(&gate_definitions)? {
+            process_operation_circuit(
+                gate_definition.circuit().iter(),
+                self.qasm_version,
+                &self.emitter_options,
+                &mut already_seen_definitions,
+                &mut definitions,
+            )?;
+            if !already_seen_definitions.contains(&gate_definition.name().to_owned()) {
+                already_seen_definitions.push(gate_definition.name().to_owned());
+                let operation = Operation::from(gate_definition.clone());
+                definitions.push_str(&gate_definition_with_options(
+                    &operation,
+                    self.qasm_version,
+                    &self.emitter_options,
+                )?);
+                definitions.push('\n');
+                // Registers nested subroutines, whose calls are written differently
+                call_operation_with_options(
+                    &operation,
+                    &self.qubit_register_name,
+                    self.qasm_version,
+                    &self.emitter_options,
+                    &mut Some(&mut variable_gatherer),
+                )?;
+            }
+        }
 
         // Main loop over the circuit
         for op in circuit {
@@ -163,60 +378,52 @@ impl Backend {
                     })
             }
 
-            // Appending gate definition if not already seen before
-            if !already_seen_definitions.contains(&op.hqslang().to_string()) {
-                let mut continue_process = false;
-                if let Operation::GateDefinition(gate_definition) = op {
-                    if !already_seen_definitions.contains(gate_definition.name()) {
-                        already_seen_definitions.push(gate_definition.name().to_owned());
-                        continue_process = true;
-                    }
-                } else {
-                    already_seen_definitions.push(op.hqslang().to_string());
-                    continue_process = true;
+            // Appending the definitions needed by nested circuits and the operation itself
+            if !matches!(op, Operation::GateDefinition(_)) {
+                if let Some(body) = nested_circuit(op) {
+                    process_operation_circuit(
+                        body.iter(),
+                        self.qasm_version,
+                        &self.emitter_options,
+                        &mut already_seen_definitions,
+                        &mut definitions,
+                    )?;
                 }
-
-                if continue_process {
-                    match op {
-                        Operation::GateDefinition(gate_definition) => process_operation_circuit(
-                            gate_definition.circuit().iter(),
-                            self.qasm_version,
-                            &mut already_seen_definitions,
-                            &mut definitions,
-                        )?,
-                        Operation::PragmaConditional(pragma_conditional) => {
-                            process_operation_circuit(
-                                pragma_conditional.circuit().iter(),
-                                self.qasm_version,
-                                &mut already_seen_definitions,
-                                &mut definitions,
-                            )?
-                        }
-                        Operation::PragmaLoop(pragma_loop) => process_operation_circuit(
-                            pragma_loop.circuit().iter(),
-                            self.qasm_version,
-                            &mut already_seen_definitions,
-                            &mut definitions,
-                        )?,
-                        _ => {}
+                let defined_op = defined_operation(op, self.qasm_version, &self.emitter_options);
+                if !already_seen_definitions.contains(&defined_op.hqslang().to_string()) {
+                    already_seen_definitions.push(defined_op.hqslang().to_string());
+                    let mut definition = gate_definition_with_options(
+                        &defined_op,
+                        self.qasm_version,
+                        &self.emitter_options,
+                    )?;
+                    if is_included(&definition, self.qasm_version, &self.emitter_options) {
+                        definition.clear();
                     }
-                    definitions.push_str(&gate_definition(op, self.qasm_version)?);
-                    if !definitions.is_empty()
-                        && !NO_DEFINITION_REQUIRED_OPERATIONS.contains(&op.hqslang())
+                    definitions.push_str(&definition);
+                    if !definition.is_empty()
+                        && !NO_DEFINITION_REQUIRED_OPERATIONS.contains(&defined_op.hqslang())
                     {
                         definitions.push('\n');
                     }
                 }
             }
+            if let Operation::InputSymbolic(input) = op {
+                declared_inputs.push(input.name().to_owned());
+            }
             // Appending operation QASM instruction
-            data.push_str(&call_operation(
+            let instruction = call_operation_with_options(
                 op,
                 &self.qubit_register_name,
                 self.qasm_version,
+                &self.emitter_options,
                 &mut Some(&mut variable_gatherer),
-            )?);
+            )?;
+            data.push_str(&instruction);
 
-            if !data.is_empty() && !ALLOWED_OPERATIONS.contains(&op.hqslang()) {
+            if !data.is_empty()
+                && (!instruction.is_empty() || !ALLOWED_OPERATIONS.contains(&op.hqslang()))
+            {
                 data.push('\n');
             }
         }
@@ -227,12 +434,36 @@ impl Backend {
             QasmVersion::V2point0(Qasm2Dialect::Qulacs) => {
                 qasm_string.push_str("include \"qelib1.inc\";\n\n")
             }
-            _ => qasm_string.push_str(definitions.as_str()),
+            _ => {
+                if let Some((library, _)) = self.emitter_options.minimal_header(self.qasm_version) {
+                    qasm_string.push_str(&format!("include \"{library}\";\n"));
+                    if !definitions.is_empty() {
+                        qasm_string.push('\n');
+                    }
+                }
+                qasm_string.push_str(definitions.as_str())
+            }
         };
 
         if let QasmVersion::V3point0(_) = self.qasm_version {
-            if !variable_gatherer.variables.is_empty() {
+            // Parameters written as InputSymbolic are already declared
+            variable_gatherer
+                .variables
+                .retain(|var| !declared_inputs.contains(var));
+            // Loop bounds are declared as integers
+            variable_gatherer
+                .integer_variables
+                .retain(|var| !declared_inputs.contains(var));
+            variable_gatherer
+                .variables
+                .retain(|var| !variable_gatherer.integer_variables.contains(var));
+            if !variable_gatherer.variables.is_empty()
+                || !variable_gatherer.integer_variables.is_empty()
+            {
                 qasm_string.push('\n');
+                for var in &variable_gatherer.integer_variables {
+                    qasm_string.push_str(format!("input uint {var};\n").as_str());
+                }
                 for var in &variable_gatherer.variables {
                     qasm_string.push_str(format!("input angle[32] {var};\n").as_str());
                 }
@@ -347,7 +578,7 @@ impl Backend {
     /// * `Ok(Circuit)` - The translated qoqo Circuit.
     /// * `RoqoqoBackendError::GenericError` - Error encountered while parsing.
     pub fn file_to_circuit(&self, file: File) -> Result<Circuit, RoqoqoBackendError> {
-        crate::file_to_circuit(file)
+        Ok(crate::file_to_circuit(file)?)
     }
 
     /// Translates a QASM string into a qoqo Circuit instance.
@@ -361,7 +592,7 @@ impl Backend {
     /// * `Ok(Circuit)` - The translated qoqo Circuit.
     /// * `RoqoqoBackendError::GenericError` - Error encountered while parsing.
     pub fn string_to_circuit(&self, input: &str) -> Result<Circuit, RoqoqoBackendError> {
-        crate::string_to_circuit(input)
+        Ok(crate::string_to_circuit(input)?)
     }
 }
 
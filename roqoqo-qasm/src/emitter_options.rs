@@ -0,0 +1,310 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Settings of the roqoqo-qasm emitter.
+
+use crate::{Qasm2Dialect, Qasm3Dialect, QasmVersion};
+use roqoqo::RoqoqoBackendError;
+use std::str::FromStr;
+
+/// Units of the durations of OpenQASM 3.0 `delay` and `box` statements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TimeUnit {
+    /// `ns`
+    Nanoseconds,
+    /// `us`, also read as `µs`
+    Microseconds,
+    /// `ms`
+    Milliseconds,
+    /// `s`
+    #[default]
+    Seconds,
+    /// `dt`, the sample time of the device, whose length in seconds has to be set explicitly.
+    Dt,
+}
+
+impl TimeUnit {
+    /// Returns the unit of a duration literal suffix.
+    pub(crate) fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "ns" => Some(TimeUnit::Nanoseconds),
+            "us" | "µs" => Some(TimeUnit::Microseconds),
+            "ms" => Some(TimeUnit::Milliseconds),
+            "s" => Some(TimeUnit::Seconds),
+            "dt" => Some(TimeUnit::Dt),
+            _ => None,
+        }
+    }
+
+    /// Returns the suffix of duration literals in this unit.
+    pub(crate) fn suffix(&self) -> &'static str {
+        match self {
+            TimeUnit::Nanoseconds => "ns",
+            TimeUnit::Microseconds => "us",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Seconds => "s",
+            TimeUnit::Dt => "dt",
+        }
+    }
+
+    /// Returns the power of ten of the unit in seconds, or None for `dt`.
+    fn decimal_exponent(self) -> Option<i32> {
+        match self {
+            TimeUnit::Nanoseconds => Some(-9),
+            TimeUnit::Microseconds => Some(-6),
+            TimeUnit::Milliseconds => Some(-3),
+            TimeUnit::Seconds => Some(0),
+            TimeUnit::Dt => None,
+        }
+    }
+
+    /// Converts the number of a duration literal in this unit to seconds.
+    ///
+    /// For the decimal units the exponent of the number is shifted, so that the duration is the
+    /// float closest to the written decimal number of seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - The number of the duration literal, such as `2.5` in `2.5us`.
+    /// * `dt` - The length of `dt` in seconds, if known.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(f64)` - The duration in seconds.
+    /// * `None` - The number is invalid, or the unit is `dt` and its length is not known.
+    pub(crate) fn to_seconds(self, number: &str, dt: Option<f64>) -> Option<f64> {
+        match self.decimal_exponent() {
+            Some(shift) => {
+                let (mantissa, exponent) = match number.split_once(['e', 'E']) {
+                    Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+                    None => (number, 0),
+                };
+                format!("{mantissa}e{}", exponent + shift).parse().ok()
+            }
+            None => Some(number.parse::<f64>().ok()? * dt?),
+        }
+    }
+
+    /// Converts a duration in seconds to this unit.
+    ///
+    /// The result is rounded to 12 significant digits, so that the rounding errors of the
+    /// conversion do not show up in the written durations.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - The duration in seconds.
+    /// * `dt` - The length of `dt` in seconds, if known.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(f64)` - The duration in this unit.
+    /// * `None` - The unit is `dt`, but its length is not known.
+    pub(crate) fn to_unit(self, seconds: f64, dt: Option<f64>) -> Option<f64> {
+        let value = match self {
+            TimeUnit::Nanoseconds => seconds * 1e9,
+            TimeUnit::Microseconds => seconds * 1e6,
+            TimeUnit::Milliseconds => seconds * 1e3,
+            TimeUnit::Seconds => seconds,
+            TimeUnit::Dt => seconds / dt?,
+        };
+        format!("{value:.11e}").parse().ok()
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = RoqoqoBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeUnit::from_suffix(s).ok_or_else(|| RoqoqoBackendError::GenericError {
+            msg: format!("Time unit is none of ns, us, ms, s and dt: {s}"),
+        })
+    }
+}
+
+/// The gates defined in the original `qelib1.inc` of OpenQASM 2.0.
+const QELIB1_GATES: &[&str] = &[
+    "u3", "u2", "u1", "cx", "id", "u0", "x", "y", "z", "h", "s", "sdg", "t", "tdg", "rx", "ry",
+    "rz", "cz", "cy", "ch", "ccx", "crz", "cu1", "cu3",
+];
+
+/// The gates defined in `stdgates.inc` of OpenQASM 3.0.
+const STDGATES_GATES: &[&str] = &[
+    "p", "x", "y", "z", "h", "s", "sdg", "t", "tdg", "sx", "rx", "ry", "rz", "cx", "cy", "cz",
+    "cp", "crx", "cry", "crz", "ch", "swap", "ccx", "cswap", "cu", "CX", "phase", "cphase", "id",
+    "u1", "u2", "u3",
+];
+
+/// Settings controlling how roqoqo operations are written as QASM.
+///
+/// Settings that are not set explicitly use the default of the QASM version.
+#[derive(Debug, Clone, Default)]
+pub struct EmitterOptions {
+    /// Whether measurements are written as `ro[0] = measure q[0];`.
+    measurement_assignment: Option<bool>,
+    /// Whether controlled and inverse gates are written with gate modifiers.
+    gate_modifiers: bool,
+    /// The unit of the durations of `delay` statements.
+    time_unit: TimeUnit,
+    /// The length of the sample time `dt` in seconds.
+    dt: Option<f64>,
+    /// Whether the standard gate library is included instead of defining the standard gates.
+    minimal_header: bool,
+}
+
+// The lengths of dt are compared by their bits, so that the comparison is an equivalence
+impl PartialEq for EmitterOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.measurement_assignment == other.measurement_assignment
+            && self.gate_modifiers == other.gate_modifiers
+            && self.time_unit == other.time_unit
+            && self.dt.map(f64::to_bits) == other.dt.map(f64::to_bits)
+            && self.minimal_header == other.minimal_header
+    }
+}
+
+impl Eq for EmitterOptions {}
+
+impl EmitterOptions {
+    /// Creates the default emitter settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether measurements are written in the assignment form of OpenQASM 3.0.
+    ///
+    /// With the assignment form, measurements are written as `ro[0] = measure q[0];` and
+    /// `ro = measure q;` instead of `measure q[0] -> ro[0];`. The setting has no effect for
+    /// OpenQASM 2.0. By default the assignment form is only used for the 3.0Braket dialect.
+    ///
+    /// # Arguments
+    ///
+    /// * `measurement_assignment` - Whether to use the assignment form.
+    pub fn with_measurement_assignment(mut self, measurement_assignment: bool) -> Self {
+        self.measurement_assignment = Some(measurement_assignment);
+        self
+    }
+
+    /// Returns whether measurements are written in the assignment form.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm_version` - The QASM version the measurements are written in.
+    pub fn measurement_assignment(&self, qasm_version: QasmVersion) -> bool {
+        match qasm_version {
+            QasmVersion::V2point0(_) => false,
+            QasmVersion::V3point0(dialect) => self
+                .measurement_assignment
+                .unwrap_or(dialect == Qasm3Dialect::Braket),
+        }
+    }
+
+    /// Sets whether controlled and inverse gates are written with the gate modifiers of
+    /// OpenQASM 3.0.
+    ///
+    /// With gate modifiers, operations such as ControlledPauliZ or InvSGate are written as
+    /// `ctrl @ z q[0],q[1];` and `inv @ s q[0];`, so that only the definitions of the modified
+    /// gates are needed. The setting has no effect for OpenQASM 2.0 and is disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate_modifiers` - Whether to use gate modifiers.
+    pub fn with_gate_modifiers(mut self, gate_modifiers: bool) -> Self {
+        self.gate_modifiers = gate_modifiers;
+        self
+    }
+
+    /// Returns whether controlled and inverse gates are written with gate modifiers.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm_version` - The QASM version the gates are written in.
+    pub fn gate_modifiers(&self, qasm_version: QasmVersion) -> bool {
+        matches!(qasm_version, QasmVersion::V3point0(_)) && self.gate_modifiers
+    }
+
+    /// Sets the unit of the durations written for PragmaSleep in OpenQASM 3.0.
+    ///
+    /// PragmaSleep times are in seconds and are written as `delay[100ns] q[0];` in the 3.0Vanilla
+    /// and 3.0Braket dialects. The default unit is `TimeUnit::Seconds`. Durations in units of
+    /// `TimeUnit::Dt` need the length of `dt` set with `with_dt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_unit` - The unit of the written durations.
+    pub fn with_time_unit(mut self, time_unit: TimeUnit) -> Self {
+        self.time_unit = time_unit;
+        self
+    }
+
+    /// Returns the unit of the durations written for PragmaSleep.
+    pub fn time_unit(&self) -> TimeUnit {
+        self.time_unit
+    }
+
+    /// Sets the length of the sample time `dt` of the device in seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` - The length of `dt` in seconds.
+    pub fn with_dt(mut self, dt: f64) -> Self {
+        self.dt = Some(dt);
+        self
+    }
+
+    /// Returns the length of the sample time `dt` in seconds, if set.
+    pub fn dt(&self) -> Option<f64> {
+        self.dt
+    }
+
+    /// Sets whether the header includes the standard gate library instead of defining gates.
+    ///
+    /// With the minimal header, `include "stdgates.inc";` is written for OpenQASM 3.0 and
+    /// `include "qelib1.inc";` for OpenQASM 2.0, and only the gates that the circuit uses and
+    /// the library does not provide are defined. The setting has no effect for the 3.0Braket
+    /// dialect, which writes no definitions, and the 2.0Qulacs dialect, which always includes
+    /// `qelib1.inc`. It is disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `minimal_header` - Whether to write the minimal header.
+    pub fn with_minimal_header(mut self, minimal_header: bool) -> Self {
+        self.minimal_header = minimal_header;
+        self
+    }
+
+    /// Returns the gates of the standard library included by the minimal header.
+    ///
+    /// For OpenQASM 2.0 only the gates of the original `qelib1.inc` are listed, which every
+    /// parser provides.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm_version` - The QASM version the header is written in.
+    ///
+    /// # Returns
+    ///
+    /// * `Some((file_name, gates))` - The included file and the names of the gates it provides.
+    /// * `None` - No minimal header is written.
+    pub fn minimal_header(
+        &self,
+        qasm_version: QasmVersion,
+    ) -> Option<(&'static str, &'static [&'static str])> {
+        match qasm_version {
+            _ if !self.minimal_header => None,
+            QasmVersion::V2point0(Qasm2Dialect::Vanilla) => Some(("qelib1.inc", QELIB1_GATES)),
+            QasmVersion::V3point0(Qasm3Dialect::Vanilla | Qasm3Dialect::Roqoqo) => {
+                Some(("stdgates.inc", STDGATES_GATES))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Error and warning types of the roqoqo-qasm parser.
+
+use roqoqo::RoqoqoBackendError;
+use std::fmt;
+
+/// The kind of problem encountered while parsing a QASM file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QasmParseErrorKind {
+    /// The input does not follow the OpenQASM grammar.
+    Syntax,
+    /// A register, gate or gate argument is used without being declared.
+    Undeclared,
+    /// A register or gate is declared a second time.
+    Redeclaration,
+    /// A qubit index lies outside of its register.
+    IndexOutOfRange,
+    /// A gate is called with the wrong number of qubits or parameters.
+    InvalidGateCall,
+    /// A parameter expression or number could not be evaluated.
+    InvalidExpression,
+    /// The statement is valid OpenQASM but has no roqoqo equivalent.
+    Unsupported,
+    /// An included file could not be found or includes itself.
+    Include,
+    /// The input could not be read.
+    Io,
+    /// The input contains no measurement, which a QuantumProgram needs.
+    MissingMeasurement,
+}
+
+impl fmt::Display for QasmParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            QasmParseErrorKind::Syntax => "Syntax error",
+            QasmParseErrorKind::Undeclared => "Undeclared identifier",
+            QasmParseErrorKind::Redeclaration => "Redeclaration",
+            QasmParseErrorKind::IndexOutOfRange => "Index out of range",
+            QasmParseErrorKind::InvalidGateCall => "Invalid gate call",
+            QasmParseErrorKind::InvalidExpression => "Invalid expression",
+            QasmParseErrorKind::Unsupported => "Unsupported statement",
+            QasmParseErrorKind::Include => "Include error",
+            QasmParseErrorKind::Io => "Input error",
+            QasmParseErrorKind::MissingMeasurement => "Missing measurement",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// Error encountered while translating a QASM file into a roqoqo Circuit.
+///
+/// The position is given as 1-based line and column of the start of the offending snippet.
+/// Errors of the kinds `Io` and `MissingMeasurement` concern the input as a whole and have no
+/// position, their line and column are 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QasmParseError {
+    /// The kind of problem.
+    kind: QasmParseErrorKind,
+    /// Description of the problem.
+    message: String,
+    /// Line of the problem in the QASM input.
+    line: usize,
+    /// Column of the problem in the QASM input.
+    column: usize,
+    /// The part of the QASM input causing the problem.
+    snippet: String,
+}
+
+impl QasmParseError {
+    /// Creates a new QasmParseError.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of problem.
+    /// * `message` - Description of the problem.
+    /// * `line` - Line of the problem in the QASM input, starting at 1.
+    /// * `column` - Column of the problem in the QASM input, starting at 1.
+    /// * `snippet` - The part of the QASM input causing the problem.
+    pub fn new(
+        kind: QasmParseErrorKind,
+        message: String,
+        line: usize,
+        column: usize,
+        snippet: String,
+    ) -> Self {
+        Self {
+            kind,
+            message,
+            line,
+            column,
+            snippet,
+        }
+    }
+
+    /// Returns the kind of problem.
+    pub fn kind(&self) -> QasmParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the line of the problem in the QASM input, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column of the problem in the QASM input, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns the part of the QASM input causing the problem.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+}
+
+impl fmt::Display for QasmParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.kind, self.message);
+        }
+        write!(
+            f,
+            "{} at line {}, column {}: {}",
+            self.kind, self.line, self.column, self.message
+        )?;
+        if !self.snippet.is_empty() {
+            write!(f, "\n    {}", self.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QasmParseError {}
+
+/// Problem encountered while translating a QASM file that did not stop the translation.
+///
+/// The position is given as 1-based line and column of the start of the offending snippet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QasmParseWarning {
+    /// Description of the problem.
+    message: String,
+    /// Line of the problem in the QASM input.
+    line: usize,
+    /// Column of the problem in the QASM input.
+    column: usize,
+    /// The part of the QASM input causing the problem.
+    snippet: String,
+}
+
+impl QasmParseWarning {
+    /// Creates a new QasmParseWarning.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Description of the problem.
+    /// * `line` - Line of the problem in the QASM input, starting at 1.
+    /// * `column` - Column of the problem in the QASM input, starting at 1.
+    /// * `snippet` - The part of the QASM input causing the problem.
+    pub fn new(message: String, line: usize, column: usize, snippet: String) -> Self {
+        Self {
+            message,
+            line,
+            column,
+            snippet,
+        }
+    }
+
+    /// Returns the description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the line of the problem in the QASM input, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column of the problem in the QASM input, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns the part of the QASM input causing the problem.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+}
+
+impl fmt::Display for QasmParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Warning at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )?;
+        if !self.snippet.is_empty() {
+            write!(f, "\n    {}", self.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<QasmParseError> for RoqoqoBackendError {
+    fn from(error: QasmParseError) -> Self {
+        RoqoqoBackendError::GenericError {
+            msg: format!("Error during conversion: {error}"),
+        }
+    }
+}
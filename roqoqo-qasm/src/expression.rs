@@ -0,0 +1,345 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Parameter expressions of QASM gate calls.
+
+use crate::Rule;
+use pest::iterators::Pair;
+use pest::pratt_parser::{Assoc, Op, PrattParser};
+use qoqo_calculator::CalculatorFloat;
+use std::collections::HashMap;
+use std::f64::consts::{E, PI, TAU};
+use std::sync::OnceLock;
+
+/// Binary operators of parameter expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOperator {
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Subtract,
+    /// `a * b`
+    Multiply,
+    /// `a / b`, truncated for integers in OpenQASM 3.0.
+    Divide,
+    /// `a % b`, with the sign of `a`.
+    Modulo,
+    /// `a ** b` and `a ^ b`
+    Power,
+}
+
+/// Functions of parameter expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Function {
+    /// `sin(x)`
+    Sin,
+    /// `cos(x)`
+    Cos,
+    /// `tan(x)`
+    Tan,
+    /// `asin(x)` or `arcsin(x)`
+    Asin,
+    /// `acos(x)` or `arccos(x)`
+    Acos,
+    /// `atan(x)` or `arctan(x)`
+    Atan,
+    /// `exp(x)`
+    Exp,
+    /// `ln(x)` or `log(x)`, the natural logarithm.
+    Ln,
+    /// `sqrt(x)`
+    Sqrt,
+    /// `floor(x)`
+    Floor,
+    /// `ceiling(x)`
+    Ceiling,
+    /// `pow(x, y)`
+    Pow,
+    /// `mod(x, y)`
+    Mod,
+}
+
+impl Function {
+    /// Returns the function with the given name.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "tan" => Some(Function::Tan),
+            "asin" | "arcsin" => Some(Function::Asin),
+            "acos" | "arccos" => Some(Function::Acos),
+            "atan" | "arctan" => Some(Function::Atan),
+            "exp" => Some(Function::Exp),
+            "ln" | "log" => Some(Function::Ln),
+            "sqrt" => Some(Function::Sqrt),
+            "floor" => Some(Function::Floor),
+            "ceiling" | "ceil" => Some(Function::Ceiling),
+            "pow" => Some(Function::Pow),
+            "mod" => Some(Function::Mod),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of arguments of the function.
+    fn number_arguments(&self) -> usize {
+        match self {
+            Function::Pow | Function::Mod => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Abstract syntax tree of a parameter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expression {
+    /// A real number or a constant such as `pi`.
+    Real(f64),
+    /// An integer literal.
+    Integer(i64),
+    /// A free identifier, such as a parameter declared with `input`.
+    Identifier(String),
+    /// `-a`
+    Negation(Box<Expression>),
+    /// A binary operation.
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    /// A function call.
+    Call(Function, Vec<Expression>),
+}
+
+/// Returns the parser resolving the precedence of the operators, from lowest to highest.
+///
+/// As in OpenQASM 3.0 and Python, the power binds tighter than the unary minus, so that
+/// `-2**2` is `-4`.
+fn pratt_parser() -> &'static PrattParser<Rule> {
+    static PRATT_PARSER: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    PRATT_PARSER.get_or_init(|| {
+        PrattParser::new()
+            .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+            .op(Op::infix(Rule::multiply, Assoc::Left)
+                | Op::infix(Rule::divide, Assoc::Left)
+                | Op::infix(Rule::modulo, Assoc::Left))
+            .op(Op::prefix(Rule::negation))
+            .op(Op::infix(Rule::power, Assoc::Right))
+    })
+}
+
+impl Expression {
+    /// Builds the expression from an `expr` pair of the grammar.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Expression)` - The expression.
+    /// * `Err(String)` - A number or function call is invalid.
+    pub(crate) fn from_pair(pair: Pair<Rule>) -> Result<Self, String> {
+        pratt_parser()
+            .map_primary(|primary| match primary.as_rule() {
+                Rule::real => primary
+                    .as_str()
+                    .parse::<f64>()
+                    .map(Expression::Real)
+                    .map_err(|_| format!("{} is not a valid number", primary.as_str())),
+                Rule::integer => primary
+                    .as_str()
+                    .parse::<i64>()
+                    .map(Expression::Integer)
+                    .map_err(|_| format!("{} is not a supported integer", primary.as_str())),
+                Rule::constant => Ok(Expression::Real(match primary.as_str() {
+                    "tau" | "τ" => TAU,
+                    "euler" | "ℇ" => E,
+                    _ => PI,
+                })),
+                Rule::function_call => {
+                    let mut inner = primary.into_inner();
+                    let name = inner.next().map(|name| name.as_str()).unwrap_or_default();
+                    let function = Function::from_name(name)
+                        .ok_or_else(|| format!("{name} is not a supported function"))?;
+                    let arguments = inner
+                        .map(Expression::from_pair)
+                        .collect::<Result<Vec<Expression>, String>>()?;
+                    if arguments.len() != function.number_arguments() {
+                        return Err(format!(
+                            "Function {name} takes {} argument(s), but is called with {}",
+                            function.number_arguments(),
+                            arguments.len()
+                        ));
+                    }
+                    Ok(Expression::Call(function, arguments))
+                }
+                Rule::expr => Expression::from_pair(primary),
+                _ => Ok(Expression::Identifier(primary.as_str().to_owned())),
+            })
+            .map_prefix(|_, operand| Ok(Expression::Negation(Box::new(operand?))))
+            .map_infix(|left, operator, right| {
+                let operator = match operator.as_rule() {
+                    Rule::add => BinaryOperator::Add,
+                    Rule::subtract => BinaryOperator::Subtract,
+                    Rule::multiply => BinaryOperator::Multiply,
+                    Rule::divide => BinaryOperator::Divide,
+                    Rule::modulo => BinaryOperator::Modulo,
+                    _ => BinaryOperator::Power,
+                };
+                Ok(Expression::Binary(
+                    operator,
+                    Box::new(left?),
+                    Box::new(right?),
+                ))
+            })
+            .parse(pair.into_inner())
+    }
+
+    /// Returns true if the expression has an integer value.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm3` - Whether the division of integers is an integer division, as in OpenQASM 3.0.
+    fn is_integer(&self, qasm3: bool) -> bool {
+        match self {
+            Expression::Integer(_) => true,
+            Expression::Negation(operand) => operand.is_integer(qasm3),
+            Expression::Binary(operator, left, right) => {
+                (qasm3 || *operator != BinaryOperator::Divide)
+                    && *operator != BinaryOperator::Power
+                    && left.is_integer(qasm3)
+                    && right.is_integer(qasm3)
+            }
+            Expression::Call(Function::Mod, arguments) => {
+                arguments.iter().all(|argument| argument.is_integer(qasm3))
+            }
+            _ => false,
+        }
+    }
+
+    /// Evaluates the expression.
+    ///
+    /// Expressions containing free identifiers are kept as symbolic CalculatorFloat expressions
+    /// that can be evaluated once the identifiers are known.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm3` - Whether the division of integers is an integer division, as in OpenQASM 3.0.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(CalculatorFloat)` - The value of the expression.
+    /// * `Err(String)` - The expression divides by zero.
+    pub(crate) fn evaluate(&self, qasm3: bool) -> Result<CalculatorFloat, String> {
+        self.evaluate_with(qasm3, &HashMap::new())
+    }
+
+    /// Evaluates the expression with values for some of its identifiers.
+    ///
+    /// Identifiers without a value are kept symbolic, as in `evaluate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm3` - Whether the division of integers is an integer division, as in OpenQASM 3.0.
+    /// * `values` - The values of the identifiers, such as the parameters of a gate call.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(CalculatorFloat)` - The value of the expression.
+    /// * `Err(String)` - The expression divides by zero.
+    pub(crate) fn evaluate_with(
+        &self,
+        qasm3: bool,
+        values: &HashMap<String, CalculatorFloat>,
+    ) -> Result<CalculatorFloat, String> {
+        match self {
+            Expression::Real(value) => Ok(CalculatorFloat::Float(*value)),
+            Expression::Integer(value) => Ok(CalculatorFloat::Float(*value as f64)),
+            Expression::Identifier(name) => Ok(values
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| CalculatorFloat::Str(name.clone()))),
+            Expression::Negation(operand) => Ok(-operand.evaluate_with(qasm3, values)?),
+            Expression::Binary(operator, left, right) => {
+                let integer_operands = left.is_integer(qasm3) && right.is_integer(qasm3);
+                let left = left.evaluate_with(qasm3, values)?;
+                let right = right.evaluate_with(qasm3, values)?;
+                match operator {
+                    BinaryOperator::Add => Ok(left + right),
+                    BinaryOperator::Subtract => Ok(left - right),
+                    BinaryOperator::Multiply => Ok(left * right),
+                    BinaryOperator::Divide => {
+                        check_divisor(&right)?;
+                        let quotient = left / right;
+                        if qasm3 && integer_operands {
+                            Ok(truncate(quotient))
+                        } else {
+                            Ok(quotient)
+                        }
+                    }
+                    BinaryOperator::Modulo => modulo(left, right),
+                    BinaryOperator::Power => Ok(left.powf(right)),
+                }
+            }
+            Expression::Call(function, arguments) => {
+                let mut values = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate_with(qasm3, values))
+                    .collect::<Result<Vec<CalculatorFloat>, String>>()?
+                    .into_iter();
+                let argument = values.next().unwrap_or_default();
+                match function {
+                    Function::Sin => Ok(argument.sin()),
+                    Function::Cos => Ok(argument.cos()),
+                    Function::Tan => Ok(apply(argument, f64::tan, "tan")),
+                    Function::Asin => Ok(apply(argument, f64::asin, "asin")),
+                    Function::Acos => Ok(argument.acos()),
+                    Function::Atan => Ok(apply(argument, f64::atan, "atan")),
+                    Function::Exp => Ok(argument.exp()),
+                    Function::Ln => Ok(apply(argument, f64::ln, "log")),
+                    Function::Sqrt => Ok(argument.sqrt()),
+                    Function::Floor => Ok(apply(argument, f64::floor, "floor")),
+                    Function::Ceiling => Ok(apply(argument, f64::ceil, "ceil")),
+                    Function::Pow => Ok(argument.powf(values.next().unwrap_or_default())),
+                    Function::Mod => modulo(argument, values.next().unwrap_or_default()),
+                }
+            }
+        }
+    }
+}
+
+/// Applies a function to a value, written in the syntax of the Calculator when symbolic.
+fn apply(value: CalculatorFloat, function: fn(f64) -> f64, name: &str) -> CalculatorFloat {
+    match value {
+        CalculatorFloat::Float(value) => CalculatorFloat::Float(function(value)),
+        CalculatorFloat::Str(value) => CalculatorFloat::Str(format!("{name}({value})")),
+    }
+}
+
+/// Rounds a value towards zero.
+fn truncate(value: CalculatorFloat) -> CalculatorFloat {
+    match value {
+        CalculatorFloat::Float(value) => CalculatorFloat::Float(value.trunc()),
+        value => value.signum() * apply(value.abs(), f64::floor, "floor"),
+    }
+}
+
+/// Returns the remainder of the division, which has the sign of the dividend.
+fn modulo(left: CalculatorFloat, right: CalculatorFloat) -> Result<CalculatorFloat, String> {
+    check_divisor(&right)?;
+    match (left, right) {
+        (CalculatorFloat::Float(left), CalculatorFloat::Float(right)) => {
+            Ok(CalculatorFloat::Float(left % right))
+        }
+        (left, right) => Ok(left.clone() - right.clone() * truncate(left / right)),
+    }
+}
+
+/// Checks that a divisor is not zero.
+fn check_divisor(divisor: &CalculatorFloat) -> Result<(), String> {
+    match divisor {
+        CalculatorFloat::Float(value) if *value == 0.0 => Err("Division by zero".to_string()),
+        _ => Ok(()),
+    }
+}
@@ -17,6 +17,7 @@ use roqoqo::operations::*;
 use roqoqo::Circuit;
 use roqoqo::RoqoqoBackendError;
 
+use crate::EmitterOptions;
 use crate::Qasm2Dialect;
 use crate::Qasm3Dialect;
 use crate::QasmVersion;
@@ -39,7 +40,7 @@ pub(crate) const ALLOWED_OPERATIONS: &[&str; 12] = &[
 ];
 
 // Operations that are ignored when looking for a QASM definition
-pub(crate) const NO_DEFINITION_REQUIRED_OPERATIONS: &[&str; 15] = &[
+pub(crate) const NO_DEFINITION_REQUIRED_OPERATIONS: &[&str; 24] = &[
     "SingleQubitGate",
     "DefinitionFloat",
     "DefinitionUsize",
@@ -47,6 +48,7 @@ pub(crate) const NO_DEFINITION_REQUIRED_OPERATIONS: &[&str; 15] = &[
     "DefinitionComplex",
     "PragmaActiveReset",
     "PragmaConditional",
+    "PragmaControlledCircuit",
     "PragmaGlobalPhase",
     "PragmaRepeatedMeasurement",
     "PragmaDamping",
@@ -55,6 +57,14 @@ pub(crate) const NO_DEFINITION_REQUIRED_OPERATIONS: &[&str; 15] = &[
     "MeasureQubit",
     "PragmaLoop",
     "CallDefinedGate",
+    "PragmaBoostNoise",
+    "PragmaGeneralNoise",
+    "PragmaOverrotation",
+    "PragmaRandomNoise",
+    "PragmaRepeatGate",
+    "PragmaSetDensityMatrix",
+    "PragmaSetStateVector",
+    "InputBit",
 ];
 
 // Operations that are supported for Qulacs QASM version
@@ -82,6 +92,9 @@ pub(crate) const QULCAS_SUPPORTED_OPERATIONS: &[&str; 21] = &[
     "RotateXY",
 ];
 
+// Name of the local bit a GateDefinition written as a subroutine measures into and returns
+const SUBROUTINE_BIT: &str = "measured_bit";
+
 /// Calls the parsing function of the VariableGatherer, if present.
 ///
 /// # Arguments:
@@ -145,13 +158,41 @@ pub fn call_circuit(
     circuit: &Circuit,
     qubit_register_name: &str,
     qasm_version: QasmVersion,
+) -> Result<Vec<String>, RoqoqoBackendError> {
+    call_circuit_with_options(
+        circuit,
+        qubit_register_name,
+        qasm_version,
+        &EmitterOptions::default(),
+    )
+}
+
+/// Translate the qoqo circuit into QASM ouput with custom emitter settings.
+///
+/// # Arguments
+///
+/// * `circuit` - The qoqo Circuit that is translated.
+/// * `qubit_register_name` - Name of the quantum register used for the roqoqo address-space
+/// * `qasm_version` - The QASM version to use.
+/// * `options` - The settings of the emitter.
+///
+/// # Returns
+///
+/// * `Ok(Vec<&str>)` - Vector containing converted operations as strings.
+/// * `Err(RoqoqoBackendError)` - Operation not supported by QASM backend.
+pub fn call_circuit_with_options(
+    circuit: &Circuit,
+    qubit_register_name: &str,
+    qasm_version: QasmVersion,
+    options: &EmitterOptions,
 ) -> Result<Vec<String>, RoqoqoBackendError> {
     let mut str_circuit: Vec<String> = Vec::new();
     for op in circuit.iter() {
-        str_circuit.push(call_operation(
+        str_circuit.push(call_operation_with_options(
             op,
             qubit_register_name,
             qasm_version,
+            options,
             &mut None,
         )?);
     }
@@ -176,6 +217,36 @@ pub fn call_operation(
     qubit_register_name: &str,
     qasm_version: QasmVersion,
     variable_gatherer: &mut Option<&mut VariableGatherer>,
+) -> Result<String, RoqoqoBackendError> {
+    call_operation_with_options(
+        operation,
+        qubit_register_name,
+        qasm_version,
+        &EmitterOptions::default(),
+        variable_gatherer,
+    )
+}
+
+/// Translates a qoqo operation to QASM (&str) with custom emitter settings.
+///
+/// # Arguments
+///
+/// * `operation` - The qoqo Operation that is executed.
+/// * `qubtit_register_name` - Name of the quantum register used for the roqoqo address.
+/// * `qasm_version` - The QASM version to use.
+/// * `options` - The settings of the emitter.
+/// * `variable_gatherer` - Optional VariableParser to call.
+///
+/// # Returns
+///
+/// * `Ok(&str)` - Converted operation in &str form.
+/// * `Err(RoqoqoBackendError)` - Operation not supported by QASM backend.
+pub fn call_operation_with_options(
+    operation: &Operation,
+    qubit_register_name: &str,
+    qasm_version: QasmVersion,
+    options: &EmitterOptions,
+    variable_gatherer: &mut Option<&mut VariableGatherer>,
 ) -> Result<String, RoqoqoBackendError> {
     if matches!(qasm_version, QasmVersion::V2point0(Qasm2Dialect::Qulacs))
         && !QULCAS_SUPPORTED_OPERATIONS.contains(&operation.hqslang())
@@ -185,6 +256,21 @@ pub fn call_operation(
             hqslang: operation.hqslang(),
         });
     }
+    if options.gate_modifiers(qasm_version) {
+        if let Some((modifiers, controls, gate)) = modifier_form(operation) {
+            let call = call_operation_with_options(
+                &gate,
+                qubit_register_name,
+                qasm_version,
+                options,
+                variable_gatherer,
+            )?;
+            return Ok(format!(
+                "{modifiers}{}",
+                add_control_qubits(&call, &controls, qubit_register_name)?
+            ));
+        }
+    }
     match operation {
         Operation::RotateZ(op) => {
             variable_gathering(op.theta(), qasm_version, variable_gatherer);
@@ -661,10 +747,11 @@ pub fn call_operation(
                             "if({}[{}]==1) {}",
                             op.condition_register(),
                             op.condition_index(),
-                            call_operation(
+                            call_operation_with_options(
                                 int_op,
                                 qubit_register_name,
                                 qasm_version,
+                                options,
                                 variable_gatherer
                             )?
                         ));
@@ -673,10 +760,11 @@ pub fn call_operation(
                             "if({}[{}]==1) {}\n",
                             op.condition_register(),
                             op.condition_index(),
-                            call_operation(
+                            call_operation_with_options(
                                 int_op,
                                 qubit_register_name,
                                 qasm_version,
+                                options,
                                 variable_gatherer
                             )?
                         ));
@@ -686,7 +774,12 @@ pub fn call_operation(
             }
             QasmVersion::V3point0(_) => {
                 let mut data = "".to_string();
-                let circuit_vec = call_circuit(op.circuit(), qubit_register_name, qasm_version)?;
+                let circuit_vec = call_circuit_with_options(
+                    op.circuit(),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                )?;
                 data.push_str(&format!(
                     "if({}[{}]==1) {{\n",
                     op.condition_register(),
@@ -699,6 +792,48 @@ pub fn call_operation(
                 Ok(data)
             }
         },
+        Operation::PragmaControlledCircuit(op) => match qasm_version {
+            QasmVersion::V3point0(_) => {
+                let mut lines: Vec<String> = vec![];
+                for int_op in op.circuit().iter() {
+                    if !(int_op.tags().contains(&"GateOperation")
+                        || matches!(
+                            int_op,
+                            Operation::CallDefinedGate(_) | Operation::PragmaControlledCircuit(_)
+                        ))
+                    {
+                        return Err(RoqoqoBackendError::GenericError {
+                            msg: format!(
+                                "Only gates can be controlled in a PragmaControlledCircuit, not {}",
+                                int_op.hqslang()
+                            ),
+                        });
+                    }
+                    let call = call_operation_with_options(
+                        int_op,
+                        qubit_register_name,
+                        qasm_version,
+                        options,
+                        variable_gatherer,
+                    )?;
+                    for line in call.lines().filter(|line| !line.is_empty()) {
+                        lines.push(format!(
+                            "ctrl @ {}",
+                            add_control_qubits(
+                                line,
+                                &[*op.controlling_qubit()],
+                                qubit_register_name
+                            )?
+                        ));
+                    }
+                }
+                Ok(lines.join("\n"))
+            }
+            QasmVersion::V2point0(_) => Err(RoqoqoBackendError::OperationNotInBackend {
+                backend: "QASM",
+                hqslang: operation.hqslang(),
+            }),
+        },
         Operation::PragmaDamping(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
                 "pragma roqoqo {} {} {} {};",
@@ -795,10 +930,16 @@ pub fn call_operation(
         },
         Operation::PragmaGetDensityMatrix(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
-                "pragma roqoqo {} {} {};",
+                "pragma roqoqo {} {}{}",
                 op.hqslang(),
                 op.readout(),
-                op.circuit().clone().unwrap_or(Circuit::new())
+                pragma_circuit_block(
+                    op.circuit().as_ref(),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                    variable_gatherer
+                )?
             )),
             _ => {
                 if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
@@ -813,10 +954,16 @@ pub fn call_operation(
         },
         Operation::PragmaGetOccupationProbability(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
-                "pragma roqoqo {} {} {};",
+                "pragma roqoqo {} {}{}",
                 op.hqslang(),
                 op.readout(),
-                op.circuit().clone().unwrap_or(Circuit::new())
+                pragma_circuit_block(
+                    op.circuit().as_ref(),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                    variable_gatherer
+                )?
             )),
             _ => {
                 if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
@@ -831,11 +978,17 @@ pub fn call_operation(
         },
         Operation::PragmaGetPauliProduct(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
-                "pragma roqoqo {} {:?} {} {};",
+                "pragma roqoqo {} {:?} {}{}",
                 op.hqslang(),
                 op.qubit_paulis(),
                 op.readout(),
-                op.circuit()
+                pragma_circuit_block(
+                    (!op.circuit().is_empty()).then_some(op.circuit()),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                    variable_gatherer
+                )?
             )),
             _ => {
                 if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
@@ -850,10 +1003,16 @@ pub fn call_operation(
         },
         Operation::PragmaGetStateVector(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
-                "pragma roqoqo {} {} {};",
+                "pragma roqoqo {} {}{}",
                 op.hqslang(),
                 op.readout(),
-                op.circuit().clone().unwrap_or(Circuit::new())
+                pragma_circuit_block(
+                    op.circuit().as_ref(),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                    variable_gatherer
+                )?
             )),
             _ => {
                 if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
@@ -882,32 +1041,53 @@ pub fn call_operation(
         },
         Operation::PragmaLoop(op) => match qasm_version {
             QasmVersion::V3point0(Qasm3Dialect::Roqoqo) => Ok(format!(
-                "pragma roqoqo {} {} {};",
+                "pragma roqoqo {} {}{}",
                 op.hqslang(),
                 op.repetitions(),
-                op.circuit()
+                pragma_circuit_block(
+                    (!op.circuit().is_empty()).then_some(op.circuit()),
+                    qubit_register_name,
+                    qasm_version,
+                    options,
+                    variable_gatherer
+                )?
             )),
             QasmVersion::V3point0(Qasm3Dialect::Vanilla) => {
-                let mut data = "".to_string();
-                match op.repetitions() {
-                    CalculatorFloat::Float(x) => {
-                        data.push_str(format!("for uint i in [0:{x}] {{\n").as_str());
-                        let circuit_vec = call_circuit(op.circuit(), qubit_register_name, qasm_version)?;
-                        for string in circuit_vec {
-                            data.push_str(format!("    {string}").as_str());
+                // OpenQASM 3.0 ranges include the stop value
+                let stop = match op.repetitions() {
+                    CalculatorFloat::Float(x) => (x - 1.0).to_string(),
+                    CalculatorFloat::Str(x) => {
+                        if let Some(gatherer) = variable_gatherer {
+                            let _ = gatherer.parse_integer(x);
                         }
-                        data.push_str("\n}");
-                        Ok(data)
-                    },
-                    CalculatorFloat::Str(x) => Err(RoqoqoBackendError::GenericError { msg: format!("Used PragmaLoop with a string {x} for repetitions and a qasm-version that is incompatible: {qasm_version:?}") })
+                        format!("{x}-1")
+                    }
+                };
+                let mut data = format!(
+                    "for uint {} in [0:{stop}] {{\n",
+                    loop_variable(op.circuit())
+                );
+                for operation in op.circuit().iter() {
+                    let instruction = call_operation_with_options(
+                        operation,
+                        qubit_register_name,
+                        qasm_version,
+                        options,
+                        variable_gatherer,
+                    )?;
+                    for line in instruction.lines() {
+                        data.push_str(format!("    {line}\n").as_str());
+                    }
                 }
+                data.push('}');
+                Ok(data)
             }
             _ => {
                 let mut data = "".to_string();
                 match op.repetitions() {
                     CalculatorFloat::Float(x) => {
                         for _ in 0_usize..(*x as usize) {
-                            let circuit_vec = call_circuit(op.circuit(), qubit_register_name, qasm_version)?;
+                            let circuit_vec = call_circuit_with_options(op.circuit(), qubit_register_name, qasm_version, options)?;
                             for string in circuit_vec {
                                 data.push_str(string.as_str());
                                 data.push('\n');
@@ -978,20 +1158,35 @@ pub fn call_operation(
         },
         Operation::PragmaRepeatedMeasurement(op) => {
             let mut output_string = "".to_string();
+            let assignment = options.measurement_assignment(qasm_version);
             match op.qubit_mapping() {
+                None if assignment => output_string.push_str(
+                    format!("{} = measure {};", op.readout(), qubit_register_name).as_str(),
+                ),
                 None => output_string.push_str(
                     format!("measure {} -> {};", qubit_register_name, op.readout()).as_str(),
                 ),
                 Some(qm) => {
                     for (key, val) in qm.iter() {
-                        output_string += format!(
-                            "measure {}[{}] -> {}[{}];\n",
-                            qubit_register_name,
-                            key,
-                            op.readout(),
-                            val
-                        )
-                        .as_str();
+                        if assignment {
+                            output_string += format!(
+                                "{}[{}] = measure {}[{}];\n",
+                                op.readout(),
+                                val,
+                                qubit_register_name,
+                                key
+                            )
+                            .as_str();
+                        } else {
+                            output_string += format!(
+                                "measure {}[{}] -> {}[{}];\n",
+                                qubit_register_name,
+                                key,
+                                op.readout(),
+                                val
+                            )
+                            .as_str();
+                        }
                     }
                 }
             }
@@ -1084,15 +1279,19 @@ pub fn call_operation(
                 }
                 Ok(output_string)
             }
-            _ => {
-                if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
-                    Ok("".to_string())
-                } else {
-                    Err(RoqoqoBackendError::OperationNotInBackend {
-                        backend: "QASM",
-                        hqslang: operation.hqslang(),
-                    })
+            QasmVersion::V3point0(_) => {
+                let duration = delay_duration(op.sleep_time(), options)?;
+                if op.qubits().is_empty() {
+                    return Ok(format!("delay[{duration}];"));
                 }
+                Ok(format!(
+                    "delay[{duration}] {};",
+                    op.qubits()
+                        .iter()
+                        .map(|qubit| format!("{qubit_register_name}[{qubit}]"))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ))
             }
         },
         Operation::PragmaStartDecompositionBlock(op) => match qasm_version {
@@ -1135,6 +1334,21 @@ pub fn call_operation(
                 op.qubits(),
                 op.execution_time()
             )),
+            QasmVersion::V2point0(_) | QasmVersion::V3point0(Qasm3Dialect::Vanilla) => {
+                // A block on no qubits is translated to a barrier on the whole register
+                if op.qubits().is_empty() {
+                    Ok(format!("barrier {qubit_register_name};"))
+                } else {
+                    Ok(format!(
+                        "barrier {};",
+                        op.qubits()
+                            .iter()
+                            .map(|qubit| format!("{qubit_register_name}[{qubit}]"))
+                            .collect::<Vec<String>>()
+                            .join(",")
+                    ))
+                }
+            }
             _ => {
                 if ALLOWED_OPERATIONS.contains(&operation.hqslang()) {
                     Ok("".to_string())
@@ -1146,13 +1360,25 @@ pub fn call_operation(
                 }
             }
         },
-        Operation::MeasureQubit(op) => Ok(format!(
-            "measure {}[{}] -> {}[{}];",
-            qubit_register_name,
-            op.qubit(),
-            op.readout(),
-            op.readout_index()
-        )),
+        Operation::MeasureQubit(op) => {
+            if options.measurement_assignment(qasm_version) {
+                Ok(format!(
+                    "{}[{}] = measure {}[{}];",
+                    op.readout(),
+                    op.readout_index(),
+                    qubit_register_name,
+                    op.qubit()
+                ))
+            } else {
+                Ok(format!(
+                    "measure {}[{}] -> {}[{}];",
+                    qubit_register_name,
+                    op.qubit(),
+                    op.readout(),
+                    op.readout_index()
+                ))
+            }
+        }
         Operation::DefinitionFloat(op) => match qasm_version {
             QasmVersion::V2point0(_) => Ok(format!("creg {}[{}];", op.name(), op.length())),
             QasmVersion::V3point0(Qasm3Dialect::Braket) => {
@@ -1244,20 +1470,52 @@ pub fn call_operation(
                 }
             }
         },
-        Operation::CallDefinedGate(op) => Ok(format!(
-            "{}({}) {};",
-            op.gate_name(),
-            op.free_parameters()
+        Operation::CallDefinedGate(op) => {
+            let params = op
+                .free_parameters()
                 .iter()
                 .map(|param| param.to_string())
-                .collect::<Vec<String>>()
-                .join(","),
-            op.qubits()
+                .collect::<Vec<String>>();
+            let qubits = op
+                .qubits()
                 .iter()
                 .map(|qubit| format!("{qubit_register_name}[{qubit}]"))
-                .collect::<Vec<String>>()
-                .join(",")
-        )),
+                .collect::<Vec<String>>();
+            let subroutine = variable_gatherer
+                .as_ref()
+                .and_then(|gatherer| gatherer.subroutines.get(op.gate_name()));
+            if let Some(returned_bit) = subroutine {
+                // Subroutines take the qubits as arguments after the parameters
+                let assignment = returned_bit
+                    .as_ref()
+                    .map(|bit| format!("{bit} = "))
+                    .unwrap_or_default();
+                Ok(format!(
+                    "{assignment}{}({});",
+                    op.gate_name(),
+                    [params, qubits].concat().join(",")
+                ))
+            } else {
+                Ok(format!(
+                    "{}({}) {};",
+                    op.gate_name(),
+                    params.join(","),
+                    qubits.join(",")
+                ))
+            }
+        }
+        Operation::GateDefinition(op) => {
+            if let (Some(gatherer), QasmVersion::V3point0(_)) = (variable_gatherer, qasm_version) {
+                if is_subroutine(op) {
+                    let returned_bit = returned_bit(op)?
+                        .map(|(readout, readout_index)| format!("{readout}[{readout_index}]"));
+                    gatherer
+                        .subroutines
+                        .insert(op.name().to_owned(), returned_bit);
+                }
+            }
+            Ok("".to_string())
+        }
         Operation::SqrtPauliY(op) => Ok(format!("sy {}[{}];", qubit_register_name, op.qubit())),
         Operation::InvSqrtPauliY(op) => {
             Ok(format!("sydg {}[{}];", qubit_register_name, op.qubit()))
@@ -1275,6 +1533,249 @@ pub fn call_operation(
     }
 }
 
+/// Returns the OpenQASM 3.0 duration literal of a PragmaSleep time in seconds.
+///
+/// # Arguments
+///
+/// * `sleep_time` - The sleep time in seconds.
+/// * `options` - The settings of the emitter, giving the time unit and the length of `dt`.
+fn delay_duration(
+    sleep_time: &CalculatorFloat,
+    options: &EmitterOptions,
+) -> Result<String, RoqoqoBackendError> {
+    match sleep_time {
+        CalculatorFloat::Float(seconds) => {
+            let time_unit = options.time_unit();
+            let value = time_unit.to_unit(*seconds, options.dt()).ok_or_else(|| {
+                RoqoqoBackendError::GenericError {
+                    msg: "Durations in units of dt require the length of dt to be set with EmitterOptions::with_dt".to_string(),
+                }
+            })?;
+            Ok(format!("{value}{}", time_unit.suffix()))
+        }
+        CalculatorFloat::Str(x) => Err(RoqoqoBackendError::GenericError {
+            msg: format!("Used PragmaSleep with a string {x} for the sleep time, which cannot be written as an OpenQASM 3.0 duration"),
+        }),
+    }
+}
+
+/// Returns the name of the loop variable of a PragmaLoop written as a `for` loop.
+///
+/// The name is given by the number of loops nested inside the body, so that a loop never shadows
+/// the variable of an enclosing loop. Loops without nested loops use `i`.
+///
+/// # Arguments
+///
+/// * `body` - The circuit repeated by the loop.
+fn loop_variable(body: &Circuit) -> String {
+    match nested_loop_depth(body) {
+        0 => "i".to_string(),
+        depth => format!("i{depth}"),
+    }
+}
+
+/// Writes the end of a `pragma roqoqo` statement for an operation containing a circuit.
+///
+/// The circuit is written as a block of QASM statements following the pragma, which the parser
+/// reads back into the circuit. Without a circuit the pragma ends with `;`.
+fn pragma_circuit_block(
+    circuit: Option<&Circuit>,
+    qubit_register_name: &str,
+    qasm_version: QasmVersion,
+    options: &EmitterOptions,
+    variable_gatherer: &mut Option<&mut VariableGatherer>,
+) -> Result<String, RoqoqoBackendError> {
+    let Some(circuit) = circuit else {
+        return Ok(";".to_string());
+    };
+    let mut data = " {\n".to_string();
+    for operation in circuit.iter() {
+        let instruction = call_operation_with_options(
+            operation,
+            qubit_register_name,
+            qasm_version,
+            options,
+            variable_gatherer,
+        )?;
+        for line in instruction.lines() {
+            data.push_str(format!("    {line}\n").as_str());
+        }
+    }
+    data.push('}');
+    Ok(data)
+}
+
+/// Returns the maximal number of PragmaLoops nested inside each other in a circuit.
+fn nested_loop_depth(circuit: &Circuit) -> usize {
+    circuit
+        .iter()
+        .map(|operation| match operation {
+            Operation::PragmaLoop(op) => nested_loop_depth(op.circuit()) + 1,
+            Operation::PragmaConditional(op) => nested_loop_depth(op.circuit()),
+            Operation::PragmaControlledCircuit(op) => nested_loop_depth(op.circuit()),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns whether a GateDefinition is written as an OpenQASM 3.0 subroutine.
+///
+/// Measurements and resets are not allowed in the body of a `gate`, definitions containing them
+/// are written as `def` subroutines.
+fn is_subroutine(definition: &GateDefinition) -> bool {
+    definition.circuit().iter().any(|operation| {
+        matches!(
+            operation,
+            Operation::MeasureQubit(_) | Operation::PragmaActiveReset(_)
+        )
+    })
+}
+
+/// Returns the classical bit a GateDefinition written as a subroutine measures into.
+///
+/// The subroutine returns the measured bit, which is assigned to this bit where it is called.
+///
+/// # Returns
+///
+/// * `Ok(Some((readout, readout_index)))` - The bit all measurements of the definition write to.
+/// * `Ok(None)` - The definition does not contain measurements.
+/// * `Err(RoqoqoBackendError::GenericError)` - The definition measures into more than one bit.
+fn returned_bit(
+    definition: &GateDefinition,
+) -> Result<Option<(String, usize)>, RoqoqoBackendError> {
+    let mut returned_bit: Option<(String, usize)> = None;
+    for operation in definition.circuit().iter() {
+        if let Operation::MeasureQubit(measurement) = operation {
+            let bit = (
+                measurement.readout().to_owned(),
+                *measurement.readout_index(),
+            );
+            match &returned_bit {
+                Some(previous) if previous != &bit => {
+                    return Err(RoqoqoBackendError::GenericError {
+                        msg: format!(
+                            "GateDefinition {} measures into more than one classical bit, a subroutine can only return one",
+                            definition.name()
+                        ),
+                    })
+                }
+                _ => returned_bit = Some(bit),
+            }
+        }
+    }
+    Ok(returned_bit)
+}
+
+/// Returns how an operation is written with the gate modifiers of OpenQASM 3.0.
+///
+/// # Returns
+///
+/// * `Some((modifiers, controls, gate))` - The modifiers written in front of the modified gate,
+///   the control qubits and the modified gate.
+/// * `None` - The operation is not written with gate modifiers.
+pub(crate) fn modifier_form(
+    operation: &Operation,
+) -> Option<(&'static str, Vec<usize>, Operation)> {
+    match operation {
+        Operation::InvSGate(op) => Some(("inv @ ", vec![], SGate::new(*op.qubit()).into())),
+        Operation::InvTGate(op) => Some(("inv @ ", vec![], TGate::new(*op.qubit()).into())),
+        Operation::InvSqrtPauliX(op) => {
+            Some(("inv @ ", vec![], SqrtPauliX::new(*op.qubit()).into()))
+        }
+        Operation::InvSqrtPauliY(op) => {
+            Some(("inv @ ", vec![], SqrtPauliY::new(*op.qubit()).into()))
+        }
+        Operation::InvSqrtISwap(op) => Some((
+            "inv @ ",
+            vec![],
+            SqrtISwap::new(*op.control(), *op.target()).into(),
+        )),
+        Operation::ControlledPauliY(op) => Some((
+            "ctrl @ ",
+            vec![*op.control()],
+            PauliY::new(*op.target()).into(),
+        )),
+        Operation::ControlledPauliZ(op) => Some((
+            "ctrl @ ",
+            vec![*op.control()],
+            PauliZ::new(*op.target()).into(),
+        )),
+        Operation::ControlledPhaseShift(op) => Some((
+            "ctrl @ ",
+            vec![*op.control()],
+            PhaseShiftState1::new(*op.target(), op.theta().clone()).into(),
+        )),
+        Operation::ControlledRotateX(op) => Some((
+            "ctrl @ ",
+            vec![*op.control()],
+            RotateX::new(*op.target(), op.theta().clone()).into(),
+        )),
+        Operation::ControlledRotateXY(op) => Some((
+            "ctrl @ ",
+            vec![*op.control()],
+            RotateXY::new(*op.target(), op.theta().clone(), op.phi().clone()).into(),
+        )),
+        Operation::ControlledSWAP(op) => Some((
+            "ctrl @ ",
+            vec![*op.control_0()],
+            SWAP::new(*op.control_1(), *op.target()).into(),
+        )),
+        Operation::Toffoli(op) => Some((
+            "ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1()],
+            PauliX::new(*op.target()).into(),
+        )),
+        Operation::ControlledControlledPauliZ(op) => Some((
+            "ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1()],
+            PauliZ::new(*op.target()).into(),
+        )),
+        Operation::ControlledControlledPhaseShift(op) => Some((
+            "ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1()],
+            PhaseShiftState1::new(*op.target(), op.theta().clone()).into(),
+        )),
+        Operation::TripleControlledPauliX(op) => Some((
+            "ctrl @ ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1(), *op.control_2()],
+            PauliX::new(*op.target()).into(),
+        )),
+        Operation::TripleControlledPauliZ(op) => Some((
+            "ctrl @ ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1(), *op.control_2()],
+            PauliZ::new(*op.target()).into(),
+        )),
+        Operation::TripleControlledPhaseShift(op) => Some((
+            "ctrl @ ctrl @ ctrl @ ",
+            vec![*op.control_0(), *op.control_1(), *op.control_2()],
+            PhaseShiftState1::new(*op.target(), op.theta().clone()).into(),
+        )),
+        _ => None,
+    }
+}
+
+/// Adds control qubits in front of the qubits of a gate call.
+fn add_control_qubits(
+    call: &str,
+    controls: &[usize],
+    qubit_register_name: &str,
+) -> Result<String, RoqoqoBackendError> {
+    let position = call
+        .find(&format!(" {qubit_register_name}["))
+        .ok_or_else(|| RoqoqoBackendError::GenericError {
+            msg: format!("Cannot add control qubits to {call}"),
+        })?;
+    let (gate, qubits) = call.split_at(position + 1);
+    Ok(format!(
+        "{gate}{}{qubits}",
+        controls
+            .iter()
+            .map(|control| format!("{qubit_register_name}[{control}],"))
+            .collect::<String>()
+    ))
+}
+
 /// Outputs the QASM gate definition of many qoqo operations.
 ///
 /// # Arguments
@@ -1288,6 +1789,30 @@ pub fn call_operation(
 pub fn gate_definition(
     operation: &Operation,
     qasm_version: QasmVersion,
+) -> Result<String, RoqoqoBackendError> {
+    gate_definition_with_options(operation, qasm_version, &EmitterOptions::default())
+}
+
+/// Outputs the QASM gate definition of many qoqo operations with custom emitter settings.
+///
+/// The settings are used for the operations in the circuits of GateDefinition operations.
+/// In OpenQASM 3.0, GateDefinitions containing measurements or resets are written as `def`
+/// subroutines, measuring into a local bit that is returned to the caller.
+///
+/// # Arguments
+///
+/// * `operation` - The roqoqo Operation to be defined.
+/// * `qasm_version` - The QASM version to use.
+/// * `options` - The settings of the emitter.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The gate QASM gate definition.
+/// * `RoqoqoBackendError::OperationNotInBackend` - Operation not supported by QASM backend.
+pub fn gate_definition_with_options(
+    operation: &Operation,
+    qasm_version: QasmVersion,
+    options: &EmitterOptions,
 ) -> Result<String, RoqoqoBackendError> {
     match operation {
         Operation::RotateX(_) => Ok(String::from(
@@ -1430,31 +1955,74 @@ pub fn gate_definition(
                 hqslang: operation.hqslang(),
             }),
         },
-        Operation::PragmaSleep(_) => Ok(String::from(
-            "opaque pragmasleep(param) a;"
-        )),
+        Operation::PragmaSleep(_) => match qasm_version {
+            QasmVersion::V2point0(_) => Ok(String::from("opaque pragmasleep(param) a;")),
+            QasmVersion::V3point0(_) => Ok("".to_string()),
+        },
         Operation::GateDefinition(gate_definition) => {
-            let mut definition_str = format!(
-                "gate {}({}) {}\n{{\n",
-                gate_definition.name(),
-                gate_definition.free_parameters().join(","),
-                gate_definition
-                    .qubits()
+            let qubits = gate_definition
+                .qubits()
+                .iter()
+                .map(|&qubit| format!("qb_{qubit}").to_owned())
+                .collect::<Vec<String>>();
+            let subroutine =
+                matches!(qasm_version, QasmVersion::V3point0(_)) && is_subroutine(gate_definition);
+            // Subroutines measure into a local bit, which is returned to the caller
+            let returns_bit = subroutine && returned_bit(gate_definition)?.is_some();
+            let mut definition_str = if subroutine {
+                let arguments = gate_definition
+                    .free_parameters()
                     .iter()
-                    .map(|&qubit| format!("qb_{qubit}").to_owned())
-                    .collect::<Vec<String>>()
-                    .join(",")
-            );
+                    .map(|param| format!("float[64] {param}"))
+                    .chain(qubits.iter().map(|qubit| format!("qubit {qubit}")))
+                    .collect::<Vec<String>>();
+                format!(
+                    "def {}({}){}\n{{\n",
+                    gate_definition.name(),
+                    arguments.join(", "),
+                    if returns_bit { " -> bit" } else { "" }
+                )
+            } else {
+                format!(
+                    "gate {}({}) {}\n{{\n",
+                    gate_definition.name(),
+                    gate_definition.free_parameters().join(","),
+                    qubits.join(",")
+                )
+            };
+            if returns_bit {
+                definition_str.push_str(&format!("    bit {SUBROUTINE_BIT};\n"));
+            }
             for operation in gate_definition.circuit().iter() {
                 definition_str.push_str("    ");
-                definition_str.push_str(&call_operation(
-                    operation,
-                    "replace_me",
-                    qasm_version,
-                    &mut None,
-                )?);
+                let operation_str = match operation {
+                    Operation::MeasureQubit(measurement) if subroutine => {
+                        if options.measurement_assignment(qasm_version) {
+                            format!(
+                                "{SUBROUTINE_BIT} = measure replace_me[{}];",
+                                measurement.qubit()
+                            )
+                        } else {
+                            format!(
+                                "measure replace_me[{}] -> {SUBROUTINE_BIT};",
+                                measurement.qubit()
+                            )
+                        }
+                    }
+                    _ => call_operation_with_options(
+                        operation,
+                        "replace_me",
+                        qasm_version,
+                        options,
+                        &mut None,
+                    )?,
+                };
+                definition_str.push_str(&operation_str);
                 definition_str.push('\n');
             }
+            if returns_bit {
+                definition_str.push_str(&format!("    return {SUBROUTINE_BIT};\n"));
+            }
             definition_str.push('}');
             for qubit in gate_definition.qubits().iter() {
                 definition_str = definition_str
@@ -31,7 +31,20 @@ mod backend;
 pub use backend::*;
 mod interface;
 pub use interface::*;
+mod emitter_options;
+pub use emitter_options::*;
+mod error;
+pub use error::*;
+mod expression;
+mod modifier;
 mod parser;
 pub use parser::*;
+mod parser_options;
+pub use parser_options::*;
+mod qasm_program;
+pub use qasm_program::*;
+mod registers;
+pub use registers::*;
+mod roqoqo_pragma;
 mod variable_gatherer;
 pub use variable_gatherer::*;
@@ -0,0 +1,277 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Gate modifiers of OpenQASM 3.0 gate calls.
+
+use crate::expression::Expression;
+use crate::Rule;
+use pest::iterators::Pair;
+use qoqo_calculator::CalculatorFloat;
+use roqoqo::operations::*;
+
+/// A gate modifier in front of a gate call, such as `ctrl @` in `ctrl @ x q[0], q[1];`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Modifier {
+    /// `ctrl(n) @` and `negctrl(n) @`, controlling the gate by the next n qubits of the call.
+    Control {
+        /// The number of control qubits.
+        number_controls: usize,
+        /// Whether the gate is applied when the control qubits are in state 0.
+        negated: bool,
+    },
+    /// `inv @`
+    Inverse,
+    /// `pow(k) @`
+    Power(CalculatorFloat),
+}
+
+impl Modifier {
+    /// Builds the modifier from a `ctrl_modifier`, `negctrl_modifier`, `inv_modifier` or
+    /// `pow_modifier` pair of the grammar.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Modifier)` - The modifier.
+    /// * `Err(String)` - The number of controls is not a positive integer or the exponent is invalid.
+    pub(crate) fn from_pair(pair: Pair<Rule>) -> Result<Self, String> {
+        let argument = pair
+            .clone()
+            .into_inner()
+            .next()
+            .map(|expression| Expression::from_pair(expression)?.evaluate(true))
+            .transpose()?;
+        match pair.as_rule() {
+            Rule::ctrl_modifier | Rule::negctrl_modifier => {
+                let number_controls = match argument {
+                    None => 1,
+                    Some(CalculatorFloat::Float(value)) if value >= 1.0 && value.fract() == 0.0 => {
+                        value as usize
+                    }
+                    Some(value) => {
+                        return Err(format!(
+                            "The number of control qubits must be a positive integer, not {value}"
+                        ))
+                    }
+                };
+                Ok(Modifier::Control {
+                    number_controls,
+                    negated: pair.as_rule() == Rule::negctrl_modifier,
+                })
+            }
+            Rule::pow_modifier => Ok(Modifier::Power(argument.unwrap_or_default())),
+            _ => Ok(Modifier::Inverse),
+        }
+    }
+
+    /// Returns the number of qubits the modifier adds to the gate call.
+    pub(crate) fn number_controls(&self) -> usize {
+        match self {
+            Modifier::Control {
+                number_controls, ..
+            } => *number_controls,
+            _ => 0,
+        }
+    }
+}
+
+/// Returns the global phase of an OpenQASM 3.0 standard gate relative to its roqoqo operation.
+///
+/// The phase is irrelevant for the gate itself, but becomes a relative phase once the gate is
+/// controlled. Only the gates translated to a single roqoqo operation are covered.
+///
+/// # Arguments
+///
+/// * `name` - The name of the gate.
+/// * `params` - The parameters of the gate call.
+pub(crate) fn translation_phase(name: &str, params: &[CalculatorFloat]) -> CalculatorFloat {
+    match (name, params) {
+        // U(theta, phi, lambda) is exp(i(phi+lambda)/2) Rz(phi) Ry(theta) Rz(lambda)
+        ("u3" | "u" | "U", [_, phi, lambda]) | ("u2", [phi, lambda]) => {
+            (phi.clone() + lambda.clone()) / 2.0
+        }
+        ("u1", [lambda]) => lambda.clone() / 2.0,
+        // sx is pow(0.5) @ x, which is exp(i pi/4) RotateX(pi/2)
+        ("sx" | "v", _) => CalculatorFloat::FRAC_PI_4,
+        ("sxdg", _) => -CalculatorFloat::FRAC_PI_4,
+        _ => CalculatorFloat::ZERO,
+    }
+}
+
+/// Returns the inverse of a gate operation.
+///
+/// Gates calling user-defined gates are not covered, their inverse needs a new definition.
+///
+/// # Returns
+///
+/// * `Some(Operation)` - The inverse operation.
+/// * `None` - The inverse of the operation is not known.
+pub(crate) fn inverse_operation(operation: &Operation) -> Option<Operation> {
+    let inverse = match operation {
+        Operation::SGate(op) => Operation::from(InvSGate::new(*op.qubit())),
+        Operation::InvSGate(op) => Operation::from(SGate::new(*op.qubit())),
+        Operation::TGate(op) => Operation::from(InvTGate::new(*op.qubit())),
+        Operation::InvTGate(op) => Operation::from(TGate::new(*op.qubit())),
+        Operation::SqrtPauliX(op) => Operation::from(InvSqrtPauliX::new(*op.qubit())),
+        Operation::InvSqrtPauliX(op) => Operation::from(SqrtPauliX::new(*op.qubit())),
+        Operation::SqrtPauliY(op) => Operation::from(InvSqrtPauliY::new(*op.qubit())),
+        Operation::InvSqrtPauliY(op) => Operation::from(SqrtPauliY::new(*op.qubit())),
+        Operation::SqrtISwap(op) => Operation::from(InvSqrtISwap::new(*op.control(), *op.target())),
+        Operation::InvSqrtISwap(op) => Operation::from(SqrtISwap::new(*op.control(), *op.target())),
+        Operation::MolmerSorensenXX(op) => Operation::from(VariableMSXX::new(
+            *op.control(),
+            *op.target(),
+            -CalculatorFloat::FRAC_PI_2,
+        )),
+        // The gates that are their own inverse
+        Operation::Hadamard(_)
+        | Operation::PauliX(_)
+        | Operation::PauliY(_)
+        | Operation::PauliZ(_)
+        | Operation::Identity(_)
+        | Operation::GPi(_)
+        | Operation::CNOT(_)
+        | Operation::ControlledPauliY(_)
+        | Operation::ControlledPauliZ(_)
+        | Operation::SWAP(_)
+        | Operation::FSwap(_)
+        | Operation::EchoCrossResonance(_)
+        | Operation::Toffoli(_)
+        | Operation::ControlledControlledPauliZ(_)
+        | Operation::ControlledSWAP(_)
+        | Operation::TripleControlledPauliX(_)
+        | Operation::TripleControlledPauliZ(_) => operation.clone(),
+        Operation::TripleControlledPhaseShift(op) => {
+            Operation::from(TripleControlledPhaseShift::new(
+                *op.control_0(),
+                *op.control_1(),
+                *op.control_2(),
+                *op.target(),
+                -op.theta().clone(),
+            ))
+        }
+        // The inverse of a unitary is its conjugate transpose
+        Operation::SingleQubitGate(op) => Operation::from(SingleQubitGate::new(
+            *op.qubit(),
+            op.alpha_r().clone(),
+            -op.alpha_i().clone(),
+            -op.beta_r().clone(),
+            -op.beta_i().clone(),
+            -op.global_phase().clone(),
+        )),
+        _ => return power_operation(operation, &CalculatorFloat::from(-1.0)),
+    };
+    Some(inverse)
+}
+
+/// Returns a rotation gate raised to a power, by multiplying its rotation angle.
+///
+/// # Returns
+///
+/// * `Some(Operation)` - The rotation with the multiplied angle.
+/// * `None` - The operation is not a rotation with a single angle.
+pub(crate) fn power_operation(
+    operation: &Operation,
+    exponent: &CalculatorFloat,
+) -> Option<Operation> {
+    let exponent = exponent.clone();
+    let power = match operation {
+        Operation::RotateX(op) => Operation::from(op.powercf(exponent)),
+        Operation::RotateY(op) => Operation::from(op.powercf(exponent)),
+        Operation::RotateZ(op) => Operation::from(op.powercf(exponent)),
+        Operation::PhaseShiftState0(op) => Operation::from(op.powercf(exponent)),
+        Operation::PhaseShiftState1(op) => Operation::from(op.powercf(exponent)),
+        Operation::RotateXY(op) => Operation::from(op.powercf(exponent)),
+        Operation::ControlledPhaseShift(op) => Operation::from(op.powercf(exponent)),
+        Operation::ControlledRotateX(op) => Operation::from(op.powercf(exponent)),
+        Operation::ControlledRotateXY(op) => Operation::from(op.powercf(exponent)),
+        Operation::ControlledControlledPhaseShift(op) => Operation::from(op.powercf(exponent)),
+        Operation::VariableMSXX(op) => Operation::from(op.powercf(exponent)),
+        Operation::XY(op) => Operation::from(op.powercf(exponent)),
+        _ => return None,
+    };
+    Some(power)
+}
+
+/// Returns the roqoqo operation of a gate with one more control qubit, if roqoqo has one.
+///
+/// # Arguments
+///
+/// * `control` - The additional control qubit.
+/// * `operation` - The controlled gate.
+///
+/// # Returns
+///
+/// * `Some(Operation)` - The controlled operation.
+/// * `None` - roqoqo has no operation for the controlled gate.
+pub(crate) fn controlled_operation(control: usize, operation: &Operation) -> Option<Operation> {
+    let controlled = match operation {
+        Operation::PauliX(op) => Operation::from(CNOT::new(control, *op.qubit())),
+        Operation::CNOT(op) => Operation::from(Toffoli::new(control, *op.control(), *op.target())),
+        Operation::Toffoli(op) => Operation::from(TripleControlledPauliX::new(
+            control,
+            *op.control_0(),
+            *op.control_1(),
+            *op.target(),
+        )),
+        Operation::PauliY(op) => Operation::from(ControlledPauliY::new(control, *op.qubit())),
+        Operation::PauliZ(op) => Operation::from(ControlledPauliZ::new(control, *op.qubit())),
+        Operation::ControlledPauliZ(op) => Operation::from(ControlledControlledPauliZ::new(
+            control,
+            *op.control(),
+            *op.target(),
+        )),
+        Operation::ControlledControlledPauliZ(op) => Operation::from(TripleControlledPauliZ::new(
+            control,
+            *op.control_0(),
+            *op.control_1(),
+            *op.target(),
+        )),
+        Operation::PhaseShiftState1(op) => Operation::from(ControlledPhaseShift::new(
+            control,
+            *op.qubit(),
+            op.theta().clone(),
+        )),
+        Operation::ControlledPhaseShift(op) => {
+            Operation::from(ControlledControlledPhaseShift::new(
+                control,
+                *op.control(),
+                *op.target(),
+                op.theta().clone(),
+            ))
+        }
+        Operation::ControlledControlledPhaseShift(op) => {
+            Operation::from(TripleControlledPhaseShift::new(
+                control,
+                *op.control_0(),
+                *op.control_1(),
+                *op.target(),
+                op.theta().clone(),
+            ))
+        }
+        Operation::RotateX(op) => Operation::from(ControlledRotateX::new(
+            control,
+            *op.qubit(),
+            op.theta().clone(),
+        )),
+        Operation::RotateXY(op) => Operation::from(ControlledRotateXY::new(
+            control,
+            *op.qubit(),
+            op.theta().clone(),
+            op.phi().clone(),
+        )),
+        Operation::SWAP(op) => {
+            Operation::from(ControlledSWAP::new(control, *op.control(), *op.target()))
+        }
+        _ => return None,
+    };
+    Some(controlled)
+}
@@ -12,88 +12,1437 @@
 //
 //! The roqoqo-qasm Parser translates qasm files in Qoqo Circuit instances.
 
+use crate::expression::{BinaryOperator, Expression};
+use crate::modifier::{
+    controlled_operation, inverse_operation, power_operation, translation_phase, Modifier,
+};
+use crate::roqoqo_pragma::parse_roqoqo_pragma;
+use crate::{
+    ParserOptions, Qasm2Dialect, Qasm3Dialect, QasmParseError, QasmParseErrorKind,
+    QasmParseWarning, QasmProgram, QasmVersion, QubitRegisters, TimeUnit, UnknownGatePolicy,
+};
 use num_complex::Complex64;
-use qoqo_calculator::Calculator;
-use roqoqo::RoqoqoBackendError;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use qoqo_calculator::CalculatorFloat;
 use roqoqo::operations::*;
-use roqoqo::Circuit;
+use roqoqo::{Circuit, QuantumProgram};
 
-use pest::error::Error;
+use pest::error::{Error, LineColLocation};
 use pest::iterators::Pair;
 use pest::Parser;
 
 /// Pest Parser for QASM -> qoqo translation.
+///
+/// The OpenQASM 3.0 grammar reuses the lexical and expression rules of the 2.0 grammar, the
+/// version-specific entry points are `Rule::openqasm` and `Rule::openqasm3`.
 #[derive(Parser, Debug)]
 #[grammar = "grammars/qasm2_0.pest"]
+#[grammar = "grammars/qasm3_0.pest"]
 struct QoqoQASMParser;
 
+/// The OpenQASM 2.0 standard library, used for `include "qelib1.inc";`.
+const QELIB1_INC: &str = include_str!("includes/qelib1.inc");
+/// The OpenQASM 3.0 standard library, used for `include "stdgates.inc";`.
+const STDGATES_INC: &str = include_str!("includes/stdgates.inc");
+
+/// Names under which Amazon Braket knows standard gates that are called differently in OpenQASM.
+const BRAKET_GATE_NAMES: [&str; 5] = ["cnot", "cphaseshift", "phaseshift", "v", "xx"];
+
+/// The maximal number of operations an integer power `pow(k) @` of a gate is expanded to.
+const MAX_POWER_OPERATIONS: usize = 1 << 20;
+
+/// State collected while traversing the parsed file.
+#[derive(Debug)]
+struct ParserState {
+    /// Name, number of qubits and number of parameters of the gates defined in the file.
+    defined_custom_gates: Vec<(String, usize, usize)>,
+    /// Length of the classical bit registers declared in the file.
+    classical_registers: HashMap<String, usize>,
+    /// Names of the float and uint registers declared in the file.
+    classical_variables: HashSet<String>,
+    /// Whether the file marks its outputs with `output`, otherwise all registers are outputs.
+    explicit_outputs: bool,
+    /// Quantum registers declared in the file.
+    qubit_registers: QubitRegisters,
+    /// Names of the parameters declared with `input`.
+    declared_inputs: HashSet<String>,
+    /// Values of the input parameters given in the parser settings.
+    input_values: HashMap<String, f64>,
+    /// Whether the file, and with it all included files, uses the OpenQASM 3.0 grammar.
+    qasm3: bool,
+    /// The parsed file, if it was read from a path.
+    file_path: Option<PathBuf>,
+    /// Directories searched for included files after the directory of the including file.
+    include_paths: Vec<PathBuf>,
+    /// Files currently being included, the innermost last.
+    include_stack: Vec<PathBuf>,
+    /// Files that have already been included.
+    included_files: HashSet<PathBuf>,
+    /// Whether included files that cannot be found are skipped with a warning instead of an error.
+    skip_missing_includes: bool,
+    /// Gate definitions from included files, added to the circuit when the gate is first used.
+    included_definitions: HashMap<String, GateDefinition>,
+    /// Definitions of included gates that have been used but not yet added to the circuit.
+    pending_definitions: Vec<Operation>,
+    /// Gates declared with a body, used to generate the definitions of their inverses.
+    gate_definitions: HashMap<String, GateDefinition>,
+    /// Names of the generated inverse of each gate, and of the gate inverted by each generated gate.
+    inverse_gates: HashMap<String, String>,
+    /// How calls of unknown gates are translated.
+    unknown_gate_policy: UnknownGatePolicy,
+    /// The length of the sample time `dt` in seconds, if known.
+    dt: Option<f64>,
+    /// The maximal number of qubits or bits of a declared register.
+    max_register_size: usize,
+    /// Warnings collected while parsing.
+    warnings: Vec<QasmParseWarning>,
+    /// Names of the included files as written in their first include statement.
+    includes: Vec<String>,
+    /// Name, number of qubits and number of parameters of the gates declared with `gate` or
+    /// `opaque`, without the generated gates.
+    declared_gates: Vec<(String, usize, usize)>,
+    /// Whether the file itself, not counting its included files, declares gates.
+    declares_gates: bool,
+    /// Whether the file contains `pragma roqoqo` statements.
+    roqoqo_pragmas: bool,
+    /// Whether the file contains `pragma braket` statements or calls gates by their Braket names.
+    braket_statements: bool,
+    /// Number of lines in front of the parsed text, when the file is parsed statement by statement.
+    line_offset: usize,
+    /// Whether calls of user-defined gates are replaced by the bodies of the gates.
+    inline_gates: bool,
+    /// Parameter names and body statements of the gates whose calls are inlined.
+    gate_templates: HashMap<String, (Vec<String>, Vec<TemplateStatement>)>,
+    /// Gates currently being inlined, the innermost last.
+    inline_stack: Vec<String>,
+    /// Subroutines returning a measured bit, whose definitions depend on the assigned bit.
+    returning_subroutines: HashMap<String, ReturningSubroutine>,
+}
+
+/// A subroutine returning the bit measured into a bit declared in the subroutine.
+///
+/// The result is written to the bit the call is assigned to, so the definition of the
+/// subroutine is only added at its first call.
+#[derive(Debug, Clone)]
+struct ReturningSubroutine {
+    /// The definition, measuring into the bits declared in the subroutine.
+    definition: GateDefinition,
+    /// The names of the bits declared in the subroutine.
+    local_bits: HashSet<String>,
+    /// The bit the calls of the subroutine are assigned to, once it has been called.
+    assigned_bit: Option<(String, usize)>,
+}
+
+impl ReturningSubroutine {
+    /// Writes a measurement into a bit declared in the subroutine into the assigned bit.
+    fn assign(&self, operation: Operation, assigned_bit: &(String, usize)) -> Operation {
+        match &operation {
+            Operation::MeasureQubit(measurement)
+                if self.local_bits.contains(measurement.readout()) =>
+            {
+                Operation::from(MeasureQubit::new(
+                    *measurement.qubit(),
+                    assigned_bit.0.clone(),
+                    assigned_bit.1,
+                ))
+            }
+            _ => operation,
+        }
+    }
+}
+
+/// A statement in the body of a user-defined gate, kept to inline calls of the gate.
+#[derive(Debug, Clone)]
+enum TemplateStatement {
+    /// A gate call.
+    Call {
+        /// The name of the called gate.
+        name: String,
+        /// The modifiers of the call, in the order they are written.
+        modifiers: Vec<Modifier>,
+        /// The parameters of the call, in terms of the parameters of the user-defined gate.
+        parameters: Vec<Expression>,
+        /// The positions of the qubits of the call in the qubit arguments of the user-defined gate.
+        qubits: Vec<usize>,
+    },
+    /// A measurement in a subroutine.
+    Measure {
+        /// The position of the measured qubit in the qubit arguments of the subroutine.
+        qubit: usize,
+        /// The name of the classical register the result is written to.
+        readout: String,
+        /// The index in the classical register the result is written to.
+        readout_index: usize,
+    },
+    /// A reset in a subroutine, of the qubit at the position in the qubit arguments.
+    Reset(usize),
+}
+
+impl TemplateStatement {
+    /// Returns the measurement or reset of the statement, or None for gate calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `qubit` - Returns the qubit for a position in the qubit arguments.
+    fn operation(&self, qubit: impl Fn(usize) -> usize) -> Option<Operation> {
+        match self {
+            Self::Measure {
+                qubit: position,
+                readout,
+                readout_index,
+            } => Some(Operation::from(MeasureQubit::new(
+                qubit(*position),
+                readout.clone(),
+                *readout_index,
+            ))),
+            Self::Reset(position) => {
+                Some(Operation::from(PragmaActiveReset::new(qubit(*position))))
+            }
+            Self::Call { .. } => None,
+        }
+    }
+}
+
+impl ParserState {
+    /// Creates the state for parsing a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `qasm3` - Whether the file is parsed with the OpenQASM 3.0 grammar.
+    /// * `file_path` - The path of the parsed file, if it was read from a path.
+    /// * `options` - The settings of the parser.
+    fn new(qasm3: bool, file_path: Option<&Path>, options: &ParserOptions) -> Self {
+        let file_path = file_path.and_then(|path| path.canonicalize().ok());
+        let base_directory = file_path.as_deref().and_then(Path::parent);
+        Self {
+            defined_custom_gates: vec![],
+            classical_registers: HashMap::new(),
+            classical_variables: HashSet::new(),
+            explicit_outputs: false,
+            qubit_registers: QubitRegisters::new(),
+            declared_inputs: HashSet::new(),
+            input_values: options.input_values().clone(),
+            qasm3,
+            include_paths: options.resolved_include_paths(base_directory),
+            file_path,
+            include_stack: vec![],
+            included_files: HashSet::new(),
+            skip_missing_includes: false,
+            included_definitions: HashMap::new(),
+            pending_definitions: vec![],
+            gate_definitions: HashMap::new(),
+            inverse_gates: HashMap::new(),
+            unknown_gate_policy: options.unknown_gate_policy(),
+            dt: options.dt(),
+            max_register_size: options.max_register_size(),
+            warnings: vec![],
+            includes: vec![],
+            declared_gates: vec![],
+            declares_gates: false,
+            roqoqo_pragmas: false,
+            braket_statements: false,
+            line_offset: 0,
+            inline_gates: options.inline_gates(),
+            gate_templates: HashMap::new(),
+            inline_stack: vec![],
+            returning_subroutines: HashMap::new(),
+        }
+    }
+
+    /// Returns the OpenQASM version of the file and the dialect detected from its statements.
+    fn detected_version(&self) -> QasmVersion {
+        if self.qasm3 {
+            QasmVersion::V3point0(if self.roqoqo_pragmas {
+                Qasm3Dialect::Roqoqo
+            } else if self.braket_statements {
+                Qasm3Dialect::Braket
+            } else {
+                Qasm3Dialect::Vanilla
+            })
+        } else if !self.declares_gates && self.includes.iter().any(|name| name == "qelib1.inc") {
+            QasmVersion::V2point0(Qasm2Dialect::Qulacs)
+        } else {
+            QasmVersion::V2point0(Qasm2Dialect::Vanilla)
+        }
+    }
+
+    /// Registers the signature of a gate declared with `gate` or `opaque`.
+    fn declare_gate(&mut self, definition: &GateDefinition) {
+        if self.include_stack.is_empty() {
+            self.declares_gates = true;
+        }
+        self.declared_gates.push((
+            definition.name().to_owned(),
+            definition.qubits().len(),
+            definition.free_parameters().len(),
+        ));
+    }
+
+    /// Finds the file named in an include statement.
+    ///
+    /// Relative paths are looked up in the directory of the including file, or the working
+    /// directory when parsing a string, and then in the include paths.
+    fn find_include(&self, name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        let directory = self
+            .include_stack
+            .last()
+            .or(self.file_path.as_ref())
+            .and_then(|file| file.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        std::iter::once(directory)
+            .chain(self.include_paths.iter().cloned())
+            .map(|directory| directory.join(path))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Registers a gate definition and returns the operations to add for it.
+    ///
+    /// Definitions in included files are only kept until the gate is used.
+    fn add_definition(&mut self, definition: GateDefinition) -> Vec<Operation> {
+        let name = definition.name().to_owned();
+        self.defined_custom_gates.push((
+            name.clone(),
+            definition.qubits().len(),
+            definition.free_parameters().len(),
+        ));
+        if self.gate_templates.contains_key(&name) {
+            // Calls of the gate are inlined, its definition is not needed
+            self.included_definitions.remove(&name);
+            return vec![];
+        }
+        if self.include_stack.is_empty() {
+            // A definition in the file replaces an included definition of the same gate
+            self.included_definitions.remove(&name);
+            self.require_included_definitions(definition.circuit().iter());
+            vec![Operation::from(definition)]
+        } else {
+            self.included_definitions.insert(name, definition);
+            vec![]
+        }
+    }
+
+    /// Applies the unknown gate policy to a gate call.
+    ///
+    /// Nothing is done for standard gates and gates declared before the call. The placeholder
+    /// definitions of the opaque policy are queued with the included definitions, so that they
+    /// are added to the top-level circuit even for calls in conditional statements.
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - The gate call, used for the position of errors and warnings.
+    /// * `name` - The name of the called gate.
+    /// * `number_qubits` - The number of qubits the gate is called on.
+    /// * `number_params` - The number of parameters the gate is called with.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The gate is known or the policy allows unknown gates.
+    /// * `Err(QasmParseError)` - The gate is unknown and the policy is strict.
+    fn handle_unknown_gate(
+        &mut self,
+        pair: &Pair<Rule>,
+        name: &str,
+        number_qubits: usize,
+        number_params: usize,
+    ) -> Result<(), QasmParseError> {
+        if standard_gate_signature(name).is_some()
+            || self
+                .defined_custom_gates
+                .iter()
+                .any(|(gate_name, _, _)| gate_name == name)
+        {
+            return Ok(());
+        }
+        match self.unknown_gate_policy {
+            UnknownGatePolicy::Strict => Err(custom_error(
+                pair,
+                QasmParseErrorKind::Undeclared,
+                format!("Gate {name} has not been declared"),
+            )),
+            UnknownGatePolicy::Lenient => {
+                self.warn(
+                    pair,
+                    format!("Gate {name} has not been declared and is dropped"),
+                );
+                Ok(())
+            }
+            // The unknown gate is declared like an opaque gate
+            UnknownGatePolicy::Opaque => {
+                let definitions = self.add_definition(GateDefinition::new(
+                    Circuit::new(),
+                    name.to_owned(),
+                    (0..number_qubits).collect(),
+                    (0..number_params)
+                        .map(|index| format!("param_{index}"))
+                        .collect(),
+                ));
+                self.pending_definitions.extend(definitions);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the bit returned by a subroutine call into the bit the call is assigned to.
+    ///
+    /// The definition of the subroutine is queued with the included definitions at its first
+    /// call, all later calls have to be assigned to the same bit.
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - The subroutine call, used for the position of errors.
+    /// * `name` - The name of the called subroutine.
+    /// * `assigned_bit` - The bit the call is assigned to, if it is assigned.
+    /// * `operations` - The translated call.
+    fn assign_returned_bit(
+        &mut self,
+        pair: &Pair<Rule>,
+        name: &str,
+        assigned_bit: Option<(String, usize)>,
+        operations: Vec<Operation>,
+    ) -> Result<Vec<Operation>, QasmParseError> {
+        let Some(subroutine) = self.returning_subroutines.get_mut(name) else {
+            return match assigned_bit {
+                Some(_) => Err(custom_error(
+                    pair,
+                    QasmParseErrorKind::InvalidGateCall,
+                    format!("Gate {name} does not return a bit"),
+                )),
+                None => Ok(operations),
+            };
+        };
+        let Some(assigned_bit) = assigned_bit else {
+            return Err(custom_error(
+                pair,
+                QasmParseErrorKind::Unsupported,
+                format!(
+                    "The bit returned by subroutine {name} must be assigned to a classical bit"
+                ),
+            ));
+        };
+        let operations = operations
+            .into_iter()
+            .map(|operation| subroutine.assign(operation, &assigned_bit))
+            .collect();
+        // Inlined subroutines do not need a definition
+        if self.gate_templates.contains_key(name) {
+            return Ok(operations);
+        }
+        match &subroutine.assigned_bit {
+            Some(previous) if previous != &assigned_bit => Err(custom_error(
+                pair,
+                QasmParseErrorKind::Unsupported,
+                format!(
+                    "The bit returned by subroutine {name} is assigned to {}[{}] in an earlier call, all calls must be assigned to the same bit",
+                    previous.0, previous.1
+                ),
+            )),
+            Some(_) => Ok(operations),
+            None => {
+                subroutine.assigned_bit = Some(assigned_bit.clone());
+                let circuit: Circuit = subroutine
+                    .definition
+                    .circuit()
+                    .iter()
+                    .map(|operation| subroutine.assign(operation.clone(), &assigned_bit))
+                    .collect();
+                let definition = GateDefinition::new(
+                    circuit,
+                    name.to_owned(),
+                    subroutine.definition.qubits().clone(),
+                    subroutine.definition.free_parameters().clone(),
+                );
+                let definitions = self.add_definition(definition);
+                self.pending_definitions.extend(definitions);
+                Ok(operations)
+            }
+        }
+    }
+
+    /// Adds a warning at the position of a statement.
+    ///
+    /// Warnings in included files name the file, their line is the line in the included file.
+    fn warn(&mut self, pair: &Pair<Rule>, message: String) {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        let snippet = pair.as_str().lines().next().unwrap_or_default().trim_end();
+        let (line, location) = match self.include_stack.last() {
+            Some(file) => (line, format!(" in included file {}", file.display())),
+            None => (line + self.line_offset, String::new()),
+        };
+        self.warnings.push(QasmParseWarning::new(
+            format!("{message}{location}"),
+            line,
+            column,
+            snippet.to_string(),
+        ));
+    }
+
+    /// Queues the definitions of the included gates called by the operations.
+    ///
+    /// The definitions of included gates called in those definitions are queued first.
+    fn require_included_definitions<'a>(
+        &mut self,
+        operations: impl IntoIterator<Item = &'a Operation>,
+    ) {
+        for operation in operations {
+            if let Operation::CallDefinedGate(call) = operation {
+                if let Some(definition) = self.included_definitions.remove(call.gate_name()) {
+                    self.require_included_definitions(definition.circuit().iter());
+                    self.pending_definitions.push(Operation::from(definition));
+                }
+            }
+        }
+    }
+
+    /// Replaces the calls of gates with a template by the translated body of the gate.
+    ///
+    /// The body is translated with the qubits and parameters of the call, calls of other gates
+    /// in the body are inlined in turn. Other operations are returned unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - The gate call, used for the position of errors.
+    /// * `operations` - The translated gate call.
+    fn inline_calls(
+        &mut self,
+        pair: &Pair<Rule>,
+        operations: Vec<Operation>,
+    ) -> Result<Vec<Operation>, QasmParseError> {
+        let mut inlined: Vec<Operation> = vec![];
+        for operation in operations {
+            let Operation::CallDefinedGate(call) = &operation else {
+                inlined.push(operation);
+                continue;
+            };
+            let Some((parameter_names, template)) = self.gate_templates.get(call.gate_name())
+            else {
+                inlined.push(operation);
+                continue;
+            };
+            if self
+                .inline_stack
+                .iter()
+                .any(|name| name == call.gate_name())
+            {
+                return Err(custom_error(
+                    pair,
+                    QasmParseErrorKind::Unsupported,
+                    format!(
+                        "Gate {} calls itself and cannot be inlined",
+                        call.gate_name()
+                    ),
+                ));
+            }
+            let values: HashMap<String, CalculatorFloat> = parameter_names
+                .iter()
+                .cloned()
+                .zip(call.free_parameters().iter().cloned())
+                .collect();
+            let template = template.clone();
+            self.inline_stack.push(call.gate_name().to_owned());
+            for statement in template {
+                let (name, modifiers, parameters, positions) = match statement {
+                    TemplateStatement::Call {
+                        name,
+                        modifiers,
+                        parameters,
+                        qubits,
+                    } => (name, modifiers, parameters, qubits),
+                    _ => {
+                        inlined.extend(statement.operation(|position| call.qubits()[position]));
+                        continue;
+                    }
+                };
+                let params = parameters
+                    .iter()
+                    .map(|expression| expression.evaluate_with(self.qasm3, &values))
+                    .collect::<Result<Vec<CalculatorFloat>, String>>()
+                    .map_err(|message| {
+                        custom_error(pair, QasmParseErrorKind::InvalidExpression, message)
+                    })?;
+                let qubits: Vec<usize> = positions
+                    .iter()
+                    .map(|&position| call.qubits()[position])
+                    .collect();
+                inlined
+                    .extend(self.gate_call_operations(pair, &name, &params, &qubits, &modifiers)?);
+            }
+            self.inline_stack.pop();
+        }
+        Ok(inlined)
+    }
+
+    /// Translates a gate call in the body of a user-defined gate.
+    ///
+    /// # Arguments
+    ///
+    /// * `gate_pair` - The gate call.
+    /// * `qubits` - The names of the qubit arguments of the user-defined gate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((operations, statement))` - The operations of the call on the positions of the qubit
+    ///   arguments and the statement used to inline the call, if the called gate is known.
+    /// * `Err(QasmParseError)` - The gate call is invalid.
+    fn gate_body_call(
+        &mut self,
+        gate_pair: &Pair<Rule>,
+        qubits: &[String],
+    ) -> Result<(Vec<Operation>, Option<TemplateStatement>), QasmParseError> {
+        let mut id = "";
+        let mut modifiers: Vec<Modifier> = vec![];
+        let mut gate_params: Vec<CalculatorFloat> = vec![];
+        let mut gate_expressions: Vec<Expression> = vec![];
+        let mut gate_qubits: Vec<usize> = vec![];
+        for gate_token in gate_pair.clone().into_inner() {
+            match gate_token.as_rule() {
+                Rule::name => id = gate_token.as_str(),
+                Rule::argument_list_def => {
+                    for param in gate_token.into_inner() {
+                        gate_params.push(evaluate_parameter(&param, self.qasm3)?);
+                        gate_expressions.push(parse_expression(&param)?);
+                    }
+                }
+                Rule::qubit_list_def => {
+                    for qbt_pair in gate_token.into_inner() {
+                        gate_qubits.push(argument_position(&qbt_pair, id, qubits)?);
+                    }
+                }
+                _ => modifiers.push(parse_modifier(&gate_token)?),
+            }
+        }
+        if self.returning_subroutines.contains_key(id) {
+            return Err(custom_error(
+                gate_pair,
+                QasmParseErrorKind::Unsupported,
+                format!("Subroutine {id} returns a bit and cannot be called in a definition"),
+            ));
+        }
+        check_distinct_qubits(gate_pair, &gate_qubits)?;
+        let number_qubits = gate_qubits_without_controls(gate_pair, gate_qubits.len(), &modifiers)?;
+        check_gate_call(
+            gate_pair,
+            id,
+            number_qubits,
+            gate_params.len(),
+            &self.defined_custom_gates,
+        )?;
+        self.handle_unknown_gate(gate_pair, id, number_qubits, gate_params.len())?;
+        let operations =
+            self.gate_call_operations(gate_pair, id, &gate_params, &gate_qubits, &modifiers)?;
+        // Calls of unknown gates dropped by the policy are not inlined
+        let known = standard_gate_signature(id).is_some()
+            || self
+                .defined_custom_gates
+                .iter()
+                .any(|(gate_name, _, _)| gate_name == id);
+        let statement = known.then(|| TemplateStatement::Call {
+            name: id.to_owned(),
+            modifiers,
+            parameters: gate_expressions,
+            qubits: gate_qubits,
+        });
+        Ok((operations, statement))
+    }
+
+    /// Translates a gate call into roqoqo operations, applying its gate modifiers.
+    ///
+    /// The modifiers are applied from the innermost, next to the gate name, to the outermost.
+    /// Controlled gates become the controlled roqoqo operation where one exists and a
+    /// PragmaControlledCircuit otherwise. Inverses of user-defined gates are generated as new
+    /// gate definitions.
+    ///
+    /// # Arguments
+    ///
+    /// * `pair` - The gate call, used for the position of errors.
+    /// * `name` - The name of the called gate.
+    /// * `params` - The parameters of the gate call.
+    /// * `qubits` - The qubits of the gate call, starting with the control qubits.
+    /// * `modifiers` - The modifiers of the gate call, in the order they are written.
+    fn gate_call_operations(
+        &mut self,
+        pair: &Pair<Rule>,
+        name: &str,
+        params: &[CalculatorFloat],
+        qubits: &[usize],
+        modifiers: &[Modifier],
+    ) -> Result<Vec<Operation>, QasmParseError> {
+        if modifiers.is_empty() {
+            let operations = gate_operations(name, params, qubits, &self.defined_custom_gates);
+            return self.inline_calls(pair, operations);
+        }
+        let number_controls = modifiers.iter().map(Modifier::number_controls).sum();
+        let (mut controls, targets) = qubits.split_at(number_controls);
+        // The decompositions of standard gates are only exact up to a global phase
+        let decomposition = standard_gate_decomposition(name, params, targets);
+        let mut phase = decomposition
+            .is_none()
+            .then(|| translation_phase(name, params));
+        let mut operations = match decomposition {
+            Some(decomposition) => decomposition,
+            None => self.inline_calls(
+                pair,
+                gate_dispatch(name, params, targets, &self.defined_custom_gates)
+                    .into_iter()
+                    .collect(),
+            )?,
+        };
+        let unsupported =
+            |message: String| custom_error(pair, QasmParseErrorKind::Unsupported, message);
+        for modifier in modifiers.iter().rev() {
+            match modifier {
+                Modifier::Inverse => {
+                    operations = self.inverse_operations(&operations).map_err(unsupported)?;
+                    phase = phase.map(|phase| -phase);
+                }
+                Modifier::Power(exponent) => {
+                    operations = self
+                        .power_operations(&operations, exponent)
+                        .map_err(unsupported)?;
+                    phase = phase.map(|phase| phase * exponent.clone());
+                }
+                Modifier::Control {
+                    number_controls,
+                    negated,
+                } => {
+                    let (outer_controls, own_controls) =
+                        controls.split_at(controls.len() - number_controls);
+                    controls = outer_controls;
+                    for &control in own_controls.iter().rev() {
+                        let relative_phase = phase.take().ok_or_else(|| {
+                            unsupported(format!("Gate {name} cannot be controlled, its translation is only exact up to a global phase"))
+                        })?;
+                        operations = controlled_operations(control, operations, relative_phase);
+                        phase = Some(CalculatorFloat::ZERO);
+                        if *negated && !operations.is_empty() {
+                            operations.insert(0, Operation::from(PauliX::new(control)));
+                            operations.push(Operation::from(PauliX::new(control)));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(operations)
+    }
+
+    /// Returns the inverse of a sequence of operations.
+    ///
+    /// Calls of user-defined gates are replaced by calls of a generated definition of the inverse
+    /// gate, which is queued with the included definitions.
+    fn inverse_operations(&mut self, operations: &[Operation]) -> Result<Vec<Operation>, String> {
+        operations
+            .iter()
+            .rev()
+            .map(|operation| match operation {
+                Operation::CallDefinedGate(call) => Ok(Operation::from(CallDefinedGate::new(
+                    self.inverse_gate(call.gate_name())?,
+                    call.qubits().clone(),
+                    call.free_parameters().clone(),
+                ))),
+                Operation::PragmaControlledCircuit(controlled) => {
+                    let circuit = self.inverse_operations(
+                        &controlled.circuit().iter().cloned().collect::<Vec<_>>(),
+                    )?;
+                    Ok(Operation::from(PragmaControlledCircuit::new(
+                        *controlled.controlling_qubit(),
+                        circuit.into_iter().collect(),
+                    )))
+                }
+                _ => inverse_operation(operation)
+                    .ok_or_else(|| format!("The inverse of {} is not known", operation.hqslang())),
+            })
+            .collect()
+    }
+
+    /// Returns the name of the inverse of a user-defined gate, defining it on first use.
+    fn inverse_gate(&mut self, name: &str) -> Result<String, String> {
+        if let Some(inverse_name) = self.inverse_gates.get(name) {
+            return Ok(inverse_name.clone());
+        }
+        let definition = self
+            .gate_definitions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Gate {name} has no definition that could be inverted"))?;
+        let mut inverse_name = format!("{name}_inv");
+        while self
+            .defined_custom_gates
+            .iter()
+            .any(|(gate_name, _, _)| *gate_name == inverse_name)
+        {
+            inverse_name.push('_');
+        }
+        let circuit = self
+            .inverse_operations(&definition.circuit().iter().cloned().collect::<Vec<_>>())?
+            .into_iter()
+            .collect();
+        let inverse = GateDefinition::new(
+            circuit,
+            inverse_name.clone(),
+            definition.qubits().clone(),
+            definition.free_parameters().clone(),
+        );
+        self.inverse_gates
+            .insert(name.to_owned(), inverse_name.clone());
+        self.inverse_gates
+            .insert(inverse_name.clone(), name.to_owned());
+        self.gate_definitions
+            .insert(inverse_name.clone(), inverse.clone());
+        let definitions = self.add_definition(inverse);
+        self.pending_definitions.extend(definitions);
+        Ok(inverse_name)
+    }
+
+    /// Returns a sequence of operations raised to a power.
+    ///
+    /// Integer powers repeat the operations, negative ones their inverse, up to
+    /// `MAX_POWER_OPERATIONS` operations in total. Other powers are only supported for single
+    /// rotations.
+    fn power_operations(
+        &mut self,
+        operations: &[Operation],
+        exponent: &CalculatorFloat,
+    ) -> Result<Vec<Operation>, String> {
+        match exponent {
+            CalculatorFloat::Float(value) if value.fract() == 0.0 => {
+                let base = if *value < 0.0 {
+                    self.inverse_operations(operations)?
+                } else {
+                    operations.to_vec()
+                };
+                let length = (value.abs() <= MAX_POWER_OPERATIONS as f64)
+                    .then(|| base.len().checked_mul(value.abs() as usize))
+                    .flatten()
+                    .filter(|length| *length <= MAX_POWER_OPERATIONS)
+                    .ok_or_else(|| {
+                        format!("pow({exponent}) would expand the gate to more than {MAX_POWER_OPERATIONS} operations")
+                    })?;
+                Ok(base.iter().cycle().take(length).cloned().collect())
+            }
+            _ => match operations {
+                [] => Ok(vec![]),
+                [operation] => power_operation(operation, exponent)
+                    .map(|power| vec![power])
+                    .ok_or_else(|| {
+                        format!(
+                            "{} can only be raised to integer powers, not {exponent}",
+                            operation.hqslang()
+                        )
+                    }),
+                _ => Err(format!(
+                    "Gates translated to several operations can only be raised to integer powers, not {exponent}"
+                )),
+            },
+        }
+    }
+
+    /// Converts a `duration` literal such as `100ns` to seconds.
+    fn duration_seconds(&self, pair: &Pair<Rule>) -> Result<f64, QasmParseError> {
+        let mut inner_pairs = pair.clone().into_inner();
+        let number = next_pair(&mut inner_pairs, pair)?.as_str();
+        let time_unit =
+            TimeUnit::from_suffix(next_pair(&mut inner_pairs, pair)?.as_str()).unwrap_or_default();
+        if time_unit == TimeUnit::Dt && self.dt.is_none() {
+            return Err(custom_error(
+                pair,
+                QasmParseErrorKind::Unsupported,
+                format!(
+                    "Duration {} needs the length of dt to be set with ParserOptions::with_dt",
+                    pair.as_str()
+                ),
+            ));
+        }
+        time_unit
+            .to_seconds(number, self.dt)
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .ok_or_else(|| {
+                custom_error(
+                    pair,
+                    QasmParseErrorKind::InvalidExpression,
+                    format!("Duration {} must be a non-negative number", pair.as_str()),
+                )
+            })
+    }
+
+    /// Checks that a declared register does not exceed the maximal register size.
+    fn check_register_size(
+        &self,
+        pair: &Pair<Rule>,
+        name: &str,
+        size: usize,
+    ) -> Result<(), QasmParseError> {
+        if size > self.max_register_size {
+            return Err(custom_error(
+                pair,
+                QasmParseErrorKind::Unsupported,
+                format!(
+                    "Register {name} of size {size} exceeds the maximal register size {}",
+                    self.max_register_size
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Resolves an `argument` pair (`register[index]`) to the roqoqo index of the qubit.
+    fn qubit_index(&self, pair: Pair<Rule>) -> Result<usize, QasmParseError> {
+        let mut inner_pairs = pair.clone().into_inner();
+        let register = next_pair(&mut inner_pairs, &pair)?.as_str();
+        let index = next_pair(&mut inner_pairs, &pair)?
+            .as_str()
+            .parse::<usize>()
+            .ok();
+        let size = self
+            .qubit_registers
+            .register_size(register)
+            .ok_or_else(|| {
+                custom_error(
+                    &pair,
+                    QasmParseErrorKind::Undeclared,
+                    format!("Quantum register {register} has not been declared"),
+                )
+            })?;
+        index
+            .and_then(|index| self.qubit_registers.qubit_index(register, index))
+            .ok_or_else(|| {
+                custom_error(
+                    &pair,
+                    QasmParseErrorKind::IndexOutOfRange,
+                    format!(
+                        "Qubit {} is out of range for quantum register {register} of size {size}",
+                        pair.as_str()
+                    ),
+                )
+            })
+    }
+
+    /// Resolves an `argument` pair (`register[index]`) to a declared classical register and bit.
+    fn bit_argument(&self, pair: Pair<Rule>) -> Result<(String, usize), QasmParseError> {
+        let mut inner_pairs = pair.clone().into_inner();
+        let register = next_pair(&mut inner_pairs, &pair)?.as_str();
+        let index: usize = parse_integer(&next_pair(&mut inner_pairs, &pair)?)?;
+        let length = *self.classical_registers.get(register).ok_or_else(|| {
+            custom_error(
+                &pair,
+                QasmParseErrorKind::Undeclared,
+                format!("Classical register {register} has not been declared"),
+            )
+        })?;
+        if index >= length {
+            return Err(custom_error(
+                &pair,
+                QasmParseErrorKind::IndexOutOfRange,
+                format!("Bit {register}[{index}] is out of range for classical register {register} of length {length}"),
+            ));
+        }
+        Ok((register.to_string(), index))
+    }
+
+    /// Resolves an operand, a single qubit `register[index]` or a whole register, to roqoqo indices.
+    fn qubit_operand(&self, pair: Pair<Rule>) -> Result<Vec<usize>, QasmParseError> {
+        if pair.as_rule() == Rule::argument {
+            return Ok(vec![self.qubit_index(pair)?]);
+        }
+        let register = pair.as_str();
+        match (
+            self.qubit_registers.register_offset(register),
+            self.qubit_registers.register_size(register),
+        ) {
+            (Some(offset), Some(size)) => Ok((offset..offset + size).collect()),
+            _ => Err(custom_error(
+                &pair,
+                QasmParseErrorKind::Undeclared,
+                format!("Quantum register {register} has not been declared"),
+            )),
+        }
+    }
+}
+
+/// Expands the operands of a statement into the qubits of the individual operations.
+///
+/// Whole-register operands are broadcast: the statement is applied once for each qubit of the
+/// registers, which must all have the same size, while single-qubit operands are repeated.
+fn broadcast(
+    pair: &Pair<Rule>,
+    operands: &[Vec<usize>],
+) -> Result<Vec<Vec<usize>>, QasmParseError> {
+    let repetitions = operands
+        .iter()
+        .map(Vec::len)
+        .find(|size| *size != 1)
+        .unwrap_or(1);
+    if operands
+        .iter()
+        .any(|qubits| qubits.len() != 1 && qubits.len() != repetitions)
+    {
+        return Err(custom_error(
+            pair,
+            QasmParseErrorKind::InvalidGateCall,
+            "Registers used together in a statement must have the same size".to_string(),
+        ));
+    }
+    Ok((0..repetitions)
+        .map(|repetition| {
+            operands
+                .iter()
+                .map(|qubits| qubits[repetition.min(qubits.len() - 1)])
+                .collect()
+        })
+        .collect())
+}
+
+/// Checks that the qubits of a single gate call are distinct.
+fn check_distinct_qubits(pair: &Pair<Rule>, qubits: &[usize]) -> Result<(), QasmParseError> {
+    if qubits
+        .iter()
+        .enumerate()
+        .any(|(index, qubit)| qubits[..index].contains(qubit))
+    {
+        return Err(custom_error(
+            pair,
+            QasmParseErrorKind::InvalidGateCall,
+            "A qubit cannot be used more than once in a gate call".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Controls a sequence of operations by one more qubit.
+///
+/// # Arguments
+///
+/// * `control` - The control qubit.
+/// * `operations` - The controlled operations.
+/// * `phase` - The global phase of the gate relative to the operations, applied to the control.
+fn controlled_operations(
+    control: usize,
+    operations: Vec<Operation>,
+    phase: CalculatorFloat,
+) -> Vec<Operation> {
+    if operations.is_empty() {
+        return vec![];
+    }
+    let known_operation = match operations.as_slice() {
+        [operation] => controlled_operation(control, operation),
+        _ => None,
+    };
+    let mut controlled = vec![known_operation.unwrap_or_else(|| {
+        Operation::from(PragmaControlledCircuit::new(
+            control,
+            operations.into_iter().collect(),
+        ))
+    })];
+    if phase != CalculatorFloat::ZERO {
+        controlled.push(Operation::from(PhaseShiftState1::new(control, phase)));
+    }
+    controlled
+}
+
+/// Returns the number of qubits of a gate call without the control qubits of its modifiers.
+fn gate_qubits_without_controls(
+    pair: &Pair<Rule>,
+    number_qubits: usize,
+    modifiers: &[Modifier],
+) -> Result<usize, QasmParseError> {
+    let number_controls: usize = modifiers.iter().map(Modifier::number_controls).sum();
+    if number_controls >= number_qubits {
+        return Err(custom_error(
+            pair,
+            QasmParseErrorKind::InvalidGateCall,
+            format!("The gate modifiers use {number_controls} control qubit(s), but the gate is called on {number_qubits} qubit(s)"),
+        ));
+    }
+    Ok(number_qubits - number_controls)
+}
+
+/// Parses a gate modifier, such as `ctrl @` or `pow(2) @`.
+fn parse_modifier(pair: &Pair<Rule>) -> Result<Modifier, QasmParseError> {
+    Modifier::from_pair(pair.clone())
+        .map_err(|message| custom_error(pair, QasmParseErrorKind::InvalidExpression, message))
+}
+
+/// Returns the major version number given in the `OPENQASM` header of a QASM file.
+///
+/// Blank lines and line comments in front of the header are skipped.
+fn header_major_version(file: &str) -> Option<u32> {
+    file.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("//"))
+        .and_then(|line| line.strip_prefix("OPENQASM"))
+        .and_then(|version| version.split(';').next())
+        .and_then(|version| version.trim().split('.').next())
+        .and_then(|major| major.parse::<u32>().ok())
+}
+
+/// Creates a parsing error located at the start of the given pair.
+fn custom_error(pair: &Pair<Rule>, kind: QasmParseErrorKind, message: String) -> QasmParseError {
+    let (line, column) = pair.as_span().start_pos().line_col();
+    let snippet = pair.as_str().lines().next().unwrap_or_default().trim_end();
+    QasmParseError::new(kind, message, line, column, snippet.to_string())
+}
+
+/// Converts an error of the pest parser, raised when the input does not follow the grammar.
+fn syntax_error(error: Error<Rule>) -> QasmParseError {
+    let (line, column) = match error.line_col {
+        LineColLocation::Pos(position) | LineColLocation::Span(position, _) => position,
+    };
+    let snippet = error
+        .line()
+        .chars()
+        .skip(column.saturating_sub(1))
+        .collect::<String>();
+    QasmParseError::new(
+        QasmParseErrorKind::Syntax,
+        error.variant.message().into_owned(),
+        line,
+        column,
+        snippet.trim_end().to_string(),
+    )
+}
+
+/// Returns the next inner pair of a statement.
+///
+/// The pairs are guaranteed by the grammar, a missing pair is reported instead of panicking.
+fn next_pair<'i>(
+    pairs: &mut impl Iterator<Item = Pair<'i, Rule>>,
+    parent: &Pair<'i, Rule>,
+) -> Result<Pair<'i, Rule>, QasmParseError> {
+    pairs.next().ok_or_else(|| {
+        custom_error(
+            parent,
+            QasmParseErrorKind::Syntax,
+            "Incomplete statement".to_string(),
+        )
+    })
+}
+
+/// Parses the number in an integer pair.
+fn parse_integer<T: FromStr>(pair: &Pair<Rule>) -> Result<T, QasmParseError> {
+    pair.as_str().parse::<T>().map_err(|_| {
+        custom_error(
+            pair,
+            QasmParseErrorKind::InvalidExpression,
+            format!("{} is not a supported integer", pair.as_str()),
+        )
+    })
+}
+
+/// Returns the number of qubits and parameters of the gates translated to roqoqo operations.
+fn standard_gate_signature(name: &str) -> Option<(usize, usize)> {
+    match name {
+        "h" | "x" | "y" | "z" | "s" | "sdg" | "t" | "tdg" | "sx" | "v" | "sxdg" | "sy" | "sydg"
+        | "id" => Some((1, 0)),
+        "rz" | "ry" | "rx" | "p" | "phase" | "phaseshift" | "gpi" | "gpi2" | "u0" | "u1"
+        | "pragmasleep" => Some((1, 1)),
+        "rxy" | "r" | "u2" => Some((1, 2)),
+        "u3" | "u" | "U" => Some((1, 3)),
+        "cx" | "CX" | "cnot" | "cy" | "cz" | "ch" | "csx" | "swap" | "iswap" | "siswap"
+        | "siswapdg" | "fswap" | "ecr" => Some((2, 0)),
+        "rxx" | "xx" | "rzz" | "cp" | "cphase" | "cphaseshift" | "cu1" | "crx" | "cry" | "crz"
+        | "pmint" | "xy" | "pscz" => Some((2, 1)),
+        "crxy" | "gvnsrot" | "gvnsrotle" | "pscp" => Some((2, 2)),
+        "fsim" | "qsim" | "spintint" | "cu3" => Some((2, 3)),
+        "cu" => Some((2, 4)),
+        "ccx" | "ccz" | "cswap" | "rccx" => Some((3, 0)),
+        "ccp" => Some((3, 1)),
+        "c3x" | "rc3x" | "c3sqrtx" => Some((4, 0)),
+        "c4x" => Some((5, 0)),
+        _ => None,
+    }
+}
+
+/// Returns the position of a qubit in the qubit arguments of a user-defined gate.
+///
+/// # Arguments
+///
+/// * `pair` - The name of the qubit.
+/// * `gate_name` - The name of the gate the qubit is used for, used in the error message.
+/// * `qubits` - The names of the qubit arguments of the user-defined gate.
+fn argument_position(
+    pair: &Pair<Rule>,
+    gate_name: &str,
+    qubits: &[String],
+) -> Result<usize, QasmParseError> {
+    qubits
+        .iter()
+        .position(|qubit_name| qubit_name.as_str() == pair.as_str())
+        .ok_or_else(|| {
+            custom_error(
+                pair,
+                QasmParseErrorKind::Undeclared,
+                format!(
+                    "{} is not a qubit argument of gate {gate_name}",
+                    pair.as_str()
+                ),
+            )
+        })
+}
+
+/// Translates a measurement or reset in the body of a subroutine.
+///
+/// Measured bits are written into the classical registers named in the subroutine, or into the
+/// bits declared in the subroutine.
+///
+/// # Arguments
+///
+/// * `pair` - The measurement or reset statement.
+/// * `name` - The name of the subroutine.
+/// * `qubits` - The names of the qubit arguments of the subroutine.
+/// * `local_bits` - The names of the bits declared in the subroutine.
+fn subroutine_statement(
+    pair: &Pair<Rule>,
+    name: &str,
+    qubits: &[String],
+    local_bits: &HashSet<String>,
+) -> Result<TemplateStatement, QasmParseError> {
+    let mut inner_pairs = pair.clone().into_inner();
+    let first_pair = next_pair(&mut inner_pairs, pair)?;
+    if pair.as_rule() == Rule::reset {
+        return Ok(TemplateStatement::Reset(argument_position(
+            &first_pair,
+            name,
+            qubits,
+        )?));
+    }
+    let second_pair = next_pair(&mut inner_pairs, pair)?;
+    let (qubit_pair, bit_pair) = if pair.as_rule() == Rule::measure_assignment {
+        (second_pair, first_pair)
+    } else {
+        (first_pair, second_pair)
+    };
+    let qubit = argument_position(&qubit_pair, name, qubits)?;
+    if bit_pair.as_rule() != Rule::argument {
+        if !local_bits.contains(bit_pair.as_str()) {
+            return Err(custom_error(
+                pair,
+                QasmParseErrorKind::InvalidGateCall,
+                "A qubit argument must be measured into a single bit".to_string(),
+            ));
+        }
+        return Ok(TemplateStatement::Measure {
+            qubit,
+            readout: bit_pair.as_str().to_owned(),
+            readout_index: 0,
+        });
+    }
+    let mut bit_argument = bit_pair.clone().into_inner();
+    let readout = next_pair(&mut bit_argument, &bit_pair)?.as_str();
+    let readout_index = parse_integer(&next_pair(&mut bit_argument, &bit_pair)?)?;
+    Ok(TemplateStatement::Measure {
+        qubit,
+        readout: readout.to_string(),
+        readout_index,
+    })
+}
+
+/// Checks that a known gate is called with the number of qubits and parameters it expects.
+fn check_gate_call(
+    pair: &Pair<Rule>,
+    name: &str,
+    number_qubits: usize,
+    number_params: usize,
+    defined_custom_gates: &[(String, usize, usize)],
+) -> Result<(), QasmParseError> {
+    let signature = standard_gate_signature(name).or_else(|| {
+        defined_custom_gates
+            .iter()
+            .rev()
+            .find(|(gate_name, _, _)| gate_name == name)
+            .map(|(_, qubits, params)| (*qubits, *params))
+    });
+    match signature {
+        Some((qubits, params)) if (qubits, params) != (number_qubits, number_params) => {
+            Err(custom_error(
+                pair,
+                QasmParseErrorKind::InvalidGateCall,
+                format!("Gate {name} acts on {qubits} qubit(s) with {params} parameter(s), but is called on {number_qubits} qubit(s) with {number_params} parameter(s)"),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Evaluates a parameter expression.
+///
+/// Expressions containing free identifiers, such as the parameters declared with `input`, are
+/// kept as symbolic expressions.
+///
+/// # Arguments
+///
+/// * `pair` - The `expr` pair of the parameter.
+/// * `qasm3` - Whether the expression follows OpenQASM 3.0, where integers are divided as integers.
+fn evaluate_parameter(pair: &Pair<Rule>, qasm3: bool) -> Result<CalculatorFloat, QasmParseError> {
+    parse_expression(pair)?.evaluate(qasm3).map_err(|message| {
+        custom_error(
+            pair,
+            QasmParseErrorKind::InvalidExpression,
+            format!("Could not evaluate parameter {}: {message}", pair.as_str()),
+        )
+    })
+}
+
+/// Builds the expression tree of an `expr` pair.
+fn parse_expression(pair: &Pair<Rule>) -> Result<Expression, QasmParseError> {
+    Expression::from_pair(pair.clone()).map_err(|message| {
+        custom_error(
+            pair,
+            QasmParseErrorKind::InvalidExpression,
+            format!("Could not evaluate parameter {}: {message}", pair.as_str()),
+        )
+    })
+}
+
+/// Returns the number of iterations of a `for` loop over a range `[start:stop]` or
+/// `[start:step:stop]`.
+///
+/// OpenQASM 3.0 ranges include the stop value. Bounds containing free identifiers, such as a
+/// parameter declared with `input uint n;`, give a symbolic number of iterations, which is only
+/// supported with a step of 1. The range `[0:n-1]` written by the backend is read back as `n`.
+///
+/// # Arguments
+///
+/// * `range` - The `loop_range` pair of the loop.
+fn loop_repetitions(range: &Pair<Rule>) -> Result<CalculatorFloat, QasmParseError> {
+    let invalid_range = |message: String| {
+        custom_error(
+            range,
+            QasmParseErrorKind::InvalidExpression,
+            format!("Could not evaluate range {}: {message}", range.as_str()),
+        )
+    };
+    let bounds = range
+        .clone()
+        .into_inner()
+        .map(Expression::from_pair)
+        .collect::<Result<Vec<Expression>, String>>()
+        .map_err(invalid_range)?;
+    let (start, step, stop) = match bounds.as_slice() {
+        [start, stop] => (start, None, stop),
+        [start, step, stop] => (start, Some(step), stop),
+        _ => return Err(invalid_range("Incomplete range".to_string())),
+    };
+    let evaluate = |expression: &Expression| expression.evaluate(true).map_err(invalid_range);
+    let step = step
+        .map(evaluate)
+        .transpose()?
+        .unwrap_or(CalculatorFloat::Float(1.0));
+    let unit_step = step == CalculatorFloat::Float(1.0);
+    match (evaluate(start)?, step, evaluate(stop)?) {
+        (
+            CalculatorFloat::Float(start),
+            CalculatorFloat::Float(step),
+            CalculatorFloat::Float(stop),
+        ) => {
+            if [start, step, stop].iter().any(|value| value.fract() != 0.0) {
+                return Err(invalid_range("Ranges must be integers".to_string()));
+            }
+            if step == 0.0 {
+                return Err(invalid_range(
+                    "The step of a range must not be zero".to_string(),
+                ));
+            }
+            Ok(CalculatorFloat::Float(
+                (((stop - start) / step).floor() + 1.0).max(0.0),
+            ))
+        }
+        (start, _, _) if unit_step => match (start, stop) {
+            (
+                CalculatorFloat::Float(start),
+                Expression::Binary(BinaryOperator::Subtract, bound, one),
+            ) if start == 0.0 && **one == Expression::Integer(1) => evaluate(bound),
+            (start, stop) => Ok(evaluate(stop)? - start + 1.0),
+        },
+        _ => Err(custom_error(
+            range,
+            QasmParseErrorKind::Unsupported,
+            format!(
+                "Range {} with a symbolic bound must have a step of 1",
+                range.as_str()
+            ),
+        )),
+    }
+}
+
 /// Dispatch function for qoqo operations.
 fn gate_dispatch(
     name: &str,
-    params: &[String],
+    params: &[CalculatorFloat],
     qubits: &[usize],
     defined_custom_gates: &[(String, usize, usize)],
 ) -> Option<Operation> {
+    if standard_gate_signature(name)
+        .is_some_and(|signature| signature != (qubits.len(), params.len()))
+    {
+        return None;
+    }
     match name {
-        "rz" => Some(Operation::from(RotateZ::new(
-            qubits[0],
-            CalculatorFloat::from(&params[0]),
-        ))),
-        "ry" => Some(Operation::from(RotateY::new(
-            qubits[0],
-            CalculatorFloat::from(&params[0]),
-        ))),
-        "rx" => Some(Operation::from(RotateX::new(
-            qubits[0],
-            CalculatorFloat::from(&params[0]),
+        "rz" => Some(Operation::from(RotateZ::new(qubits[0], params[0].clone()))),
+        "ry" => Some(Operation::from(RotateY::new(qubits[0], params[0].clone()))),
+        "rx" => Some(Operation::from(RotateX::new(qubits[0], params[0].clone()))),
+        // The opaque gate the backend declares for PragmaSleep on each qubit
+        "pragmasleep" => Some(Operation::from(PragmaSleep::new(
+            vec![qubits[0]],
+            params[0].clone(),
         ))),
         "h" => Some(Operation::from(Hadamard::new(qubits[0]))),
         "x" => Some(Operation::from(PauliX::new(qubits[0]))),
         "y" => Some(Operation::from(PauliY::new(qubits[0]))),
         "z" => Some(Operation::from(PauliZ::new(qubits[0]))),
         "s" => Some(Operation::from(SGate::new(qubits[0]))),
+        "sdg" => Some(Operation::from(InvSGate::new(qubits[0]))),
         "t" => Some(Operation::from(TGate::new(qubits[0]))),
-        "p" => Some(Operation::from(PhaseShiftState1::new(
+        "tdg" => Some(Operation::from(InvTGate::new(qubits[0]))),
+        // The parameter of u0 is the duration of the idle gate
+        "id" | "u0" => Some(Operation::from(Identity::new(qubits[0]))),
+        "p" | "phase" | "phaseshift" => Some(Operation::from(PhaseShiftState1::new(
             qubits[0],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
-        "sx" => Some(Operation::from(SqrtPauliX::new(qubits[0]))),
+        "sx" | "v" => Some(Operation::from(SqrtPauliX::new(qubits[0]))),
         "sxdg" => Some(Operation::from(InvSqrtPauliX::new(qubits[0]))),
-        "cx" => Some(Operation::from(CNOT::new(qubits[0], qubits[1]))),
-        "rxx" => {
-            if let Ok(float) = CalculatorFloat::from(&params[0]).float() {
-                if is_close(float.into(), CalculatorFloat::PI.float().unwrap().into()) {
+        "sy" => Some(Operation::from(SqrtPauliY::new(qubits[0]))),
+        "sydg" => Some(Operation::from(InvSqrtPauliY::new(qubits[0]))),
+        "cx" | "CX" | "cnot" => Some(Operation::from(CNOT::new(qubits[0], qubits[1]))),
+        "rxx" | "xx" => {
+            if let Ok(float) = params[0].clone().float() {
+                if is_close(
+                    float.into(),
+                    CalculatorFloat::FRAC_PI_2.float().unwrap().into(),
+                ) {
                     return Some(Operation::from(MolmerSorensenXX::new(qubits[0], qubits[1])));
                 }
             }
             Some(Operation::from(VariableMSXX::new(
                 qubits[0],
                 qubits[1],
-                CalculatorFloat::from(&params[0]),
+                params[0].clone(),
             )))
         }
         "cy" => Some(Operation::from(ControlledPauliY::new(qubits[0], qubits[1]))),
         "cz" => Some(Operation::from(ControlledPauliZ::new(qubits[0], qubits[1]))),
-        "cp" => Some(Operation::from(ControlledPhaseShift::new(
-            qubits[0],
-            qubits[1],
-            CalculatorFloat::from(&params[0]),
-        ))),
+        "cp" | "cphase" | "cphaseshift" | "cu1" => Some(Operation::from(
+            ControlledPhaseShift::new(qubits[0], qubits[1], params[0].clone()),
+        )),
         "crx" => Some(Operation::from(ControlledRotateX::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
         "crxy" => Some(Operation::from(ControlledRotateXY::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
+            params[0].clone(),
+            params[1].clone(),
         ))),
         "swap" => Some(Operation::from(SWAP::new(qubits[0], qubits[1]))),
         "iswap" => Some(Operation::from(ISwap::new(qubits[0], qubits[1]))),
@@ -103,125 +1452,94 @@ fn gate_dispatch(
         "fsim" => Some(Operation::from(Fsim::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
-            CalculatorFloat::from(&params[2]),
+            params[0].clone(),
+            params[1].clone(),
+            params[2].clone(),
         ))),
         "qsim" => Some(Operation::from(Qsim::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
-            CalculatorFloat::from(&params[2]),
+            params[0].clone(),
+            params[1].clone(),
+            params[2].clone(),
         ))),
         "pmint" => Some(Operation::from(PMInteraction::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
         "gvnsrot" => Some(Operation::from(GivensRotation::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
+            params[0].clone(),
+            params[1].clone(),
         ))),
         "gvnsrotle" => Some(Operation::from(GivensRotationLittleEndian::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
+            params[0].clone(),
+            params[1].clone(),
         ))),
         "xy" => Some(Operation::from(XY::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
         "spintint" => Some(Operation::from(SpinInteraction::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
-            CalculatorFloat::from(&params[2]),
+            params[0].clone(),
+            params[1].clone(),
+            params[2].clone(),
         ))),
         "rxy" | "r" => Some(Operation::from(RotateXY::new(
             qubits[0],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
+            params[0].clone(),
+            params[1].clone(),
         ))),
+        "gpi" => Some(Operation::from(GPi::new(qubits[0], params[0].clone()))),
+        "gpi2" => Some(Operation::from(GPi2::new(qubits[0], params[0].clone()))),
         "ecr" => Some(Operation::from(EchoCrossResonance::new(
             qubits[0], qubits[1],
         ))),
         "pscz" => Some(Operation::from(PhaseShiftedControlledZ::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
         "pscp" => Some(Operation::from(PhaseShiftedControlledPhase::new(
             qubits[0],
             qubits[1],
-            CalculatorFloat::from(&params[0]),
-            CalculatorFloat::from(&params[1]),
+            params[0].clone(),
+            params[1].clone(),
         ))),
-        "u3" => {
-            let theta = CalculatorFloat::from(&params[0]);
-            let phi = CalculatorFloat::from(&params[1]);
-            let lambda = CalculatorFloat::from(&params[2]);
-            let alpha_r =
-                ((phi.clone() + lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).cos();
-            let alpha_i =
-                (-(phi.clone() + lambda.clone()) / 2.0).sin() * (theta.clone() / 2.0).cos();
-            let beta_r = ((phi.clone() - lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).sin();
-            let beta_i = ((phi - lambda) / 2.0).sin() * (theta / 2.0).sin();
-            Some(Operation::from(SingleQubitGate::new(
-                qubits[0],
-                alpha_r,
-                alpha_i,
-                beta_r,
-                beta_i,
-                CalculatorFloat::ZERO,
-            )))
-        }
-        "u2" => {
-            let theta = CalculatorFloat::FRAC_PI_2;
-            let phi = CalculatorFloat::from(&params[0]);
-            let lambda = CalculatorFloat::from(&params[1]);
-            let alpha_r =
-                ((phi.clone() + lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).cos();
-            let alpha_i =
-                (-(phi.clone() + lambda.clone()) / 2.0).sin() * (theta.clone() / 2.0).cos();
-            let beta_r = ((phi.clone() - lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).sin();
-            let beta_i = ((phi - lambda) / 2.0).sin() * (theta / 2.0).sin();
-            Some(Operation::from(SingleQubitGate::new(
-                qubits[0],
-                alpha_r,
-                alpha_i,
-                beta_r,
-                beta_i,
-                CalculatorFloat::ZERO,
-            )))
-        }
-        "u1" => {
-            let theta = CalculatorFloat::ZERO;
-            let phi = CalculatorFloat::ZERO;
-            let lambda = CalculatorFloat::from(&params[0]);
-            let alpha_r =
-                ((phi.clone() + lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).cos();
-            let alpha_i =
-                (-(phi.clone() + lambda.clone()) / 2.0).sin() * (theta.clone() / 2.0).cos();
-            let beta_r = ((phi.clone() - lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).sin();
-            let beta_i = ((phi - lambda) / 2.0).sin() * (theta / 2.0).sin();
-            Some(Operation::from(SingleQubitGate::new(
-                qubits[0],
-                alpha_r,
-                alpha_i,
-                beta_r,
-                beta_i,
-                CalculatorFloat::ZERO,
-            )))
-        }
+        "u3" | "u" | "U" => Some(u3_gate(
+            qubits[0],
+            params[0].clone(),
+            params[1].clone(),
+            params[2].clone(),
+        )),
+        "u2" => Some(u3_gate(
+            qubits[0],
+            CalculatorFloat::FRAC_PI_2,
+            params[0].clone(),
+            params[1].clone(),
+        )),
+        "u1" => Some(u3_gate(
+            qubits[0],
+            CalculatorFloat::ZERO,
+            CalculatorFloat::ZERO,
+            params[0].clone(),
+        )),
         "ccx" => Some(Operation::from(Toffoli::new(
             qubits[0], qubits[1], qubits[2],
         ))),
+        "cswap" => Some(Operation::from(ControlledSWAP::new(
+            qubits[0], qubits[1], qubits[2],
+        ))),
+        "c3x" => Some(Operation::from(TripleControlledPauliX::new(
+            qubits[0], qubits[1], qubits[2], qubits[3],
+        ))),
         "ccz" => Some(Operation::from(ControlledControlledPauliZ::new(
             qubits[0], qubits[1], qubits[2],
         ))),
@@ -229,7 +1547,7 @@ fn gate_dispatch(
             qubits[0],
             qubits[1],
             qubits[2],
-            CalculatorFloat::from(&params[0]),
+            params[0].clone(),
         ))),
         _ => defined_custom_gates
             .contains(&(name.to_owned(), qubits.len(), params.len()))
@@ -237,241 +1555,1142 @@ fn gate_dispatch(
                 Operation::from(CallDefinedGate::new(
                     name.to_owned(),
                     qubits.to_vec(),
-                    params
-                        .iter()
-                        .map(|param| {
-                            let mut param_str = param.replace("pi", "3.141592653589793");
-                            param_str = param_str.replace("ln", "log");
-                            CalculatorFloat::from(param_str)
-                        })
-                        .collect(),
+                    params.to_vec(),
                 ))
             }),
     }
 }
 
-/// Main parse function method.
-fn parse_qasm_file(file: &str) -> Result<Circuit, Box<Error<Rule>>> {
-    let pairs = QoqoQASMParser::parse(Rule::openqasm, file)?;
-    let mut circuit = Circuit::new();
-    let mut defined_custom_gates: Vec<(String, usize, usize)> = vec![];
-    /// The parsing works like an AST traversal. The structure is defined by the grammar.
-    ///     - pair.as_rule() represents the rule itself, to get into the inner ones, `.into_inner()` is called
-    ///     - from the new inner instance we can further move to the right in the rule by calling `.next().unwrap()[.as_str()]`
-    fn parse_single_rule(
-        pair: Pair<Rule>,
-        defined_custom_gates: &mut Vec<(String, usize, usize)>,
-    ) -> Option<Operation> {
-        match pair.as_rule() {
-            Rule::c_decl => {
-                let mut inner_pairs = pair.into_inner();
-                let id = inner_pairs.next().unwrap().as_str();
-                let integer = inner_pairs
-                    .next()
-                    .unwrap()
-                    .as_str()
-                    .parse::<usize>()
-                    .unwrap();
-                Some(Operation::from(DefinitionBit::new(
-                    id.to_string(),
-                    integer,
-                    true,
-                )))
-            }
-            Rule::gate => {
-                let mut inner_pairs = pair.into_inner();
-                let id = inner_pairs.next().unwrap().as_str();
-                let mut params: Vec<String> = vec![];
-                let mut qubits: Vec<usize> = vec![];
-                for pair in inner_pairs.clone() {
-                    match pair.as_rule() {
-                        Rule::parameter_list => {
-                            let params_list = inner_pairs.next().unwrap().into_inner();
-                            for param in params_list {
-                                // Handle 'pi' constant and math functions renames (Calculator)
-                                let mut param_str =
-                                    param.as_str().replace("pi", "3.141592653589793");
-                                param_str = param_str.replace("ln", "log");
-                                // Parse the mathematical expression
-                                let calc = Calculator::new();
-                                let parsed = calc.parse_str(&param_str).unwrap();
-                                // Pass the parsed expression (now float) as String
-                                params.push(parsed.to_string());
-                            }
-                        }
-                        Rule::qubit_list => {
-                            let qbt_list = inner_pairs.next().unwrap().into_inner();
-                            for qbt_rule in qbt_list {
-                                let mut inner_pairs = qbt_rule.into_inner();
-                                let _id = inner_pairs.next().unwrap().as_str();
-                                let integer = inner_pairs
-                                    .next()
-                                    .unwrap()
-                                    .as_str()
-                                    .parse::<usize>()
-                                    .unwrap();
-                                qubits.push(integer);
-                            }
-                        }
-                        _ => continue,
-                    }
-                }
-                gate_dispatch(id, &params, &qubits, defined_custom_gates)
-            }
-            Rule::measurement => {
-                let mut inner_pairs = pair.into_inner();
-                let mut first_argument = inner_pairs.next().unwrap().into_inner();
-                let _first_id = first_argument.next().unwrap().as_str();
-                let first_integer = first_argument.next().unwrap().as_str();
-                let mut second_argument = inner_pairs.next().unwrap().into_inner();
-                let second_id = second_argument.next().unwrap().as_str();
-                let second_integer = second_argument.next().unwrap().as_str();
-                Some(Operation::from(MeasureQubit::new(
-                    first_integer.parse::<usize>().unwrap(),
-                    second_id.to_string(),
-                    second_integer.parse::<usize>().unwrap(),
-                )))
-            }
-            Rule::reset => {
-                let mut inner_pairs = pair.into_inner();
-                let mut first_argument = inner_pairs.next().unwrap().into_inner();
-                let _first_id = first_argument.next().unwrap().as_str();
-                let first_integer = first_argument.next().unwrap().as_str();
-                Some(Operation::from(PragmaActiveReset::new(
-                    first_integer.parse::<usize>().unwrap(),
-                )))
-            }
-            Rule::gate_def => {
-                let mut inner_pairs = pair.into_inner();
-                let id = inner_pairs.next().unwrap().as_str();
-                if gate_dispatch(
-                    id,
-                    &[
-                        "0.0".to_owned(),
-                        "0.0".to_owned(),
-                        "0.0".to_owned(),
-                        "0.0".to_owned(),
-                    ],
-                    &[0_usize, 1_usize, 2_usize, 3_usize],
-                    defined_custom_gates,
-                )
-                .is_some()
-                {
-                    return None;
-                }
-                let mut params: Vec<String> = vec![];
-                let mut qubits: Vec<String> = vec![];
-                let mut definition_circuit = Circuit::new();
-                for pair in inner_pairs.clone() {
-                    match pair.as_rule() {
-                        Rule::parameter_list_def => {
-                            let params_list = inner_pairs.next().unwrap().into_inner();
-                            for param in params_list {
-                                params.push(param.as_str().to_owned());
-                            }
-                        }
-                        Rule::qubit_list_def => {
-                            qubits = inner_pairs
-                                .next()
-                                .unwrap()
-                                .into_inner()
-                                .map(|qbt_pair| qbt_pair.as_str().to_owned())
-                                .collect();
-                        }
-                        Rule::gates_definition => {
-                            for gate_pair in inner_pairs.next().unwrap().into_inner() {
-                                let mut inner_gate_pairs = gate_pair.into_inner();
-                                let id = inner_gate_pairs.next().unwrap().as_str();
-                                let mut gate_params: Vec<String> = vec![];
-                                let mut gate_qubits: Vec<usize> = vec![];
-                                for gate_token in inner_gate_pairs.clone() {
-                                    match gate_token.as_rule() {
-                                        Rule::argument_list_def => {
-                                            gate_params = inner_gate_pairs
-                                                .next()
-                                                .unwrap()
-                                                .into_inner()
-                                                .map(|param| {
-                                                    let param_str = param
-                                                        .as_str()
-                                                        .replace("pi/2", "1.5707963267948966")
-                                                        .replace("pi", "3.141592653589793");
-                                                    param_str.to_string()
-                                                })
-                                                .collect();
-                                        }
-                                        Rule::qubit_list_def => {
-                                            gate_qubits = inner_gate_pairs
-                                                .next()
-                                                .unwrap()
-                                                .into_inner()
-                                                .filter_map(|qbt_pair| {
-                                                    qubits.iter().position(|qubit_name| {
-                                                        qubit_name.as_str() == qbt_pair.as_str()
-                                                    })
-                                                })
-                                                .collect();
-                                        }
-                                        _ => continue,
-                                    }
-                                }
-                                if let Some(gate) = gate_dispatch(
-                                    id,
-                                    &gate_params,
-                                    &gate_qubits,
-                                    defined_custom_gates,
-                                ) {
-                                    definition_circuit.add_operation(gate);
-                                }
-                            }
-                        }
-                        _ => continue,
-                    }
-                }
-                defined_custom_gates.push((id.to_owned(), qubits.len(), params.len()));
-                Some(Operation::from(GateDefinition::new(
-                    definition_circuit,
-                    id.to_owned(),
-                    (0..qubits.len()).collect::<Vec<usize>>(),
-                    params,
-                )))
-            }
-            _ => None,
-        }
-    }
-
-    for pair in pairs {
-        if let Some(op) = parse_single_rule(pair, &mut defined_custom_gates) {
-            circuit.add_operation(op);
-        }
-    }
-
-    Ok(circuit)
+/// Creates the SingleQubitGate of the OpenQASM `U(theta, phi, lambda)` gate.
+///
+/// The global phase of the gate is not kept.
+fn u3_gate(
+    qubit: usize,
+    theta: CalculatorFloat,
+    phi: CalculatorFloat,
+    lambda: CalculatorFloat,
+) -> Operation {
+    let alpha_r = ((phi.clone() + lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).cos();
+    let alpha_i = (-(phi.clone() + lambda.clone()) / 2.0).sin() * (theta.clone() / 2.0).cos();
+    let beta_r = ((phi.clone() - lambda.clone()) / 2.0).cos() * (theta.clone() / 2.0).sin();
+    let beta_i = ((phi - lambda) / 2.0).sin() * (theta / 2.0).sin();
+    Operation::from(SingleQubitGate::new(
+        qubit,
+        alpha_r,
+        alpha_i,
+        beta_r,
+        beta_i,
+        CalculatorFloat::ZERO,
+    ))
 }
 
-/// Translates a QASM file into a qoqo Circuit instance.
+/// Decomposes a standard gate without a roqoqo equivalent into roqoqo operations.
+///
+/// The decompositions are the definitions of the gates in qelib1.inc and stdgates.inc, with
+/// `u1` and `p` written as PhaseShiftState1, `u2(0,pi)` as Hadamard and `u1(pi/4)` as TGate.
+/// They are exact up to a global phase.
 ///
 /// # Arguments
 ///
-/// * `file` - The '.qasm' file to translate.
+/// * `name` - The name of the gate.
+/// * `params` - The parameters of the gate call.
+/// * `qubits` - The qubits the gate acts on.
 ///
 /// # Returns
 ///
-/// * `Circuit` - The translated qoqo Circuit.
-/// * `RoqoqoBackendError::GenericError` - Error encountered while parsing.
-pub fn file_to_circuit(file: File) -> Result<Circuit, RoqoqoBackendError> {
-    let unparsed_file = BufReader::new(file)
-        .lines()
-        .map(|line| line.unwrap() + "\n")
-        .collect::<String>();
+/// * `Some(Vec<Operation>)` - The operations the gate is decomposed into.
+/// * `None` - The gate is not decomposed.
+fn standard_gate_decomposition(
+    name: &str,
+    params: &[CalculatorFloat],
+    qubits: &[usize],
+) -> Option<Vec<Operation>> {
+    if !standard_gate_signature(name)
+        .is_some_and(|signature| signature == (qubits.len(), params.len()))
+    {
+        return None;
+    }
+    let param = |index: usize| params[index].clone();
+    // Controlled square root of X on the target, as written in c3sqrtx and c4x
+    let controlled_phase_on_x = |control: usize, target: usize, theta: f64| {
+        vec![
+            Operation::from(Hadamard::new(target)),
+            Operation::from(ControlledPhaseShift::new(control, target, theta.into())),
+            Operation::from(Hadamard::new(target)),
+        ]
+    };
+    match name {
+        "cry" => Some(vec![
+            Operation::from(RotateY::new(qubits[1], param(0) / 2.0)),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+            Operation::from(RotateY::new(qubits[1], -param(0) / 2.0)),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+        ]),
+        "crz" => Some(vec![
+            Operation::from(RotateZ::new(qubits[1], param(0) / 2.0)),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+            Operation::from(RotateZ::new(qubits[1], -param(0) / 2.0)),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+        ]),
+        "rzz" => Some(vec![
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+            Operation::from(PhaseShiftState1::new(qubits[1], param(0))),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+        ]),
+        "ch" => Some(vec![
+            Operation::from(SGate::new(qubits[1])),
+            Operation::from(Hadamard::new(qubits[1])),
+            Operation::from(TGate::new(qubits[1])),
+            Operation::from(CNOT::new(qubits[0], qubits[1])),
+            Operation::from(InvTGate::new(qubits[1])),
+            Operation::from(Hadamard::new(qubits[1])),
+            Operation::from(InvSGate::new(qubits[1])),
+        ]),
+        "csx" => Some(controlled_phase_on_x(
+            qubits[0],
+            qubits[1],
+            std::f64::consts::FRAC_PI_2,
+        )),
+        "cu3" | "cu" => {
+            let (control, target) = (qubits[0], qubits[1]);
+            let (theta, phi, lambda) = (param(0), param(1), param(2));
+            let mut operations = vec![];
+            if name == "cu" {
+                operations.push(Operation::from(PhaseShiftState1::new(control, param(3))));
+            }
+            operations.extend([
+                Operation::from(PhaseShiftState1::new(
+                    control,
+                    (lambda.clone() + phi.clone()) / 2.0,
+                )),
+                Operation::from(PhaseShiftState1::new(
+                    target,
+                    (lambda.clone() - phi.clone()) / 2.0,
+                )),
+                Operation::from(CNOT::new(control, target)),
+                u3_gate(
+                    target,
+                    -theta.clone() / 2.0,
+                    CalculatorFloat::ZERO,
+                    -(phi.clone() + lambda) / 2.0,
+                ),
+                Operation::from(CNOT::new(control, target)),
+                u3_gate(target, theta / 2.0, phi, CalculatorFloat::ZERO),
+            ]);
+            Some(operations)
+        }
+        "rccx" => {
+            let (a, b, c) = (qubits[0], qubits[1], qubits[2]);
+            Some(vec![
+                Operation::from(Hadamard::new(c)),
+                Operation::from(TGate::new(c)),
+                Operation::from(CNOT::new(b, c)),
+                Operation::from(InvTGate::new(c)),
+                Operation::from(CNOT::new(a, c)),
+                Operation::from(TGate::new(c)),
+                Operation::from(CNOT::new(b, c)),
+                Operation::from(InvTGate::new(c)),
+                Operation::from(Hadamard::new(c)),
+            ])
+        }
+        "rc3x" => {
+            let (a, b, c, d) = (qubits[0], qubits[1], qubits[2], qubits[3]);
+            Some(vec![
+                Operation::from(Hadamard::new(d)),
+                Operation::from(TGate::new(d)),
+                Operation::from(CNOT::new(c, d)),
+                Operation::from(InvTGate::new(d)),
+                Operation::from(Hadamard::new(d)),
+                Operation::from(CNOT::new(a, d)),
+                Operation::from(TGate::new(d)),
+                Operation::from(CNOT::new(b, d)),
+                Operation::from(InvTGate::new(d)),
+                Operation::from(CNOT::new(a, d)),
+                Operation::from(TGate::new(d)),
+                Operation::from(CNOT::new(b, d)),
+                Operation::from(InvTGate::new(d)),
+                Operation::from(Hadamard::new(d)),
+                Operation::from(TGate::new(d)),
+                Operation::from(CNOT::new(c, d)),
+                Operation::from(InvTGate::new(d)),
+                Operation::from(Hadamard::new(d)),
+            ])
+        }
+        "c3sqrtx" => {
+            let (a, b, c, d) = (qubits[0], qubits[1], qubits[2], qubits[3]);
+            let angle = std::f64::consts::PI / 8.0;
+            let mut operations = controlled_phase_on_x(a, d, angle);
+            operations.push(Operation::from(CNOT::new(a, b)));
+            operations.extend(controlled_phase_on_x(b, d, -angle));
+            operations.push(Operation::from(CNOT::new(a, b)));
+            operations.extend(controlled_phase_on_x(b, d, angle));
+            operations.push(Operation::from(CNOT::new(b, c)));
+            operations.extend(controlled_phase_on_x(c, d, -angle));
+            operations.push(Operation::from(CNOT::new(a, c)));
+            operations.extend(controlled_phase_on_x(c, d, angle));
+            operations.push(Operation::from(CNOT::new(b, c)));
+            operations.extend(controlled_phase_on_x(c, d, -angle));
+            operations.push(Operation::from(CNOT::new(a, c)));
+            operations.extend(controlled_phase_on_x(c, d, angle));
+            Some(operations)
+        }
+        "c4x" => {
+            let (a, b, c, d, e) = (qubits[0], qubits[1], qubits[2], qubits[3], qubits[4]);
+            let angle = std::f64::consts::FRAC_PI_2;
+            let rc3x = standard_gate_decomposition("rc3x", &[], &[a, b, c, d])?;
+            // The second rc3x has to be inverted for the decomposition to be exact
+            let rc3x_inverse = rc3x
+                .iter()
+                .rev()
+                .map(|operation| match operation {
+                    Operation::TGate(gate) => Operation::from(InvTGate::new(*gate.qubit())),
+                    Operation::InvTGate(gate) => Operation::from(TGate::new(*gate.qubit())),
+                    _ => operation.clone(),
+                })
+                .collect::<Vec<Operation>>();
+            let mut operations = controlled_phase_on_x(d, e, angle);
+            operations.extend(rc3x);
+            operations.extend(controlled_phase_on_x(d, e, -angle));
+            operations.extend(rc3x_inverse);
+            operations.extend(standard_gate_decomposition("c3sqrtx", &[], &[a, b, c, e])?);
+            Some(operations)
+        }
+        _ => None,
+    }
+}
+
+/// Translates a gate call into roqoqo operations.
+///
+/// Standard gates without a roqoqo equivalent are decomposed, all other gates are translated
+/// with `gate_dispatch`.
+fn gate_operations(
+    name: &str,
+    params: &[CalculatorFloat],
+    qubits: &[usize],
+    defined_custom_gates: &[(String, usize, usize)],
+) -> Vec<Operation> {
+    standard_gate_decomposition(name, params, qubits).unwrap_or_else(|| {
+        gate_dispatch(name, params, qubits, defined_custom_gates)
+            .into_iter()
+            .collect()
+    })
+}
+
+/// Adds an operation to a circuit, merging it with the last operation where possible.
+///
+/// See `merged_operation` for the operations that are merged.
+fn add_operation_grouped(circuit: &mut Circuit, operation: Operation) {
+    if let Some(merged) = circuit
+        .operations()
+        .last()
+        .and_then(|last| merged_operation(last, &operation))
+    {
+        let last_index = circuit.len() - 1;
+        if let Some(last) = circuit.get_mut(last_index) {
+            *last = merged;
+        }
+        return;
+    }
+    circuit.add_operation(operation);
+}
+
+/// Returns the operation replacing an operation and the operation following it, if they are merged.
+///
+/// When the previous operation is a PragmaConditional on the same bit, the body of the new
+/// conditional is appended to it. This is not done when the previous body contains a
+/// measurement, as it could change the value of the condition.
+/// A PragmaSetNumberOfMeasurements following a single PragmaRepeatedMeasurement into the same
+/// readout sets the number of measurements of that PragmaRepeatedMeasurement.
+fn merged_operation(previous: &Operation, operation: &Operation) -> Option<Operation> {
+    match (previous, operation) {
+        // The 3.0Roqoqo dialect writes the number of measurements of a whole-register measurement
+        // as a separate pragma after the measurement
+        (
+            Operation::PragmaRepeatedMeasurement(previous),
+            Operation::PragmaSetNumberOfMeasurements(number),
+        ) if previous.readout() == number.readout() && *previous.number_measurements() == 1 => {
+            Some(Operation::from(PragmaRepeatedMeasurement::new(
+                previous.readout().clone(),
+                *number.number_measurements(),
+                previous.qubit_mapping().clone(),
+            )))
+        }
+        (Operation::PragmaConditional(previous), Operation::PragmaConditional(conditional))
+            if previous.condition_register() == conditional.condition_register()
+                && previous.condition_index() == conditional.condition_index()
+                && !contains_measurement(previous.circuit()) =>
+        {
+            let mut body = previous.circuit().clone();
+            for op in conditional.circuit().iter() {
+                add_operation_grouped(&mut body, op.clone());
+            }
+            Some(Operation::from(PragmaConditional::new(
+                conditional.condition_register().clone(),
+                *conditional.condition_index(),
+                body,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// Removes the qubit mappings of PragmaRepeatedMeasurements measuring every qubit i into bit i.
+///
+/// Without a mapping, all qubits are measured into the readout, so this is only done once the
+/// number of qubits of the whole input is known.
+fn without_full_qubit_mappings(circuit: Circuit, number_qubits: usize) -> Circuit {
+    circuit
+        .into_iter()
+        .map(|operation| match operation {
+            Operation::PragmaRepeatedMeasurement(measurement)
+                if measurement.qubit_mapping().as_ref().is_some_and(|mapping| {
+                    mapping.len() == number_qubits
+                        && mapping.iter().all(|(qubit, bit)| qubit == bit)
+                }) =>
+            {
+                Operation::from(PragmaRepeatedMeasurement::new(
+                    measurement.readout().clone(),
+                    *measurement.number_measurements(),
+                    None,
+                ))
+            }
+            Operation::PragmaConditional(conditional) => Operation::from(PragmaConditional::new(
+                conditional.condition_register().clone(),
+                *conditional.condition_index(),
+                without_full_qubit_mappings(conditional.circuit().clone(), number_qubits),
+            )),
+            Operation::PragmaLoop(pragma_loop) => Operation::from(PragmaLoop::new(
+                pragma_loop.repetitions().clone(),
+                without_full_qubit_mappings(pragma_loop.circuit().clone(), number_qubits),
+            )),
+            operation => operation,
+        })
+        .collect()
+}
+
+/// Returns true if the circuit, or any circuit nested in it, contains a measurement.
+pub(crate) fn contains_measurement(circuit: &Circuit) -> bool {
+    circuit.iter().any(|op| match op {
+        Operation::PragmaConditional(conditional) => contains_measurement(conditional.circuit()),
+        Operation::PragmaLoop(pragma_loop) => contains_measurement(pragma_loop.circuit()),
+        _ => op.tags().contains(&"Measurement"),
+    })
+}
+
+/// Main parse function method.
+///
+/// The grammar is chosen from the `OPENQASM` header: files declaring version 3 are parsed with the
+/// OpenQASM 3.0 grammar, all other files with the OpenQASM 2.0 grammar.
+///
+/// # Arguments
+///
+/// * `file` - The content of the QASM file.
+/// * `file_path` - The path of the file, used to resolve relative includes.
+/// * `options` - The settings of the parser.
+/// * `skip_missing_includes` - Whether included files that cannot be found are skipped with a
+///   warning, for opened files whose directory is unknown.
+fn parse_qasm_file(
+    file: &str,
+    file_path: Option<&Path>,
+    options: &ParserOptions,
+    skip_missing_includes: bool,
+) -> Result<QasmProgram, QasmParseError> {
+    let qasm3 = header_major_version(file) == Some(3);
+    let entry_rule = if qasm3 {
+        Rule::openqasm3
+    } else {
+        Rule::openqasm
+    };
+    let pairs = QoqoQASMParser::parse(entry_rule, file).map_err(syntax_error)?;
+    let mut circuit = Circuit::new();
+    let mut state = ParserState::new(qasm3, file_path, options);
+    state.skip_missing_includes = skip_missing_includes;
+    state.explicit_outputs = pairs
+        .clone()
+        .flatten()
+        .any(|pair| pair.as_rule() == Rule::output);
+    let mut header_comments: Vec<String> = vec![];
+    let mut in_header = true;
+    for pair in pairs {
+        match pair.as_rule() {
+            // The version number of the header
+            Rule::real | Rule::integer => (),
+            Rule::comment if in_header => {
+                header_comments.push(pair.as_str().trim_start_matches("//").trim().to_owned())
+            }
+            _ => in_header = false,
+        }
+        let operations = parse_single_rule(pair, &mut state)?;
+        for definition in state.pending_definitions.drain(..) {
+            circuit.add_operation(definition);
+        }
+        for op in operations {
+            add_operation_grouped(&mut circuit, op);
+        }
+    }
+
+    let circuit = without_full_qubit_mappings(
+        combine_complex_definitions(circuit),
+        state.qubit_registers.number_qubits(),
+    );
+    let classical_registers = circuit
+        .definitions()
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::DefinitionBit(definition) => {
+                Some((definition.name().to_owned(), *definition.length()))
+            }
+            _ => None,
+        })
+        .collect();
+    Ok(QasmProgram {
+        version: state.detected_version(),
+        circuit,
+        qubit_registers: state.qubit_registers,
+        classical_registers,
+        includes: state.includes,
+        gate_signatures: state.declared_gates,
+        header_comments,
+        warnings: state.warnings,
+    })
+}
+
+/// The parsing works like an AST traversal. The structure is defined by the grammar.
+///     - pair.as_rule() represents the rule itself, to get into the inner ones, `.into_inner()` is called
+///     - from the new inner instance we can further move to the right in the rule by calling `next_pair(...)?[.as_str()]`
+fn parse_single_rule(
+    pair: Pair<Rule>,
+    state: &mut ParserState,
+) -> Result<Vec<Operation>, QasmParseError> {
+    match pair.as_rule() {
+        Rule::q_decl | Rule::qubit_decl => {
+            // The OpenQASM 3.0 form is `qubit[size] id;`, the size being optional
+            let mut size = 1;
+            let mut id = "";
+            for inner_pair in pair.clone().into_inner() {
+                match inner_pair.as_rule() {
+                    Rule::id => id = inner_pair.as_str(),
+                    Rule::integer => size = parse_integer(&inner_pair)?,
+                    _ => continue,
+                }
+            }
+            if state.qubit_registers.contains(id) {
+                return Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::Redeclaration,
+                    format!("Quantum register {id} has already been declared"),
+                ));
+            }
+            state.check_register_size(&pair, id, size)?;
+            if state.qubit_registers.add_register(id, size).is_none() {
+                return Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::Unsupported,
+                    format!("Quantum register {id} of size {size} exceeds the number of qubits roqoqo can address"),
+                ));
+            }
+            Ok(vec![])
+        }
+        Rule::c_decl | Rule::bit_decl | Rule::float_decl | Rule::uint_decl => {
+            // The OpenQASM 3.0 form is `[output] type[length] id;`, the length being optional.
+            // Without any `output` in the file all registers are outputs.
+            let mut integer = 1;
+            let mut id = "";
+            let mut is_output = !state.explicit_outputs;
+            for inner_pair in pair.clone().into_inner() {
+                match inner_pair.as_rule() {
+                    Rule::id => id = inner_pair.as_str(),
+                    Rule::integer => integer = parse_integer(&inner_pair)?,
+                    Rule::output => is_output = true,
+                    _ => continue,
+                }
+            }
+            if state.classical_registers.contains_key(id) || state.classical_variables.contains(id)
+            {
+                return Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::Redeclaration,
+                    format!("Classical register {id} has already been declared"),
+                ));
+            }
+            match pair.as_rule() {
+                Rule::float_decl => {
+                    state.classical_variables.insert(id.to_string());
+                    Ok(vec![Operation::from(DefinitionFloat::new(
+                        id.to_string(),
+                        integer,
+                        is_output,
+                    ))])
+                }
+                Rule::uint_decl => {
+                    state.classical_variables.insert(id.to_string());
+                    Ok(vec![Operation::from(DefinitionUsize::new(
+                        id.to_string(),
+                        integer,
+                        is_output,
+                    ))])
+                }
+                _ => {
+                    state.check_register_size(&pair, id, integer)?;
+                    state.classical_registers.insert(id.to_string(), integer);
+                    Ok(vec![Operation::from(DefinitionBit::new(
+                        id.to_string(),
+                        integer,
+                        is_output,
+                    ))])
+                }
+            }
+        }
+        Rule::gate | Rule::modified_gate | Rule::subroutine_call => {
+            let mut id = "";
+            let mut modifiers: Vec<Modifier> = vec![];
+            let mut params: Vec<CalculatorFloat> = vec![];
+            let mut operands: Vec<Vec<usize>> = vec![];
+            let mut assigned_bit: Option<(String, usize)> = None;
+            for inner_pair in pair.clone().into_inner() {
+                match inner_pair.as_rule() {
+                    Rule::name => id = inner_pair.as_str(),
+                    Rule::assigned_bit => {
+                        let bit_pair =
+                            next_pair(&mut inner_pair.clone().into_inner(), &inner_pair)?;
+                        assigned_bit = Some(state.bit_argument(bit_pair)?);
+                    }
+                    Rule::parameter_list => {
+                        for param in inner_pair.into_inner() {
+                            params.push(evaluate_parameter(&param, state.qasm3)?);
+                        }
+                    }
+                    Rule::qubit_list => {
+                        let qbt_list = inner_pair.into_inner();
+                        for qbt_rule in qbt_list {
+                            operands.push(state.qubit_operand(qbt_rule)?);
+                        }
+                    }
+                    // Subroutine calls list the parameters and then the qubits as arguments
+                    Rule::expr => params.push(evaluate_parameter(&inner_pair, state.qasm3)?),
+                    Rule::argument => operands.push(state.qubit_operand(inner_pair)?),
+                    _ => modifiers.push(parse_modifier(&inner_pair)?),
+                }
+            }
+            let number_qubits = gate_qubits_without_controls(&pair, operands.len(), &modifiers)?;
+            check_gate_call(
+                &pair,
+                id,
+                number_qubits,
+                params.len(),
+                &state.defined_custom_gates,
+            )?;
+            state.handle_unknown_gate(&pair, id, number_qubits, params.len())?;
+            if BRAKET_GATE_NAMES.contains(&id) {
+                state.braket_statements = true;
+            }
+            let mut operations: Vec<Operation> = vec![];
+            for qubits in broadcast(&pair, &operands)? {
+                check_distinct_qubits(&pair, &qubits)?;
+                operations
+                    .extend(state.gate_call_operations(&pair, id, &params, &qubits, &modifiers)?);
+            }
+            let operations = state.assign_returned_bit(&pair, id, assigned_bit, operations)?;
+            state.require_included_definitions(&operations);
+            Ok(operations)
+        }
+        Rule::measurement | Rule::measure_assignment => {
+            let mut inner_pairs = pair.clone().into_inner();
+            let first_pair = next_pair(&mut inner_pairs, &pair)?;
+            let second_pair = next_pair(&mut inner_pairs, &pair)?;
+            // The assignment form of OpenQASM 3.0 names the bits before the qubits
+            let (qubit_pair, bit_pair) = if pair.as_rule() == Rule::measure_assignment {
+                (second_pair, first_pair)
+            } else {
+                (first_pair, second_pair)
+            };
+            match (qubit_pair.as_rule(), bit_pair.as_rule()) {
+                (Rule::argument, Rule::argument) => {
+                    let qubit = state.qubit_index(qubit_pair)?;
+                    let (readout, readout_index) = state.bit_argument(bit_pair)?;
+                    Ok(vec![Operation::from(MeasureQubit::new(
+                        qubit,
+                        readout,
+                        readout_index,
+                    ))])
+                }
+                (Rule::id, Rule::id) => {
+                    let qubits = state.qubit_operand(qubit_pair)?;
+                    let readout = bit_pair.as_str();
+                    let length = *state.classical_registers.get(readout).ok_or_else(|| {
+                        custom_error(
+                            &bit_pair,
+                            QasmParseErrorKind::Undeclared,
+                            format!("Classical register {readout} has not been declared"),
+                        )
+                    })?;
+                    // The readout may be longer than the register, as in the backend output
+                    if length < qubits.len() {
+                        return Err(custom_error(
+                            &pair,
+                            QasmParseErrorKind::InvalidGateCall,
+                            format!("Cannot measure {} qubits into classical register {readout} of length {length}", qubits.len()),
+                        ));
+                    }
+                    // Registers may still be declared after the measurement, whether the
+                    // mapping can be left out is only known at the end of the input
+                    let qubit_mapping = qubits.iter().copied().zip(0..length).collect();
+                    Ok(vec![Operation::from(PragmaRepeatedMeasurement::new(
+                        readout.to_string(),
+                        1,
+                        Some(qubit_mapping),
+                    ))])
+                }
+                _ => Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::InvalidGateCall,
+                    "A single qubit must be measured into a single bit and a whole quantum register into a whole classical register".to_string(),
+                )),
+            }
+        }
+        Rule::reset => {
+            let qubits = state.qubit_operand(next_pair(&mut pair.clone().into_inner(), &pair)?)?;
+            Ok(qubits
+                .into_iter()
+                .map(|qubit| Operation::from(PragmaActiveReset::new(qubit)))
+                .collect())
+        }
+        Rule::gate_def | Rule::gate_def3 | Rule::subroutine_def => {
+            let mut inner_pairs = pair.clone().into_inner();
+            let id = next_pair(&mut inner_pairs, &pair)?.as_str();
+            // Definitions of the gates translated to roqoqo operations are not needed
+            if standard_gate_signature(id).is_some() {
+                return Ok(vec![]);
+            }
+            let mut params: Vec<String> = vec![];
+            let mut qubits: Vec<String> = vec![];
+            let mut definition_circuit = Circuit::new();
+            let mut template: Vec<TemplateStatement> = vec![];
+            let mut local_bits: HashSet<String> = HashSet::new();
+            for inner_pair in inner_pairs {
+                match inner_pair.as_rule() {
+                    Rule::parameter_list_def => {
+                        for param in inner_pair.into_inner() {
+                            params.push(param.as_str().to_owned());
+                        }
+                    }
+                    Rule::qubit_list_def => {
+                        qubits = inner_pair
+                            .into_inner()
+                            .map(|qbt_pair| qbt_pair.as_str().to_owned())
+                            .collect();
+                    }
+                    Rule::subroutine_argument => {
+                        let mut argument = inner_pair.clone().into_inner();
+                        let argument_type = next_pair(&mut argument, &inner_pair)?;
+                        let name = next_pair(&mut argument, &inner_pair)?.as_str().to_owned();
+                        if argument_type.as_rule() == Rule::qubit_type {
+                            qubits.push(name);
+                        } else {
+                            params.push(name);
+                        }
+                    }
+                    Rule::gates_definition | Rule::gates_definition3 | Rule::subroutine_body => {
+                        for gate_pair in inner_pair.into_inner() {
+                            match gate_pair.as_rule() {
+                                Rule::comment | Rule::return_statement => continue,
+                                Rule::bit_decl => {
+                                    if let Some(bit_pair) = gate_pair
+                                        .clone()
+                                        .into_inner()
+                                        .find(|bit_pair| bit_pair.as_rule() == Rule::id)
+                                    {
+                                        local_bits.insert(bit_pair.as_str().to_owned());
+                                    }
+                                }
+                                Rule::measurement | Rule::measure_assignment | Rule::reset => {
+                                    let statement =
+                                        subroutine_statement(&gate_pair, id, &qubits, &local_bits)?;
+                                    if let Some(operation) =
+                                        statement.operation(|position| position)
+                                    {
+                                        definition_circuit.add_operation(operation);
+                                    }
+                                    template.push(statement);
+                                }
+                                _ => {
+                                    let (operations, statement) =
+                                        state.gate_body_call(&gate_pair, &qubits)?;
+                                    for gate in operations {
+                                        definition_circuit.add_operation(gate);
+                                    }
+                                    template.extend(statement);
+                                }
+                            }
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+            if state.inline_gates {
+                state
+                    .gate_templates
+                    .insert(id.to_owned(), (params.clone(), template));
+            }
+            let definition = GateDefinition::new(
+                definition_circuit,
+                id.to_owned(),
+                (0..qubits.len()).collect::<Vec<usize>>(),
+                params,
+            );
+            let returns_bit = definition.circuit().iter().any(|operation| {
+                matches!(operation, Operation::MeasureQubit(measurement)
+                    if local_bits.contains(measurement.readout()))
+            });
+            if returns_bit {
+                // The definition is added at the first call, with the bit the call is assigned to
+                state.declare_gate(&definition);
+                state.defined_custom_gates.push((
+                    id.to_owned(),
+                    qubits.len(),
+                    definition.free_parameters().len(),
+                ));
+                state.returning_subroutines.insert(
+                    id.to_owned(),
+                    ReturningSubroutine {
+                        definition,
+                        local_bits,
+                        assigned_bit: None,
+                    },
+                );
+                return Ok(vec![]);
+            }
+            state
+                .gate_definitions
+                .insert(id.to_owned(), definition.clone());
+            state.declare_gate(&definition);
+            Ok(state.add_definition(definition))
+        }
+        Rule::opaque => {
+            let mut inner_pairs = pair.clone().into_inner();
+            let id = next_pair(&mut inner_pairs, &pair)?.as_str();
+            if standard_gate_signature(id).is_some() {
+                return Ok(vec![]);
+            }
+            let mut params: Vec<String> = vec![];
+            let mut number_qubits = 0;
+            for inner_pair in inner_pairs {
+                match inner_pair.as_rule() {
+                    Rule::parameter_list_def => {
+                        for param in inner_pair.into_inner() {
+                            params.push(param.as_str().to_owned());
+                        }
+                    }
+                    Rule::qubit_list_def => number_qubits = inner_pair.into_inner().count(),
+                    _ => continue,
+                }
+            }
+            // The implementation of an opaque gate is unknown, it is defined by an empty
+            // placeholder circuit so that calls of the gate can be translated.
+            let definition = GateDefinition::new(
+                Circuit::new(),
+                id.to_owned(),
+                (0..number_qubits).collect::<Vec<usize>>(),
+                params,
+            );
+            state.declare_gate(&definition);
+            Ok(state.add_definition(definition))
+        }
+        Rule::barrier => {
+            let mut qubits: Vec<usize> = vec![];
+            for inner_pair in pair.clone().into_inner() {
+                if inner_pair.as_rule() == Rule::qubit_list {
+                    for qbt_rule in inner_pair.into_inner() {
+                        qubits.extend(state.qubit_operand(qbt_rule)?);
+                    }
+                }
+            }
+            // A barrier without operands acts on all qubits
+            if qubits.is_empty() {
+                qubits = (0..state.qubit_registers.number_qubits()).collect();
+            }
+            Ok(vec![Operation::from(PragmaStopParallelBlock::new(
+                qubits,
+                CalculatorFloat::ZERO,
+            ))])
+        }
+        Rule::delay => {
+            let mut sleep_time = 0.0;
+            let mut qubits: Vec<usize> = vec![];
+            for inner_pair in pair.clone().into_inner() {
+                match inner_pair.as_rule() {
+                    Rule::duration => sleep_time = state.duration_seconds(&inner_pair)?,
+                    _ => {
+                        for qbt_rule in inner_pair.into_inner() {
+                            qubits.extend(state.qubit_operand(qbt_rule)?);
+                        }
+                    }
+                }
+            }
+            // A delay without operands acts on all qubits
+            if qubits.is_empty() {
+                qubits = (0..state.qubit_registers.number_qubits()).collect();
+            }
+            Ok(vec![Operation::from(PragmaSleep::new(
+                qubits,
+                CalculatorFloat::from(sleep_time),
+            ))])
+        }
+        Rule::box_block => {
+            // The duration of a box is kept as the execution time of a parallel block ending
+            // after the body
+            let mut inner_pairs = pair.clone().into_inner();
+            let mut block = next_pair(&mut inner_pairs, &pair)?;
+            let mut duration = None;
+            if block.as_rule() == Rule::duration {
+                duration = Some(state.duration_seconds(&block)?);
+                block = next_pair(&mut inner_pairs, &pair)?;
+            }
+            let body = parse_block(block, state)?;
+            let mut qubits: Vec<usize> = match body.involved_qubits() {
+                InvolvedQubits::Set(qubits) => qubits.into_iter().collect(),
+                InvolvedQubits::All => (0..state.qubit_registers.number_qubits()).collect(),
+                _ => vec![],
+            };
+            qubits.sort_unstable();
+            let mut operations: Vec<Operation> = body.into_iter().collect();
+            if let Some(duration) = duration {
+                operations.push(Operation::from(PragmaStopParallelBlock::new(
+                    qubits,
+                    CalculatorFloat::from(duration),
+                )));
+            }
+            Ok(operations)
+        }
+        Rule::input_decl => {
+            // All input types are translated to a float parameter
+            let id = pair
+                .clone()
+                .into_inner()
+                .find(|inner_pair| inner_pair.as_rule() == Rule::id)
+                .ok_or_else(|| {
+                    custom_error(
+                        &pair,
+                        QasmParseErrorKind::Syntax,
+                        "Incomplete statement".to_string(),
+                    )
+                })?
+                .as_str();
+            if !state.declared_inputs.insert(id.to_string()) {
+                return Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::Redeclaration,
+                    format!("Input parameter {id} has already been declared"),
+                ));
+            }
+            let value = state.input_values.get(id).copied().unwrap_or_default();
+            Ok(vec![Operation::from(InputSymbolic::new(
+                id.to_string(),
+                value,
+            ))])
+        }
+        Rule::bit_assignment => {
+            let mut inner_pairs = pair.clone().into_inner();
+            let (register, index) = state.bit_argument(next_pair(&mut inner_pairs, &pair)?)?;
+            let value = matches!(next_pair(&mut inner_pairs, &pair)?.as_str(), "1" | "true");
+            Ok(vec![Operation::from(InputBit::new(register, index, value))])
+        }
+        Rule::include => parse_include(pair, state),
+        Rule::pragma => {
+            match pair
+                .as_str()
+                .trim_start_matches("pragma")
+                .split_whitespace()
+                .next()
+            {
+                Some("roqoqo") => state.roqoqo_pragmas = true,
+                Some("braket") => state.braket_statements = true,
+                _ => (),
+            }
+            // Circuits contained in roqoqo pragmas are written as a block following the pragma
+            let mut inner_pairs = pair.clone().into_inner();
+            let text = next_pair(&mut inner_pairs, &pair)?.as_str();
+            let circuit = inner_pairs
+                .next()
+                .map(|block| parse_block(block, state))
+                .transpose()?;
+            Ok(parse_roqoqo_pragma(text, circuit)
+                .map_err(|error| custom_error(&pair, error.kind(), error.message().to_string()))?
+                .into_iter()
+                .collect())
+        }
+        Rule::gphase => {
+            let phase = evaluate_parameter(
+                &next_pair(&mut pair.clone().into_inner(), &pair)?,
+                state.qasm3,
+            )?;
+            Ok(vec![Operation::from(PragmaGlobalPhase::new(phase))])
+        }
+        Rule::for_loop => {
+            // The width of the loop variable type is not needed
+            let mut inner_pairs = pair
+                .clone()
+                .into_inner()
+                .filter(|inner_pair| inner_pair.as_rule() != Rule::integer);
+            let loop_variable = next_pair(&mut inner_pairs, &pair)?.as_str();
+            let repetitions = loop_repetitions(&next_pair(&mut inner_pairs, &pair)?)?;
+            let body_pair = next_pair(&mut inner_pairs, &pair)?;
+            // A PragmaLoop repeats the same body, the value of the loop variable is not available
+            if let Some(usage) = body_pair
+                .clone()
+                .into_inner()
+                .flatten()
+                .filter(|inner_pair| inner_pair.as_rule() == Rule::expr)
+                .flat_map(|expression| expression.into_inner().flatten())
+                .find(|inner_pair| {
+                    inner_pair.as_rule() == Rule::id && inner_pair.as_str() == loop_variable
+                })
+            {
+                return Err(custom_error(
+                    &usage,
+                    QasmParseErrorKind::Unsupported,
+                    format!(
+                        "The loop variable {loop_variable} cannot be used in the body of the loop"
+                    ),
+                ));
+            }
+            let body = parse_block(body_pair, state)?;
+            Ok(vec![Operation::from(PragmaLoop::new(repetitions, body))])
+        }
+        Rule::if_statement | Rule::if_block => {
+            let mut inner_pairs = pair.clone().into_inner();
+            let condition = next_pair(&mut inner_pairs, &pair)?;
+            let value = next_pair(&mut inner_pairs, &pair)?.as_str();
+            // roqoqo can only condition on a bit being set, a condition on a whole register
+            // is expressed by nesting conditionals on each of its bits.
+            let condition_bits: Vec<(String, usize)> = match condition.as_rule() {
+                Rule::argument => {
+                    let bit = state.bit_argument(condition)?;
+                    if value != "1" {
+                        return Err(custom_error(
+                            &pair,
+                            QasmParseErrorKind::Unsupported,
+                            "A single bit can only be checked for the value 1".to_string(),
+                        ));
+                    }
+                    vec![bit]
+                }
+                _ => {
+                    let register = condition.as_str();
+                    let length = *state.classical_registers.get(register).ok_or_else(|| {
+                        custom_error(
+                            &pair,
+                            QasmParseErrorKind::Undeclared,
+                            format!("Classical register {register} has not been declared"),
+                        )
+                    })?;
+                    let all_bits_set = 1_u128
+                        .checked_shl(length as u32)
+                        .map(|shifted| (shifted - 1).to_string());
+                    if all_bits_set.as_deref() != Some(value) {
+                        return Err(custom_error(
+                            &pair,
+                            QasmParseErrorKind::Unsupported,
+                            format!("Only conditions requiring all bits of a register to be 1 are supported, {register}=={value} cannot be expressed"),
+                        ));
+                    }
+                    (0..length)
+                        .map(|index| (register.to_string(), index))
+                        .collect()
+                }
+            };
+            let mut body = parse_block(next_pair(&mut inner_pairs, &pair)?, state)?;
+            for (register, index) in condition_bits.into_iter().rev() {
+                let mut outer_body = Circuit::new();
+                outer_body.add_operation(PragmaConditional::new(register, index, body));
+                body = outer_body;
+            }
+            Ok(body.into_iter().collect())
+        }
+        _ => Ok(vec![]),
+    }
+}
 
-    parse_qasm_file(&unparsed_file).map_err(|x| RoqoqoBackendError::GenericError {
-        msg: format!("Error during conversion: {x}"),
+/// Parses the body of a loop or of a classically controlled block into a Circuit.
+///
+/// The body is either a `{ ... }` block or a single statement.
+fn parse_block(pair: Pair<Rule>, state: &mut ParserState) -> Result<Circuit, QasmParseError> {
+    let mut circuit = Circuit::new();
+    let statements = match pair.as_rule() {
+        Rule::block => pair.into_inner().collect::<Vec<Pair<Rule>>>(),
+        _ => vec![pair],
+    };
+    for statement in statements {
+        for op in parse_single_rule(statement, state)? {
+            add_operation_grouped(&mut circuit, op);
+        }
+    }
+    Ok(circuit)
+}
+
+/// Translates the statements of an included file.
+///
+/// Gate definitions are kept in the parser state and only added to the circuit when the gate
+/// is used, all other statements are translated as if they replaced the include statement.
+fn parse_include(
+    pair: Pair<Rule>,
+    state: &mut ParserState,
+) -> Result<Vec<Operation>, QasmParseError> {
+    let name = next_pair(&mut pair.clone().into_inner(), &pair)?.as_str();
+    let (path, content) = match name {
+        "qelib1.inc" => (PathBuf::from(name), QELIB1_INC.to_string()),
+        "stdgates.inc" => (PathBuf::from(name), STDGATES_INC.to_string()),
+        _ => {
+            let Some(path) = state
+                .find_include(name)
+                .and_then(|path| path.canonicalize().ok())
+            else {
+                if state.skip_missing_includes {
+                    state.warn(
+                        &pair,
+                        format!("Included file {name} could not be found and is skipped"),
+                    );
+                    return Ok(vec![]);
+                }
+                return Err(custom_error(
+                    &pair,
+                    QasmParseErrorKind::Include,
+                    format!("Included file {name} could not be found"),
+                ));
+            };
+            let content = std::fs::read_to_string(&path).map_err(|err| {
+                custom_error(
+                    &pair,
+                    QasmParseErrorKind::Include,
+                    format!("Included file {name} could not be read: {err}"),
+                )
+            })?;
+            (path, content + "\n")
+        }
+    };
+    let including_files: Vec<&PathBuf> =
+        state.file_path.iter().chain(&state.include_stack).collect();
+    if let Some(start) = including_files.iter().position(|file| **file == path) {
+        let cycle = including_files[start..]
+            .iter()
+            .map(|file| file.display().to_string())
+            .chain([path.display().to_string()])
+            .collect::<Vec<String>>()
+            .join(" -> ");
+        return Err(custom_error(
+            &pair,
+            QasmParseErrorKind::Include,
+            format!("Include cycle: {cycle}"),
+        ));
+    }
+    // Files included a second time do not add anything new
+    if !state.included_files.insert(path.clone()) {
+        return Ok(vec![]);
+    }
+    state.includes.push(name.to_owned());
+    let rule = if state.qasm3 {
+        Rule::include_file3
+    } else {
+        Rule::include_file
+    };
+    state.include_stack.push(path);
+    let result = QoqoQASMParser::parse(rule, &content)
+        .map_err(syntax_error)
+        .and_then(|included_pairs| {
+            let mut operations = vec![];
+            for included_pair in included_pairs {
+                operations.extend(parse_single_rule(included_pair, state)?);
+            }
+            Ok(operations)
+        });
+    state.include_stack.pop();
+    result.map_err(|error| {
+        custom_error(
+            &pair,
+            error.kind(),
+            format!("Error in included file {name}: {error}"),
+        )
     })
 }
 
+/// Combines the `name_re` and `name_im` float registers written for a DefinitionComplex.
+///
+/// Only pairs with the same length and output flag are combined, the DefinitionComplex takes the
+/// place of the real part.
+fn combine_complex_definitions(circuit: Circuit) -> Circuit {
+    let floats: HashMap<&str, (usize, bool)> = circuit
+        .definitions()
+        .iter()
+        .filter_map(|operation| match operation {
+            Operation::DefinitionFloat(definition) => Some((
+                definition.name().as_str(),
+                (*definition.length(), *definition.is_output()),
+            )),
+            _ => None,
+        })
+        .collect();
+    let complex_names: HashSet<&str> = floats
+        .iter()
+        .filter_map(|(name, signature)| {
+            let base = name.strip_suffix("_re")?;
+            (floats.get(format!("{base}_im").as_str()) == Some(signature)).then_some(base)
+        })
+        .collect();
+    if complex_names.is_empty() {
+        return circuit;
+    }
+    let mut combined = Circuit::new();
+    for operation in circuit.iter() {
+        if let Operation::DefinitionFloat(definition) = operation {
+            let name = definition.name();
+            if let Some(base) = name
+                .strip_suffix("_re")
+                .filter(|base| complex_names.contains(base))
+            {
+                combined.add_operation(DefinitionComplex::new(
+                    base.to_owned(),
+                    *definition.length(),
+                    *definition.is_output(),
+                ));
+                continue;
+            }
+            if name
+                .strip_suffix("_im")
+                .is_some_and(|base| complex_names.contains(base))
+            {
+                continue;
+            }
+        }
+        combined.add_operation(operation.clone());
+    }
+    combined
+}
+
+/// Translates a QASM file into a qoqo Circuit instance.
+///
+/// OpenQASM 2.0 and 3.0 are supported, the version is read from the `OPENQASM` header. The
+/// whole file is read into memory, very large files can be translated with `reader_to_circuit`.
+///
+/// The directory of an opened file is unknown, so relative includes are looked up in the working
+/// directory only, and included files that cannot be found are skipped with a warning. Included
+/// files are looked up next to the file with `path_to_qasm_program_with_options`.
+///
+/// # Arguments
+///
+/// * `file` - The '.qasm' file to translate.
+///
+/// # Returns
+///
+/// * `Circuit` - The translated qoqo Circuit.
+/// * `QasmParseError` - Error encountered while parsing, with its position in the file.
+pub fn file_to_circuit(file: File) -> Result<Circuit, QasmParseError> {
+    file_to_qasm_program(file).map(QasmProgram::into_circuit)
+}
+
 /// Translates a QASM string into a qoqo Circuit instance.
 ///
+/// OpenQASM 2.0 and 3.0 are supported, the version is read from the `OPENQASM` header.
+///
 /// # Arguments
 ///
 /// * `input` - The QASM string to translate.
@@ -479,12 +2698,540 @@ pub fn file_to_circuit(file: File) -> Result<Circuit, RoqoqoBackendError> {
 /// # Returns
 ///
 /// * `Circuit` - The translated qoqo Circuit.
-/// * `RoqoqoBackendError::GenericError` - Error encountered while parsing.
-pub fn string_to_circuit(input: &str) -> Result<Circuit, RoqoqoBackendError> {
-    let with_newline = input.to_owned() + "\n";
-    parse_qasm_file(&with_newline).map_err(|x| RoqoqoBackendError::GenericError {
-        msg: format!("Error during conversion: {x}"),
-    })
+/// * `QasmParseError` - Error encountered while parsing, with its position in the string.
+pub fn string_to_circuit(input: &str) -> Result<Circuit, QasmParseError> {
+    string_to_qasm_program(input).map(QasmProgram::into_circuit)
+}
+
+/// Translates a QASM file into a qoqo Circuit instance and the table of its quantum registers.
+///
+/// The qubits of all quantum registers are mapped to one flat range of roqoqo qubit indices, the
+/// returned table gives the index of each qubit of each register.
+///
+/// # Arguments
+///
+/// * `file` - The '.qasm' file to translate.
+///
+/// # Returns
+///
+/// * `(Circuit, QubitRegisters)` - The translated qoqo Circuit and the quantum register table.
+/// * `QasmParseError` - Error encountered while parsing, with its position in the file.
+pub fn file_to_circuit_with_registers(
+    file: File,
+) -> Result<(Circuit, QubitRegisters), QasmParseError> {
+    let program = file_to_qasm_program(file)?;
+    let registers = program.qubit_registers().clone();
+    Ok((program.into_circuit(), registers))
+}
+
+/// Translates a QASM string into a qoqo Circuit instance and the table of its quantum registers.
+///
+/// See `file_to_circuit_with_registers`.
+///
+/// # Arguments
+///
+/// * `input` - The QASM string to translate.
+///
+/// # Returns
+///
+/// * `(Circuit, QubitRegisters)` - The translated qoqo Circuit and the quantum register table.
+/// * `QasmParseError` - Error encountered while parsing, with its position in the string.
+pub fn string_to_circuit_with_registers(
+    input: &str,
+) -> Result<(Circuit, QubitRegisters), QasmParseError> {
+    let program = string_to_qasm_program(input)?;
+    let registers = program.qubit_registers().clone();
+    Ok((program.into_circuit(), registers))
+}
+
+/// Translates a QASM file into a QasmProgram holding the translated Circuit and the
+/// information of the file.
+///
+/// Next to the Circuit, the program holds the detected version and dialect, the quantum and
+/// classical registers, the included files, the signatures of the declared gates, the comments
+/// of the header and the parser warnings. Included files are resolved as in `file_to_circuit`.
+///
+/// # Arguments
+///
+/// * `file` - The '.qasm' file to translate.
+///
+/// # Returns
+///
+/// * `QasmProgram` - The translated file.
+/// * `QasmParseError` - Error encountered while parsing, with its position in the file.
+pub fn file_to_qasm_program(file: File) -> Result<QasmProgram, QasmParseError> {
+    parse_qasm_file(
+        &read_qasm_file(file)?,
+        None,
+        &ParserOptions::default(),
+        true,
+    )
+}
+
+/// Translates a QASM string into a QasmProgram holding the translated Circuit and the
+/// information of the string.
+///
+/// See `file_to_qasm_program`.
+///
+/// # Arguments
+///
+/// * `input` - The QASM string to translate.
+///
+/// # Returns
+///
+/// * `QasmProgram` - The translated string.
+/// * `QasmParseError` - Error encountered while parsing, with its position in the string.
+pub fn string_to_qasm_program(input: &str) -> Result<QasmProgram, QasmParseError> {
+    string_to_qasm_program_with_options(input, &ParserOptions::default())
+}
+
+/// Translates the QASM file at a path into a QasmProgram, using the given parser settings.
+///
+/// This is the entry point for all settings of the parser when reading from a path, see
+/// `file_to_qasm_program` for the content of the program. Included files are looked up relative
+/// to the directory of the file.
+///
+/// # Arguments
+///
+/// * `path` - The path of the '.qasm' file to translate.
+/// * `options` - The settings of the parser.
+///
+/// # Returns
+///
+/// * `QasmProgram` - The translated file.
+/// * `QasmParseError` - Error encountered while reading or parsing the file.
+pub fn path_to_qasm_program_with_options(
+    path: &Path,
+    options: &ParserOptions,
+) -> Result<QasmProgram, QasmParseError> {
+    parse_qasm_file(&read_qasm_path(path)?, Some(path), options, false)
+}
+
+/// Translates a QASM string into a QasmProgram, using the given parser settings.
+///
+/// This is the entry point for all settings of the parser when reading from a string, see
+/// `file_to_qasm_program` for the content of the program. Relative includes are looked up in the
+/// working directory before the include paths.
+///
+/// # Arguments
+///
+/// * `input` - The QASM string to translate.
+/// * `options` - The settings of the parser.
+///
+/// # Returns
+///
+/// * `QasmProgram` - The translated string.
+/// * `QasmParseError` - Error encountered while parsing.
+pub fn string_to_qasm_program_with_options(
+    input: &str,
+    options: &ParserOptions,
+) -> Result<QasmProgram, QasmParseError> {
+    parse_qasm_file(&(input.to_owned() + "\n"), None, options, false)
+}
+
+/// Translates a QASM file into a roqoqo QuantumProgram reading out the classical registers.
+///
+/// See `QasmProgram::into_quantum_program` for the construction of the program.
+///
+/// # Arguments
+///
+/// * `file` - The '.qasm' file to translate.
+///
+/// # Returns
+///
+/// * `QuantumProgram::ClassicalRegister` - The program running the translated Circuit.
+/// * `QasmParseError` - Error encountered while parsing, or the file contains no measurement.
+pub fn file_to_quantum_program(file: File) -> Result<QuantumProgram, QasmParseError> {
+    file_to_qasm_program(file)?.into_quantum_program()
+}
+
+/// Translates a QASM string into a roqoqo QuantumProgram reading out the classical registers.
+///
+/// See `QasmProgram::into_quantum_program` for the construction of the program.
+///
+/// # Arguments
+///
+/// * `input` - The QASM string to translate.
+///
+/// # Returns
+///
+/// * `QuantumProgram::ClassicalRegister` - The program running the translated Circuit.
+/// * `QasmParseError` - Error encountered while parsing, or the string contains no measurement.
+pub fn string_to_quantum_program(input: &str) -> Result<QuantumProgram, QasmParseError> {
+    string_to_qasm_program(input)?.into_quantum_program()
+}
+
+/// Translates QASM input statement by statement into roqoqo operations.
+///
+/// The input is read from any `BufRead` one statement at a time, so that the memory used while
+/// parsing is bounded by the longest statement and the tables of registers and gates, instead of
+/// growing with the size of the input. The operations are yielded in the order of the input,
+/// each operation as soon as the statement following it is read. Consecutive operations are
+/// merged as in `string_to_circuit`.
+///
+/// Unlike `string_to_circuit`, the parser cannot look ahead in the input:
+///
+/// * Registers declared before the first `output` declaration are outputs.
+/// * `name_re` and `name_im` float registers are only combined into a `DefinitionComplex` when
+///   they are declared one after the other.
+/// * Measurements of whole registers always have an explicit qubit mapping, as registers may
+///   still be declared after them.
+///
+/// Relative includes are looked up in the working directory before the include paths.
+#[derive(Debug)]
+pub struct QasmStreamParser<R: BufRead> {
+    /// The QASM input.
+    reader: R,
+    /// The settings of the parser.
+    options: ParserOptions,
+    /// The state of the parser, created when the `OPENQASM` header has been read.
+    state: Option<ParserState>,
+    /// Number of lines read from the input.
+    line_number: usize,
+    /// Depth of the braces open at the end of the lines read for the current statement.
+    brace_depth: usize,
+    /// The last character of the current statement outside of comments and whitespace.
+    last_character: Option<char>,
+    /// The last translated operation, kept until it is known not to be merged with the next one.
+    held: Option<Operation>,
+    /// Translated operations that are ready to be yielded.
+    ready: VecDeque<Operation>,
+    /// Whether the input has been read completely or an error has been encountered.
+    finished: bool,
+    /// The error encountered, yielded after the operations in front of it.
+    error: Option<QasmParseError>,
+}
+
+impl<R: BufRead> QasmStreamParser<R> {
+    /// Creates a parser reading QASM input from a reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The QASM input.
+    /// * `options` - The settings of the parser.
+    pub fn new(reader: R, options: &ParserOptions) -> Self {
+        Self {
+            reader,
+            options: options.clone(),
+            state: None,
+            line_number: 0,
+            brace_depth: 0,
+            last_character: None,
+            held: None,
+            ready: VecDeque::new(),
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Returns the table of the quantum registers declared in the statements read so far.
+    pub fn qubit_registers(&self) -> Option<&QubitRegisters> {
+        self.state.as_ref().map(|state| &state.qubit_registers)
+    }
+
+    /// Returns the warnings collected in the statements read so far.
+    pub fn warnings(&self) -> &[QasmParseWarning] {
+        self.state
+            .as_ref()
+            .map(|state| state.warnings.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Reads the lines of the next statement.
+    ///
+    /// A statement ends with a line ending in `;` or `}` outside of all braces. Blank lines and
+    /// comment lines are returned on their own.
+    ///
+    /// # Returns
+    ///
+    /// * `Some((String, usize))` - The statement and the number of lines in front of it.
+    /// * `None` - The input has been read completely.
+    fn read_statement(&mut self) -> Result<Option<(String, usize)>, QasmParseError> {
+        let line_offset = self.line_number;
+        let mut statement = String::new();
+        loop {
+            let length = statement.len();
+            let read = self.reader.read_line(&mut statement).map_err(|err| {
+                QasmParseError::new(
+                    QasmParseErrorKind::Io,
+                    format!("Could not read the input: {err}"),
+                    self.line_number + 1,
+                    0,
+                    String::new(),
+                )
+            })?;
+            if read == 0 {
+                // An unfinished statement is left to the grammar to report
+                return Ok((!statement.is_empty()).then(|| (statement + "\n", line_offset)));
+            }
+            self.line_number += 1;
+            if !statement.ends_with('\n') {
+                statement.push('\n');
+            }
+            self.scan_line(&statement[length..]);
+            let complete = self.brace_depth == 0
+                && matches!(self.last_character, None | Some(';') | Some('}'));
+            if complete {
+                self.last_character = None;
+                return Ok(Some((statement, line_offset)));
+            }
+        }
+    }
+
+    /// Updates the brace depth and the last character of the current statement with a line.
+    fn scan_line(&mut self, line: &str) {
+        let mut in_string = false;
+        let mut characters = line.chars().peekable();
+        while let Some(character) = characters.next() {
+            match character {
+                '"' => in_string = !in_string,
+                _ if in_string => (),
+                '/' if characters.peek() == Some(&'/') => break,
+                '{' => self.brace_depth += 1,
+                '}' => self.brace_depth = self.brace_depth.saturating_sub(1),
+                _ => (),
+            }
+            if !character.is_whitespace() {
+                self.last_character = Some(character);
+            }
+        }
+    }
+
+    /// Translates a statement and queues the resulting operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `statement` - The text of the statement.
+    /// * `line_offset` - The number of lines in front of the statement.
+    fn parse_statement(
+        &mut self,
+        statement: &str,
+        line_offset: usize,
+    ) -> Result<(), QasmParseError> {
+        let shift = |error: QasmParseError| {
+            QasmParseError::new(
+                error.kind(),
+                error.message().to_owned(),
+                error.line() + line_offset,
+                error.column(),
+                error.snippet().to_owned(),
+            )
+        };
+        let header_version = header_major_version(statement);
+        let state = match self.state.as_mut() {
+            Some(_) if header_version.is_some() => {
+                return Err(shift(QasmParseError::new(
+                    QasmParseErrorKind::Syntax,
+                    "The OPENQASM header can only be given once".to_string(),
+                    1,
+                    1,
+                    statement.trim().to_owned(),
+                )));
+            }
+            Some(state) => state,
+            // Comments in front of the header are skipped
+            None if statement
+                .lines()
+                .map(str::trim)
+                .all(|line| line.is_empty() || line.starts_with("//")) =>
+            {
+                return Ok(());
+            }
+            None => {
+                let qasm3 = header_version.ok_or_else(|| {
+                    shift(QasmParseError::new(
+                        QasmParseErrorKind::Syntax,
+                        "The input has to start with an OPENQASM header".to_string(),
+                        1,
+                        1,
+                        statement.trim().to_owned(),
+                    ))
+                })? == 3;
+                self.state
+                    .insert(ParserState::new(qasm3, None, &self.options))
+            }
+        };
+        let rule = if state.qasm3 {
+            Rule::include_file3
+        } else {
+            Rule::include_file
+        };
+        let pairs = QoqoQASMParser::parse(rule, statement)
+            .map_err(syntax_error)
+            .map_err(shift)?;
+        if pairs
+            .clone()
+            .flatten()
+            .any(|pair| pair.as_rule() == Rule::output)
+        {
+            state.explicit_outputs = true;
+        }
+        state.line_offset = line_offset;
+        for pair in pairs {
+            let operations = parse_single_rule(pair, state).map_err(shift)?;
+            if !state.pending_definitions.is_empty() {
+                self.ready.extend(self.held.take());
+                self.ready.extend(state.pending_definitions.drain(..));
+            }
+            for operation in operations {
+                self.held = Some(match self.held.take() {
+                    Some(previous) => match merged_operation(&previous, &operation)
+                        .or_else(|| combined_complex_definition(&previous, &operation))
+                    {
+                        Some(merged) => merged,
+                        None => {
+                            self.ready.push_back(previous);
+                            operation
+                        }
+                    },
+                    None => operation,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for QasmStreamParser<R> {
+    type Item = Result<Operation, QasmParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(operation) = self.ready.pop_front() {
+                return Some(Ok(operation));
+            }
+            if self.finished {
+                return self.error.take().map(Err);
+            }
+            let result = self.read_statement().and_then(|statement| match statement {
+                Some((statement, line_offset)) => self.parse_statement(&statement, line_offset),
+                None => {
+                    if self.state.is_none() {
+                        return Err(QasmParseError::new(
+                            QasmParseErrorKind::Syntax,
+                            "The input has to start with an OPENQASM header".to_string(),
+                            self.line_number,
+                            0,
+                            String::new(),
+                        ));
+                    }
+                    self.finished = true;
+                    self.ready.extend(self.held.take());
+                    Ok(())
+                }
+            });
+            // The operations in front of the error are yielded before the error
+            if let Err(error) = result {
+                self.finished = true;
+                self.ready.extend(self.held.take());
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// Returns the DefinitionComplex replacing a `name_re` float register followed by a `name_im`
+/// float register of the same length and output flag.
+fn combined_complex_definition(previous: &Operation, operation: &Operation) -> Option<Operation> {
+    match (previous, operation) {
+        (Operation::DefinitionFloat(real), Operation::DefinitionFloat(imaginary)) => {
+            let base = real.name().strip_suffix("_re")?;
+            (imaginary.name().strip_suffix("_im") == Some(base)
+                && real.length() == imaginary.length()
+                && real.is_output() == imaginary.is_output())
+            .then(|| {
+                Operation::from(DefinitionComplex::new(
+                    base.to_owned(),
+                    *real.length(),
+                    *real.is_output(),
+                ))
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Translates QASM input read statement by statement into a qoqo Circuit instance.
+///
+/// See `QasmStreamParser` for the differences to `string_to_circuit`. The memory used for
+/// parsing does not grow with the size of the input, only the Circuit does.
+///
+/// # Arguments
+///
+/// * `reader` - The QASM input.
+/// * `options` - The settings of the parser.
+///
+/// # Returns
+///
+/// * `Circuit` - The translated qoqo Circuit.
+/// * `QasmParseError` - Error encountered while reading or parsing, with its position in the input.
+pub fn reader_to_circuit<R: BufRead>(
+    reader: R,
+    options: &ParserOptions,
+) -> Result<Circuit, QasmParseError> {
+    let mut circuit = Circuit::new();
+    reader_for_each_operation(reader, options, |operation| {
+        circuit.add_operation(operation)
+    })?;
+    Ok(circuit)
+}
+
+/// Translates QASM input read statement by statement and passes each operation to a callback.
+///
+/// See `QasmStreamParser` for the differences to `string_to_circuit`. Apart from the tables of
+/// registers and gates, the memory used does not grow with the size of the input.
+///
+/// # Arguments
+///
+/// * `reader` - The QASM input.
+/// * `options` - The settings of the parser.
+/// * `callback` - Called with each translated operation in the order of the input.
+///
+/// # Returns
+///
+/// * `Ok(())` - The input has been translated completely.
+/// * `QasmParseError` - Error encountered while reading or parsing, with its position in the input.
+///   The operations in front of the error have been passed to the callback.
+pub fn reader_for_each_operation<R: BufRead>(
+    reader: R,
+    options: &ParserOptions,
+    mut callback: impl FnMut(Operation),
+) -> Result<(), QasmParseError> {
+    for operation in QasmStreamParser::new(reader, options) {
+        callback(operation?);
+    }
+    Ok(())
+}
+
+/// Reads an opened QASM file.
+fn read_qasm_file(file: File) -> Result<String, QasmParseError> {
+    BufReader::new(file)
+        .lines()
+        .map(|line| line.map(|line| line + "\n"))
+        .collect::<Result<String, _>>()
+        .map_err(|err| {
+            QasmParseError::new(
+                QasmParseErrorKind::Io,
+                format!("Could not read the file: {err}"),
+                0,
+                0,
+                String::new(),
+            )
+        })
+}
+
+/// Reads the QASM file at a path.
+fn read_qasm_path(path: &Path) -> Result<String, QasmParseError> {
+    let unparsed_file = std::fs::read_to_string(path).map_err(|err| {
+        QasmParseError::new(
+            QasmParseErrorKind::Io,
+            format!("Could not read the file {}: {err}", path.display()),
+            0,
+            0,
+            String::new(),
+        )
+    })?;
+    Ok(unparsed_file + "\n")
 }
 
 // helper function
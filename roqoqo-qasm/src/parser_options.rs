@@ -0,0 +1,196 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Settings of the roqoqo-qasm parser.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How calls of gates that are neither standard gates nor declared in the file are translated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnknownGatePolicy {
+    /// Unknown gates are reported as a `QasmParseErrorKind::Undeclared` error.
+    Strict,
+    /// Unknown gates are dropped and a warning is collected for each call.
+    Lenient,
+    /// Unknown gates are declared as opaque gates, calls become `CallDefinedGate` operations.
+    #[default]
+    Opaque,
+}
+
+/// The default maximal size of a quantum or classical register, 2^20.
+pub const DEFAULT_MAX_REGISTER_SIZE: usize = 1 << 20;
+
+/// Settings controlling how QASM input is translated into a roqoqo Circuit.
+///
+/// The default settings are used by `file_to_circuit` and `string_to_circuit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserOptions {
+    /// Directories searched for included files.
+    include_paths: Vec<PathBuf>,
+    /// Values of the parameters declared with `input`.
+    input_values: HashMap<String, f64>,
+    /// How calls of unknown gates are translated.
+    unknown_gate_policy: UnknownGatePolicy,
+    /// The length of the sample time `dt` in seconds.
+    dt: Option<f64>,
+    /// Whether calls of user-defined gates are replaced by the bodies of the gates.
+    inline_gates: bool,
+    /// The maximal number of qubits or bits of a declared register.
+    max_register_size: usize,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            include_paths: vec![],
+            input_values: HashMap::new(),
+            unknown_gate_policy: UnknownGatePolicy::default(),
+            dt: None,
+            inline_gates: false,
+            max_register_size: DEFAULT_MAX_REGISTER_SIZE,
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Creates the default parser settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory to the search paths for included files.
+    ///
+    /// `qelib1.inc` and `stdgates.inc` are always taken from the copies bundled with roqoqo-qasm.
+    /// Other files are looked up in the directory of the including file first and then in the
+    /// search paths, in the order they were added. Relative search paths are relative to the
+    /// directory of the parsed file, or to the working directory when parsing a string.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory to search.
+    pub fn with_include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    /// Returns the directories searched for included files.
+    pub fn include_paths(&self) -> &[PathBuf] {
+        &self.include_paths
+    }
+
+    /// Sets the value of a parameter declared with `input`.
+    ///
+    /// The value is used as the default of the InputSymbolic the declaration is translated to.
+    /// Parameters without a value default to 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the input parameter.
+    /// * `value` - The value of the input parameter.
+    pub fn with_input_value(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.input_values.insert(name.into(), value);
+        self
+    }
+
+    /// Returns the values of the parameters declared with `input`.
+    pub fn input_values(&self) -> &HashMap<String, f64> {
+        &self.input_values
+    }
+
+    /// Sets how calls of gates that are neither standard gates nor declared in the file are
+    /// translated, including calls inside gate definitions.
+    ///
+    /// The default policy is `UnknownGatePolicy::Opaque`, so that no call is lost. Unknown gates
+    /// are only dropped when `UnknownGatePolicy::Lenient` is chosen explicitly.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy for unknown gates.
+    pub fn with_unknown_gate_policy(mut self, policy: UnknownGatePolicy) -> Self {
+        self.unknown_gate_policy = policy;
+        self
+    }
+
+    /// Returns how calls of unknown gates are translated.
+    pub fn unknown_gate_policy(&self) -> UnknownGatePolicy {
+        self.unknown_gate_policy
+    }
+
+    /// Sets the length of the sample time `dt` of the device in seconds.
+    ///
+    /// Durations of `delay` and `box` statements are converted to seconds, durations in units of
+    /// `dt` can only be read once its length is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` - The length of `dt` in seconds.
+    pub fn with_dt(mut self, dt: f64) -> Self {
+        self.dt = Some(dt);
+        self
+    }
+
+    /// Returns the length of the sample time `dt` in seconds, if set.
+    pub fn dt(&self) -> Option<f64> {
+        self.dt
+    }
+
+    /// Sets whether calls of user-defined gates are replaced by the bodies of the gates.
+    ///
+    /// Inlined calls are expanded recursively with the qubits and parameters of the call, so that
+    /// the parsed circuit contains no `CallDefinedGate` and no `GateDefinition` for gates with a
+    /// body. Calls of opaque gates are kept, as their bodies are unknown. Gate modifiers are
+    /// applied to the operations of the inlined body. By default calls are not inlined.
+    ///
+    /// # Arguments
+    ///
+    /// * `inline_gates` - Whether calls of user-defined gates are inlined.
+    pub fn with_inline_gates(mut self, inline_gates: bool) -> Self {
+        self.inline_gates = inline_gates;
+        self
+    }
+
+    /// Returns whether calls of user-defined gates are replaced by the bodies of the gates.
+    pub fn inline_gates(&self) -> bool {
+        self.inline_gates
+    }
+
+    /// Sets the maximal number of qubits or bits of a register.
+    ///
+    /// Operations on whole registers are expanded into one operation per qubit, so that very large
+    /// declarations would exhaust the memory. Declarations of larger registers are reported as a
+    /// `QasmParseErrorKind::Unsupported` error. The default is `DEFAULT_MAX_REGISTER_SIZE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_register_size` - The maximal size of a register.
+    pub fn with_max_register_size(mut self, max_register_size: usize) -> Self {
+        self.max_register_size = max_register_size;
+        self
+    }
+
+    /// Returns the maximal number of qubits or bits of a register.
+    pub fn max_register_size(&self) -> usize {
+        self.max_register_size
+    }
+
+    /// Returns the search paths with relative paths resolved against a base directory.
+    pub(crate) fn resolved_include_paths(&self, base_directory: Option<&Path>) -> Vec<PathBuf> {
+        self.include_paths
+            .iter()
+            .map(|path| match base_directory {
+                Some(base) if path.is_relative() => base.join(path),
+                _ => path.clone(),
+            })
+            .collect()
+    }
+}
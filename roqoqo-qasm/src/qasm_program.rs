@@ -0,0 +1,148 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Result of parsing a QASM file, holding the information of the file next to the Circuit.
+
+use crate::parser::contains_measurement;
+use crate::{QasmParseError, QasmParseErrorKind, QasmParseWarning, QasmVersion, QubitRegisters};
+use roqoqo::measurements::ClassicalRegister;
+use roqoqo::operations::{Define, Operation};
+use roqoqo::{Circuit, QuantumProgram};
+
+/// A parsed QASM file.
+///
+/// Next to the translated Circuit, the program keeps the information of the file that has no
+/// place in a roqoqo Circuit, so that tools re-exporting or reporting on the file can use it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QasmProgram {
+    /// The translated Circuit.
+    pub(crate) circuit: Circuit,
+    /// The OpenQASM version and dialect detected in the file.
+    pub(crate) version: QasmVersion,
+    /// The quantum registers declared in the file.
+    pub(crate) qubit_registers: QubitRegisters,
+    /// The name and length of the classical bit registers in the order of declaration.
+    pub(crate) classical_registers: Vec<(String, usize)>,
+    /// The names of the included files as written in the include statements.
+    pub(crate) includes: Vec<String>,
+    /// The name, number of qubits and number of parameters of the declared gates.
+    pub(crate) gate_signatures: Vec<(String, usize, usize)>,
+    /// The comments between the `OPENQASM` header and the first statement.
+    pub(crate) header_comments: Vec<String>,
+    /// Warnings collected while parsing.
+    pub(crate) warnings: Vec<QasmParseWarning>,
+}
+
+impl QasmProgram {
+    /// Returns the translated Circuit.
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    /// Returns the translated Circuit, consuming the program.
+    pub fn into_circuit(self) -> Circuit {
+        self.circuit
+    }
+
+    /// Wraps the translated Circuit in a roqoqo QuantumProgram reading out the classical
+    /// registers, consuming the program.
+    ///
+    /// The measurement is a ClassicalRegister measurement of the Circuit, returning the registers
+    /// declared as `output`, or all registers if the file declares no outputs. The parameters
+    /// declared with `input` are the input parameters of the program, in the order of their
+    /// declaration. Their InputSymbolic definitions are removed, they would otherwise override
+    /// the values the program is run with, and the values set with
+    /// `ParserOptions::with_input_value` are not used.
+    ///
+    /// # Returns
+    ///
+    /// * `QuantumProgram::ClassicalRegister` - The program running the translated Circuit.
+    /// * `QasmParseError` - The file contains no measurement, reported with the kind
+    ///   `QasmParseErrorKind::MissingMeasurement` and without a position.
+    pub fn into_quantum_program(self) -> Result<QuantumProgram, QasmParseError> {
+        if !contains_measurement(&self.circuit) {
+            return Err(QasmParseError::new(
+                QasmParseErrorKind::MissingMeasurement,
+                "A QuantumProgram needs a measurement, but the QASM input contains none"
+                    .to_string(),
+                0,
+                0,
+                String::new(),
+            ));
+        }
+        let mut input_parameter_names: Vec<String> = vec![];
+        let mut measured_circuit = Circuit::new();
+        for operation in self.circuit {
+            match operation {
+                Operation::InputSymbolic(input) => {
+                    input_parameter_names.push(input.name().to_owned())
+                }
+                operation => measured_circuit.add_operation(operation),
+            }
+        }
+        Ok(QuantumProgram::ClassicalRegister {
+            measurement: ClassicalRegister {
+                constant_circuit: None,
+                circuits: vec![measured_circuit],
+            },
+            input_parameter_names,
+        })
+    }
+
+    /// Returns the OpenQASM version and dialect detected in the file.
+    ///
+    /// The version is read from the `OPENQASM` header. OpenQASM 3.0 files are of the Roqoqo
+    /// dialect when they contain `pragma roqoqo` statements, of the Braket dialect when they
+    /// contain `pragma braket` statements or call gates by their Braket names such as `cnot`, and
+    /// Vanilla otherwise. OpenQASM 2.0 files are of the Qulacs dialect when they include
+    /// `qelib1.inc` without defining gates of their own, and Vanilla otherwise.
+    pub fn version(&self) -> QasmVersion {
+        self.version
+    }
+
+    /// Returns the table of the quantum registers declared in the file.
+    pub fn qubit_registers(&self) -> &QubitRegisters {
+        &self.qubit_registers
+    }
+
+    /// Returns the name and length of the classical bit registers in the order of declaration.
+    pub fn classical_registers(&self) -> &[(String, usize)] {
+        &self.classical_registers
+    }
+
+    /// Returns the names of the included files as written in the include statements.
+    ///
+    /// Each file is listed once, files included by included files follow the file including them.
+    pub fn includes(&self) -> &[String] {
+        &self.includes
+    }
+
+    /// Returns the name, number of qubits and number of parameters of the gates declared with
+    /// `gate` or `opaque` in the file and its included files.
+    ///
+    /// Gates translated to roqoqo operations, such as the gates of the standard libraries, are
+    /// not listed.
+    pub fn gate_signatures(&self) -> &[(String, usize, usize)] {
+        &self.gate_signatures
+    }
+
+    /// Returns the comments between the `OPENQASM` header and the first statement, without the
+    /// leading `//`.
+    pub fn header_comments(&self) -> &[String] {
+        &self.header_comments
+    }
+
+    /// Returns the warnings collected while parsing.
+    pub fn warnings(&self) -> &[QasmParseWarning] {
+        &self.warnings
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Register table mapping the quantum registers of a QASM file to roqoqo qubit indices.
+
+/// Table of the quantum registers declared in a QASM file.
+///
+/// roqoqo addresses qubits with a single flat index. The registers are laid out one after the
+/// other in the order of their declaration, so that `qreg a[2]; qreg b[2];` maps `a[0]`, `a[1]`,
+/// `b[0]` and `b[1]` to the qubits 0, 1, 2 and 3. The total number of qubits always fits into a
+/// `usize`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QubitRegisters {
+    /// The name, offset and size of each register in the order of declaration.
+    registers: Vec<(String, usize, usize)>,
+}
+
+impl QubitRegisters {
+    /// Creates a new, empty register table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a register at the end of the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the quantum register.
+    /// * `size` - The number of qubits in the register.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(usize)` - The flat index of the first qubit of the register.
+    /// * `None` - A register with the same name has already been declared or the total number of
+    ///   qubits would overflow.
+    pub fn add_register(&mut self, name: &str, size: usize) -> Option<usize> {
+        if self.contains(name) {
+            return None;
+        }
+        let offset = self.number_qubits();
+        offset.checked_add(size)?;
+        self.registers.push((name.to_owned(), offset, size));
+        Some(offset)
+    }
+
+    /// Returns true if a register with the given name has been declared.
+    pub fn contains(&self, name: &str) -> bool {
+        self.registers
+            .iter()
+            .any(|(register, _, _)| register == name)
+    }
+
+    /// Returns the flat roqoqo qubit index of a qubit in a register.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the quantum register.
+    /// * `index` - The index of the qubit in the register.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(usize)` - The roqoqo qubit index.
+    /// * `None` - The register has not been declared or the index is out of its range.
+    pub fn qubit_index(&self, name: &str, index: usize) -> Option<usize> {
+        self.registers
+            .iter()
+            .find(|(register, _, _)| register == name)
+            .filter(|(_, _, size)| index < *size)
+            .and_then(|(_, offset, _)| offset.checked_add(index))
+    }
+
+    /// Returns the flat index of the first qubit of a register.
+    pub fn register_offset(&self, name: &str) -> Option<usize> {
+        self.registers
+            .iter()
+            .find(|(register, _, _)| register == name)
+            .map(|(_, offset, _)| *offset)
+    }
+
+    /// Returns the number of qubits in a register.
+    pub fn register_size(&self, name: &str) -> Option<usize> {
+        self.registers
+            .iter()
+            .find(|(register, _, _)| register == name)
+            .map(|(_, _, size)| *size)
+    }
+
+    /// Returns the total number of qubits of all registers.
+    pub fn number_qubits(&self) -> usize {
+        self.registers
+            .last()
+            .map_or(0, |(_, offset, size)| offset + size)
+    }
+
+    /// Returns an iterator over the name, offset and size of the registers in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize, usize)> {
+        self.registers
+            .iter()
+            .map(|(name, offset, size)| (name.as_str(), *offset, *size))
+    }
+
+    /// Returns true if no register has been declared.
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
+}
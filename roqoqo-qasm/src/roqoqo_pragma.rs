@@ -0,0 +1,353 @@
+// Copyright © 2021-2023 HQS Quantum Simulations GmbH. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the
+// License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Reading of the `pragma roqoqo` statements written by the 3.0Roqoqo dialect.
+
+use crate::{QasmParseError, QasmParseErrorKind};
+use ndarray::{Array1, Array2};
+use num_complex::Complex64;
+use qoqo_calculator::CalculatorFloat;
+use roqoqo::operations::*;
+use roqoqo::Circuit;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Splits the arguments of a pragma into tokens.
+///
+/// Tokens are separated by whitespace outside of brackets, so that vectors, maps and arrays
+/// written with `{:?}` or `{}` are kept in one token.
+#[derive(Debug)]
+struct PragmaArguments<'a> {
+    /// The part of the pragma that has not been read yet.
+    rest: &'a str,
+    /// The name of the pragma, used in error messages.
+    name: &'a str,
+}
+
+impl<'a> PragmaArguments<'a> {
+    /// Returns the next token.
+    fn next_token(&mut self) -> Result<&'a str, QasmParseError> {
+        let rest = self.rest.trim_start();
+        let mut depth = 0_usize;
+        let mut end = rest.len();
+        for (index, character) in rest.char_indices() {
+            match character {
+                '[' | '{' | '(' => depth += 1,
+                ']' | '}' | ')' => depth = depth.saturating_sub(1),
+                _ if character.is_whitespace() && depth == 0 => {
+                    end = index;
+                    break;
+                }
+                _ => (),
+            }
+        }
+        let (token, rest) = rest.split_at(end);
+        self.rest = rest;
+        if token.is_empty() {
+            return Err(pragma_error(
+                QasmParseErrorKind::Syntax,
+                format!("Missing argument of {}", self.name),
+            ));
+        }
+        Ok(token)
+    }
+
+    /// Returns everything that has not been read yet.
+    fn remainder(&mut self) -> &'a str {
+        let rest = self.rest.trim();
+        self.rest = "";
+        rest
+    }
+
+    /// Reads an unsigned integer.
+    fn usize(&mut self) -> Result<usize, QasmParseError> {
+        let token = self.next_token()?;
+        parse_number(token)
+    }
+
+    /// Reads a float.
+    fn f64(&mut self) -> Result<f64, QasmParseError> {
+        let token = self.next_token()?;
+        parse_number(token)
+    }
+
+    /// Reads a CalculatorFloat, symbolic values are kept as strings.
+    fn calculator_float(&mut self) -> Result<CalculatorFloat, QasmParseError> {
+        Ok(CalculatorFloat::from(self.next_token()?))
+    }
+
+    /// Reads a vector of qubits written as `[0, 1]`.
+    fn qubits(&mut self) -> Result<Vec<usize>, QasmParseError> {
+        let token = self.next_token()?;
+        let inner = strip_delimiters(token, '[', ']')?;
+        inner
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(parse_number)
+            .collect()
+    }
+
+    /// Reads a map between qubits written as `{0: 1, 1: 0}`.
+    fn qubit_map(&mut self) -> Result<HashMap<usize, usize>, QasmParseError> {
+        let token = self.next_token()?;
+        let inner = strip_delimiters(token, '{', '}')?;
+        inner
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (key, value) = entry.split_once(':').ok_or_else(|| {
+                    pragma_error(
+                        QasmParseErrorKind::InvalidExpression,
+                        format!("{entry} is not a qubit mapping entry"),
+                    )
+                })?;
+                Ok((parse_number(key.trim())?, parse_number(value.trim())?))
+            })
+            .collect()
+    }
+
+    /// Returns the circuit read from the block following the pragma.
+    ///
+    /// Circuits written into the arguments of the pragma by earlier versions of the backend
+    /// cannot be read back, an empty argument is read as no circuit.
+    fn circuit(&mut self, block: Option<Circuit>) -> Result<Option<Circuit>, QasmParseError> {
+        if block.is_some() || self.remainder().is_empty() {
+            Ok(block)
+        } else {
+            Err(pragma_error(
+                QasmParseErrorKind::Unsupported,
+                format!(
+                    "The circuit of {} must be written as a block following the pragma",
+                    self.name
+                ),
+            ))
+        }
+    }
+
+    /// Checks that all arguments have been read.
+    fn finish(&mut self) -> Result<(), QasmParseError> {
+        let rest = self.remainder();
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            Err(pragma_error(
+                QasmParseErrorKind::Syntax,
+                format!("Unexpected argument {rest} of {}", self.name),
+            ))
+        }
+    }
+}
+
+/// Creates the error for a pragma, the position is set by the caller.
+fn pragma_error(kind: QasmParseErrorKind, message: String) -> QasmParseError {
+    QasmParseError::new(kind, message, 0, 0, String::new())
+}
+
+/// Parses a number written with `{}` or `{:?}`.
+fn parse_number<T: FromStr>(token: &str) -> Result<T, QasmParseError> {
+    token.parse::<T>().map_err(|_| {
+        pragma_error(
+            QasmParseErrorKind::InvalidExpression,
+            format!("{token} is not a valid number"),
+        )
+    })
+}
+
+/// Removes the opening and closing delimiter around a token.
+fn strip_delimiters(token: &str, open: char, close: char) -> Result<&str, QasmParseError> {
+    token
+        .strip_prefix(open)
+        .and_then(|inner| inner.strip_suffix(close))
+        .ok_or_else(|| {
+            pragma_error(
+                QasmParseErrorKind::InvalidExpression,
+                format!("{token} is not enclosed in {open}{close}"),
+            )
+        })
+}
+
+/// Parses the entries of an array written with the `Display` of ndarray.
+///
+/// Returns the entries in row-major order and the number of rows.
+fn parse_array_entries<T: FromStr>(token: &str) -> Result<(Vec<T>, usize), QasmParseError> {
+    // Large arrays are abbreviated by ndarray and cannot be read back
+    if token.contains("...") {
+        return Err(pragma_error(
+            QasmParseErrorKind::Unsupported,
+            "Abbreviated arrays cannot be read back".to_string(),
+        ));
+    }
+    let entries = token
+        .split(|character: char| {
+            character == '[' || character == ']' || character == ',' || character.is_whitespace()
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(parse_number)
+        .collect::<Result<Vec<T>, QasmParseError>>()?;
+    let rows = token.matches('[').count().saturating_sub(1).max(1);
+    Ok((entries, rows))
+}
+
+/// Parses a one-dimensional array written as `[1+0i, 0+0i]`.
+fn parse_array1<T: FromStr>(token: &str) -> Result<Array1<T>, QasmParseError> {
+    let (entries, _) = parse_array_entries(token)?;
+    Ok(Array1::from(entries))
+}
+
+/// Parses a two-dimensional array written as `[[1, 0],\n [0, 1]]`.
+fn parse_array2<T: FromStr>(token: &str) -> Result<Array2<T>, QasmParseError> {
+    let (entries, rows) = parse_array_entries(token)?;
+    let columns = entries.len() / rows;
+    Array2::from_shape_vec((rows, columns), entries).map_err(|_| {
+        pragma_error(
+            QasmParseErrorKind::InvalidExpression,
+            format!("{token} is not a rectangular array"),
+        )
+    })
+}
+
+/// Translates a `pragma roqoqo` statement back into the operation it was written for.
+///
+/// Pragmas of other tools are ignored. The circuit of operations such as `PragmaLoop` is written
+/// as a block of QASM statements following the pragma and is passed in already parsed.
+///
+/// # Arguments
+///
+/// * `pragma` - The text of the pragma statement, without the block of its circuit.
+/// * `circuit` - The circuit parsed from the block following the pragma, if there is one.
+///
+/// # Returns
+///
+/// * `Ok(Some(Operation))` - The operation written as the pragma.
+/// * `Ok(None)` - The pragma is not a roqoqo pragma.
+/// * `Err(QasmParseError)` - The pragma could not be read, the position is not set.
+pub(crate) fn parse_roqoqo_pragma(
+    pragma: &str,
+    mut circuit: Option<Circuit>,
+) -> Result<Option<Operation>, QasmParseError> {
+    let content = pragma
+        .trim()
+        .trim_start_matches("pragma")
+        .trim_end_matches(';');
+    let Some(content) = content.trim_start().strip_prefix("roqoqo") else {
+        return Ok(None);
+    };
+    let mut arguments = PragmaArguments {
+        rest: content,
+        name: "pragma roqoqo",
+    };
+    let name = arguments.next_token()?;
+    arguments.name = name;
+    let operation = match name {
+        "PragmaBoostNoise" => Operation::from(PragmaBoostNoise::new(arguments.calculator_float()?)),
+        "PragmaDamping" => Operation::from(PragmaDamping::new(
+            arguments.usize()?,
+            arguments.calculator_float()?,
+            arguments.calculator_float()?,
+        )),
+        "PragmaDephasing" => Operation::from(PragmaDephasing::new(
+            arguments.usize()?,
+            arguments.calculator_float()?,
+            arguments.calculator_float()?,
+        )),
+        "PragmaDepolarising" => Operation::from(PragmaDepolarising::new(
+            arguments.usize()?,
+            arguments.calculator_float()?,
+            arguments.calculator_float()?,
+        )),
+        "PragmaGeneralNoise" => Operation::from(PragmaGeneralNoise::new(
+            arguments.usize()?,
+            arguments.calculator_float()?,
+            parse_array2(arguments.next_token()?)?,
+        )),
+        "PragmaGetDensityMatrix" => Operation::from(PragmaGetDensityMatrix::new(
+            arguments.next_token()?.to_string(),
+            arguments.circuit(circuit.take())?,
+        )),
+        "PragmaGetOccupationProbability" => Operation::from(PragmaGetOccupationProbability::new(
+            arguments.next_token()?.to_string(),
+            arguments.circuit(circuit.take())?,
+        )),
+        "PragmaGetStateVector" => Operation::from(PragmaGetStateVector::new(
+            arguments.next_token()?.to_string(),
+            arguments.circuit(circuit.take())?,
+        )),
+        // The backend writes no block for an empty circuit
+        "PragmaGetPauliProduct" => Operation::from(PragmaGetPauliProduct::new(
+            arguments.qubit_map()?,
+            arguments.next_token()?.to_string(),
+            arguments.circuit(circuit.take())?.unwrap_or_default(),
+        )),
+        "PragmaLoop" => Operation::from(PragmaLoop::new(
+            arguments.calculator_float()?,
+            arguments.circuit(circuit.take())?.unwrap_or_default(),
+        )),
+        "PragmaOverrotation" => Operation::from(PragmaOverrotation::new(
+            arguments.next_token()?.to_string(),
+            arguments.qubits()?,
+            arguments.f64()?,
+            arguments.f64()?,
+        )),
+        "PragmaRandomNoise" => Operation::from(PragmaRandomNoise::new(
+            arguments.usize()?,
+            arguments.calculator_float()?,
+            arguments.calculator_float()?,
+            arguments.calculator_float()?,
+        )),
+        "PragmaRepeatGate" => Operation::from(PragmaRepeatGate::new(arguments.usize()?)),
+        "PragmaSetDensityMatrix" => {
+            Operation::from(PragmaSetDensityMatrix::new(parse_array2::<Complex64>(
+                arguments.next_token()?,
+            )?))
+        }
+        "PragmaSetNumberOfMeasurements" => Operation::from(PragmaSetNumberOfMeasurements::new(
+            arguments.usize()?,
+            arguments.next_token()?.to_string(),
+        )),
+        "PragmaSetStateVector" => {
+            Operation::from(PragmaSetStateVector::new(parse_array1::<Complex64>(
+                arguments.next_token()?,
+            )?))
+        }
+        "PragmaSleep" => Operation::from(PragmaSleep::new(
+            arguments.qubits()?,
+            arguments.calculator_float()?,
+        )),
+        "PragmaStartDecompositionBlock" => Operation::from(PragmaStartDecompositionBlock::new(
+            arguments.qubits()?,
+            arguments.qubit_map()?,
+        )),
+        "PragmaStopDecompositionBlock" => {
+            Operation::from(PragmaStopDecompositionBlock::new(arguments.qubits()?))
+        }
+        "PragmaStopParallelBlock" => Operation::from(PragmaStopParallelBlock::new(
+            arguments.qubits()?,
+            arguments.calculator_float()?,
+        )),
+        _ => {
+            return Err(pragma_error(
+                QasmParseErrorKind::Unsupported,
+                format!("{name} cannot be read from a roqoqo pragma"),
+            ))
+        }
+    };
+    arguments.finish()?;
+    if circuit.is_some() {
+        return Err(pragma_error(
+            QasmParseErrorKind::Syntax,
+            format!("{name} does not contain a circuit"),
+        ));
+    }
+    Ok(Some(operation))
+}
@@ -1,5 +1,5 @@
 use qoqo_calculator::CalculatorError;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 static ATOL: f64 = f64::EPSILON;
 
@@ -178,6 +178,11 @@ fn function_2_arguments(input: &str, arg0: f64, arg1: f64) -> Result<f64, Calcul
 pub struct VariableGatherer {
     ///  HashSet of variables in current Circuit
     pub variables: HashSet<String>,
+    ///  HashSet of variables used as loop bounds, which need to be integers
+    pub integer_variables: HashSet<String>,
+    ///  HashMap of GateDefinitions written as OpenQASM 3.0 subroutines, called with `name(...)`,
+    ///  to the classical bit the returned bit is assigned to, if they return one
+    pub subroutines: HashMap<String, Option<String>>,
 }
 
 impl Default for VariableGatherer {
@@ -191,6 +196,8 @@ impl VariableGatherer {
     pub fn new() -> Self {
         VariableGatherer {
             variables: HashSet::new(),
+            integer_variables: HashSet::new(),
+            subroutines: HashMap::new(),
         }
     }
 
@@ -220,6 +227,19 @@ impl VariableGatherer {
             Some(_) => Ok(()),
         }
     }
+
+    ///  Parse a string expression whose variables need to be integers, such as a loop bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `expression` - Expression that is parsed
+    ///
+    pub fn parse_integer(&mut self, expression: &str) -> Result<(), CalculatorError> {
+        let mut integer_gatherer = VariableGatherer::new();
+        integer_gatherer.parse(expression)?;
+        self.integer_variables.extend(integer_gatherer.variables);
+        Ok(())
+    }
 }
 
 /// Enum combining different types of Tokens in an Expression.
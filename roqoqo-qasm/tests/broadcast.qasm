@@ -0,0 +1,14 @@
+OPENQASM 2.0;
+include "qelib1.inc";
+qreg a[2];
+qreg b[2];
+creg c[2];
+creg d[2];
+
+h a;
+cx a,b;
+cx a[0],b;
+rz(0.5) b;
+reset b;
+measure a -> c;
+measure b -> d;
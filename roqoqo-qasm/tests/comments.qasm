@@ -1,6 +1,6 @@
 OPENQASM 2.0;
 creg c[2];
-qreg q[2];
+qreg q[3];
 
 rz(0.2) q[0];
 // ry(0.3) q[1];
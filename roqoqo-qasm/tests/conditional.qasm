@@ -0,0 +1,12 @@
+OPENQASM 2.0;
+qreg q[2];
+creg c[2];
+creg d[1];
+
+measure q[0] -> c[0];
+if(c[0]==1) x q[0];
+if(c[0]==1) h q[1];
+if(d==1) x q[1];
+if(c==3) y q[0];
+if(c==3) measure q[1] -> d[0];
+if(c==3) z q[1];
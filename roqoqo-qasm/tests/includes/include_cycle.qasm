@@ -0,0 +1,4 @@
+OPENQASM 2.0;
+include "cycle_a.inc";
+qreg q[1];
+gate_a q[0];
@@ -0,0 +1,6 @@
+OPENQASM 2.0;
+include "qelib1.inc";
+include "search_lib.inc";
+qreg q[3];
+entangle_all q[0], q[1], q[2];
+sdg q[0];
@@ -19,7 +19,7 @@ use std::path::Path;
 use qoqo_calculator::CalculatorFloat;
 use roqoqo::prelude::*;
 use roqoqo::{operations::*, Circuit};
-use roqoqo_qasm::Backend;
+use roqoqo_qasm::{Backend, EmitterOptions};
 
 use test_case::test_case;
 
@@ -154,6 +154,53 @@ fn test_str_order() {
     assert!(openqasm_pos < def_pos && def_pos < input_pos && input_pos < rz_pos)
 }
 
+/// Test the form of the measurements written by the backend
+#[test_case("2.0", None, "measure q[1] -> ro[0];\nmeasure q -> ro;\n"; "2.0")]
+#[test_case("3.0", None, "measure q[1] -> ro[0];\nmeasure q -> ro;\n"; "3.0")]
+#[test_case("3.0", Some(true), "ro[0] = measure q[1];\nro = measure q;\n"; "3.0 assignment")]
+#[test_case("3.0Braket", None, "ro[0] = measure q[1];\nro = measure q;\n"; "3.0Braket")]
+#[test_case("3.0Braket", Some(false), "measure q[1] -> ro[0];\nmeasure q -> ro;\n"; "3.0Braket arrow")]
+fn test_measurement_assignment(
+    qasm_version: &str,
+    measurement_assignment: Option<bool>,
+    measurements: &str,
+) {
+    let mut backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+    if let Some(measurement_assignment) = measurement_assignment {
+        backend = backend.with_emitter_options(
+            EmitterOptions::new().with_measurement_assignment(measurement_assignment),
+        );
+    }
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 2, true);
+    circuit += MeasureQubit::new(1, "ro".to_string(), 0);
+    circuit += PragmaRepeatedMeasurement::new("ro".to_string(), 1, None);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm.ends_with(measurements));
+}
+
+/// Test that gate modifiers only need the definitions of the modified gates
+#[test]
+fn test_gate_modifiers() {
+    let backend = Backend::new(None, Some("3.0".to_string()))
+        .unwrap()
+        .with_emitter_options(EmitterOptions::new().with_gate_modifiers(true));
+    let mut circuit = Circuit::new();
+    circuit += ControlledPauliZ::new(0, 1);
+    circuit += InvSGate::new(1);
+    circuit += SGate::new(0);
+    circuit +=
+        PragmaControlledCircuit::new(2, [Operation::from(Hadamard::new(0))].into_iter().collect());
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm.contains("gate z a { u1(pi) a; }\n"));
+    assert_eq!(qasm.matches("gate s a { u1(pi/2) a; }").count(), 1);
+    assert!(qasm.contains("gate h a { u2(0,pi) a; }\n"));
+    assert!(!qasm.contains("gate cz"));
+    assert!(qasm.ends_with("ctrl @ z q[0],q[1];\ninv @ s q[1];\ns q[0];\nctrl @ h q[2],q[0];\n"));
+}
+
 /// Test Debug, Clone and PartialEq for Backend
 #[test]
 fn test_debug_clone_partialeq() {
@@ -162,7 +209,7 @@ fn test_debug_clone_partialeq() {
     // Test Debug trait
     assert_eq!(
         format!("{backend:?}"),
-        "Backend { qubit_register_name: \"qtest\", qasm_version: V2point0(Vanilla) }"
+        "Backend { qubit_register_name: \"qtest\", qasm_version: V2point0(Vanilla), emitter_options: EmitterOptions { measurement_assignment: None, gate_modifiers: false, time_unit: Seconds, dt: None, minimal_header: false } }"
     );
 
     // Test Clone trait
@@ -176,8 +223,18 @@ fn test_debug_clone_partialeq() {
     assert!(backend == backend_0);
     assert!(backend_2 != backend);
     assert!(backend != backend_2);
+
+    // Eq, including the length of dt
+    let backend_dt = backend
+        .clone()
+        .with_emitter_options(EmitterOptions::new().with_dt(f64::NAN));
+    assert_eq!(backend_dt.clone(), backend_dt);
+    assert!(backend_dt != backend);
+    assert_eq_trait(&backend);
 }
 
+fn assert_eq_trait<T: Eq>(_: &T) {}
+
 /// Test correct parameters handling
 #[test]
 fn test_parametric_gates() {
@@ -267,6 +324,202 @@ fn test_gate_definition_circuit(qasm_version: &str, qubits: &str, bits: &str) {
     assert_eq!(lines, extracted.unwrap());
 }
 
+/// Test that calls of GateDefinitions without parameters are read back
+#[test_case("2.0"; "2.0")]
+#[test_case("3.0"; "3.0")]
+fn test_gate_definition_without_parameters(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+    let mut inner_gate = Circuit::new();
+    inner_gate += PauliX::new(0);
+    let mut outer_gate = Circuit::new();
+    outer_gate += CallDefinedGate::new("b".to_owned(), vec![1], vec![]);
+    outer_gate += CNOT::new(0, 1);
+    let mut circuit = Circuit::new();
+    circuit += GateDefinition::new(inner_gate, "b".to_owned(), vec![0], vec![]);
+    circuit += GateDefinition::new(outer_gate, "a".to_owned(), vec![0, 1], vec![]);
+    circuit += CallDefinedGate::new("a".to_owned(), vec![0, 1], vec![]);
+    circuit += CallDefinedGate::new("b".to_owned(), vec![1], vec![]);
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm_str.contains("\na() q[0],q[1];\nb() q[1];\n"));
+    assert_eq!(backend.string_to_circuit(&qasm_str).unwrap(), circuit);
+}
+
+/// Test that GateDefinitions with measurements and resets are written as subroutines in 3.0
+#[test]
+fn test_gate_definition_subroutine() {
+    let backend = Backend::new(None, Some("3.0".to_string())).unwrap();
+
+    let mut circuit_gate = Circuit::new();
+    circuit_gate += RotateX::new(0, CalculatorFloat::from("theta"));
+    circuit_gate += CNOT::new(0, 1);
+    circuit_gate += MeasureQubit::new(1, "ro".to_string(), 0);
+    circuit_gate += PragmaActiveReset::new(1);
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 1, true);
+    circuit += GateDefinition::new(
+        circuit_gate,
+        "measure_parity".to_owned(),
+        vec![0, 1],
+        vec!["theta".to_owned()],
+    );
+    circuit += CallDefinedGate::new(
+        "measure_parity".to_owned(),
+        vec![2, 0],
+        vec![CalculatorFloat::from(0.5)],
+    );
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm_str.contains("def measure_parity(float[64] theta, qubit qb_0, qubit qb_1) -> bit\n{\n    bit measured_bit;\n    rx(theta) qb_0;\n    cx qb_0,qb_1;\n    measure qb_1 -> measured_bit;\n    reset qb_1;\n    return measured_bit;\n}\n"));
+    assert!(qasm_str.contains("\nro[0] = measure_parity(5e-1,q[2],q[0]);\n"));
+    assert!(!qasm_str.contains("gate measure_parity"));
+
+    assert_eq!(backend.string_to_circuit(&qasm_str).unwrap(), circuit);
+
+    // OpenQASM 2.0 has no subroutines, the definition stays a gate
+    let backend = Backend::new(None, Some("2.0".to_string())).unwrap();
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm_str.contains("gate measure_parity(theta) qb_0,qb_1\n"));
+    assert!(qasm_str.contains("\nmeasure_parity(5e-1) q[2],q[0];\n"));
+}
+
+/// Test that subroutines return a local bit, which is assigned to the measured bit at the call
+#[test_case(false, "measure qb_0 -> measured_bit;"; "measure arrow")]
+#[test_case(true, "measured_bit = measure qb_0;"; "measure assignment")]
+fn test_subroutine_returned_bit(measurement_assignment: bool, measurement: &str) {
+    let backend = Backend::new(None, Some("3.0".to_string()))
+        .unwrap()
+        .with_emitter_options(
+            EmitterOptions::new()
+                .with_minimal_header(true)
+                .with_measurement_assignment(measurement_assignment),
+        );
+    let mut circuit_gate = Circuit::new();
+    circuit_gate += Hadamard::new(0);
+    circuit_gate += MeasureQubit::new(0, "ro".to_string(), 0);
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 1, true);
+    circuit += GateDefinition::new(circuit_gate, "hm".to_owned(), vec![0], vec![]);
+    circuit += CallDefinedGate::new("hm".to_owned(), vec![0], vec![]);
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert_eq!(
+        qasm_str,
+        format!("OPENQASM 3.0;\n\ninclude \"stdgates.inc\";\n\ndef hm(qubit qb_0) -> bit\n{{\n    bit measured_bit;\n    h qb_0;\n    {measurement}\n    return measured_bit;\n}}\n\nqubit[1] q;\n\noutput bit[1] ro;\nro[0] = hm(q[0]);\n")
+    );
+    assert_eq!(backend.string_to_circuit(&qasm_str).unwrap(), circuit);
+}
+
+/// Test that a subroutine cannot return more than one measured bit
+#[test]
+fn test_subroutine_two_returned_bits() {
+    let backend = Backend::new(None, Some("3.0".to_string())).unwrap();
+    let mut circuit_gate = Circuit::new();
+    circuit_gate += MeasureQubit::new(0, "ro".to_string(), 0);
+    circuit_gate += MeasureQubit::new(1, "ro".to_string(), 1);
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 2, true);
+    circuit += GateDefinition::new(circuit_gate, "mm".to_owned(), vec![0, 1], vec![]);
+    circuit += CallDefinedGate::new("mm".to_owned(), vec![0, 1], vec![]);
+
+    let error = backend.circuit_to_qasm_str(&circuit).unwrap_err();
+    assert!(matches!(error, RoqoqoBackendError::GenericError { .. }));
+}
+
+/// Test that GateDefinitions are written after the gates they call, also when nested
+#[test]
+fn test_gate_definition_dependency_order() {
+    let backend = Backend::new(None, Some("3.0".to_string())).unwrap();
+
+    let mut outer_gate = Circuit::new();
+    outer_gate += CallDefinedGate::new("inner".to_owned(), vec![1, 0], vec![]);
+    outer_gate += PauliZ::new(0);
+    let mut inner_gate = Circuit::new();
+    inner_gate += CNOT::new(0, 1);
+    inner_gate += Hadamard::new(1);
+    let mut loop_body = Circuit::new();
+    loop_body += GateDefinition::new(inner_gate, "inner".to_owned(), vec![0, 1], vec![]);
+    loop_body += CallDefinedGate::new("outer".to_owned(), vec![0, 1], vec![]);
+    let mut circuit = Circuit::new();
+    circuit += GateDefinition::new(outer_gate, "outer".to_owned(), vec![0, 1], vec![]);
+    circuit += PragmaLoop::new(2.into(), loop_body);
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    let inner = qasm_str.find("gate inner() qb_0,qb_1\n").unwrap();
+    let outer = qasm_str.find("gate outer() qb_0,qb_1\n").unwrap();
+    assert!(inner < outer);
+    assert!(qasm_str.find("gate h a").unwrap() < inner);
+    assert!(qasm_str.find("gate z a").unwrap() < outer);
+    assert_eq!(qasm_str.matches("gate inner").count(), 1);
+}
+
+/// Test that recursive GateDefinitions are reported as an error
+#[test_case(&[("a", "a")]; "calls itself")]
+#[test_case(&[("a", "b"), ("b", "c"), ("c", "a")]; "cycle")]
+fn test_gate_definition_recursive(calls: &[(&str, &str)]) {
+    let backend = Backend::new(None, Some("2.0".to_string())).unwrap();
+    let mut circuit = Circuit::new();
+    for (name, called) in calls {
+        let mut gate = Circuit::new();
+        gate += CallDefinedGate::new(called.to_string(), vec![0], vec![]);
+        circuit += GateDefinition::new(gate, name.to_string(), vec![0], vec![]);
+    }
+    circuit += CallDefinedGate::new("a".to_owned(), vec![0], vec![]);
+
+    let error = backend.circuit_to_qasm_str(&circuit).unwrap_err();
+    assert!(
+        matches!(error, RoqoqoBackendError::GenericError { msg } if msg.contains("is recursive"))
+    );
+}
+
+/// Test that the minimal header includes the standard library and defines only the missing gates
+#[test_case("2.0", "include \"qelib1.inc\";\n\nqreg q[1];\n\ncreg ro[1];\nh q[0];\nmeasure q[0] -> ro[0];\n"; "2.0")]
+#[test_case("3.0", "include \"stdgates.inc\";\n\nqubit[1] q;\n\noutput bit[1] ro;\nh q[0];\nmeasure q[0] -> ro[0];\n"; "3.0")]
+fn test_minimal_header(qasm_version: &str, body: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string()))
+        .unwrap()
+        .with_emitter_options(EmitterOptions::new().with_minimal_header(true));
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".to_string(), 1, true);
+    circuit += Hadamard::new(0);
+    circuit += MeasureQubit::new(0, "ro".to_string(), 0);
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert_eq!(qasm_str, format!("OPENQASM {qasm_version};\n\n{body}"));
+    assert_eq!(backend.string_to_circuit(&qasm_str).unwrap(), circuit);
+}
+
+/// Test that the minimal header defines the non-standard gates and the gates they depend on
+#[test_case("2.0"; "2.0")]
+#[test_case("3.0"; "3.0")]
+fn test_minimal_header_definitions(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string()))
+        .unwrap()
+        .with_emitter_options(EmitterOptions::new().with_minimal_header(true));
+    let mut gate = Circuit::new();
+    gate += EchoCrossResonance::new(0, 1);
+    gate += RotateX::new(1, "theta".into());
+    let mut circuit = Circuit::new();
+    circuit += GateDefinition::new(
+        gate,
+        "ecr_rx".to_owned(),
+        vec![0, 1],
+        vec!["theta".to_owned()],
+    );
+    circuit += CallDefinedGate::new("ecr_rx".to_owned(), vec![1, 0], vec![0.5.into()]);
+    circuit += SqrtPauliY::new(0);
+    circuit += CNOT::new(0, 1);
+
+    let qasm_str = backend.circuit_to_qasm_str(&circuit).unwrap();
+    let ecr = qasm_str.find("\ngate ecr a,b {").unwrap();
+    assert!(ecr < qasm_str.find("\ngate ecr_rx(theta) qb_0,qb_1\n").unwrap());
+    assert!(qasm_str.contains("\ngate sy a {"));
+    for standard_gate in ["u3", "u2", "u1", "rx", "ry", "rz", "cx", "x"] {
+        assert!(!qasm_str.contains(&format!("gate {standard_gate} ")));
+    }
+    assert_eq!(backend.string_to_circuit(&qasm_str).unwrap(), circuit);
+}
+
 /// Test that the Qulacs version works properly
 #[test]
 fn test_qulacs_version() {
@@ -18,8 +18,11 @@ use roqoqo::operations::*;
 use roqoqo::prelude::*;
 use roqoqo::Circuit;
 use roqoqo_qasm::Qasm2Dialect;
-use roqoqo_qasm::{call_circuit, call_operation, gate_definition, Qasm3Dialect, QasmVersion};
-use std::collections::HashMap;
+use roqoqo_qasm::{
+    call_circuit, call_circuit_with_options, call_operation, call_operation_with_options,
+    gate_definition, EmitterOptions, Qasm3Dialect, QasmVersion, TimeUnit, VariableGatherer,
+};
+use std::collections::{HashMap, HashSet};
 use std::f64::consts::PI;
 use test_case::test_case;
 
@@ -73,7 +76,8 @@ fn tmp_create_map() -> HashMap<usize, usize> {
 #[test_case(Operation::from(EchoCrossResonance::new(0, 1)), "ecr q[0],q[1];"; "EchoCrossResonance")]
 #[test_case(Operation::from(InputSymbolic::new("other".to_string(), 0.0)), ""; "InputSymbolic")]
 #[test_case(Operation::from(PragmaStopDecompositionBlock::new(vec![0,1])), ""; "PragmaStopDecompositionBlock")]
-#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), ""; "PragmaStopParallelBlock")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), "barrier q;"; "PragmaStopParallelBlock")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![0, 2], CalculatorFloat::from(0.0))), "barrier q[0],q[2];"; "PragmaStopParallelBlock on qubits")]
 #[test_case(Operation::from(PragmaSetNumberOfMeasurements::new(20, "ro".to_string())), ""; "PragmaSetNumberOfMeasurements")]
 #[test_case(Operation::from(PragmaStartDecompositionBlock::new(vec![0,1], HashMap::new())), ""; "PragmaStartDecompositionBlock")]
 #[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), ""; "PragmaGetDensityMatrix")]
@@ -180,7 +184,8 @@ fn test_call_operation_vanilla_2_error(operation: Operation) {
 #[test_case(Operation::from(EchoCrossResonance::new(0, 1)), "ecr q[0],q[1];"; "EchoCrossResonance")]
 #[test_case(Operation::from(InputSymbolic::new("other".to_string(), 0.0)), "input float other;"; "InputSymbolic")]
 #[test_case(Operation::from(PragmaStopDecompositionBlock::new(vec![0,1])), ""; "PragmaStopDecompositionBlock")]
-#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), ""; "PragmaStopParallelBlock")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), "barrier q;"; "PragmaStopParallelBlock")]
+#[test_case(Operation::from(PragmaStopParallelBlock::new(vec![0, 2], CalculatorFloat::from(0.0))), "barrier q[0],q[2];"; "PragmaStopParallelBlock on qubits")]
 #[test_case(Operation::from(PragmaSetNumberOfMeasurements::new(20, "ro".to_string())), ""; "PragmaSetNumberOfMeasurements")]
 #[test_case(Operation::from(PragmaStartDecompositionBlock::new(vec![0,1], HashMap::new())), ""; "PragmaStartDecompositionBlock")]
 #[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), ""; "PragmaGetDensityMatrix")]
@@ -290,10 +295,10 @@ fn test_call_operation_vanilla_3_error(operation: Operation) {
 #[test_case(Operation::from(PragmaStopParallelBlock::new(vec![], CalculatorFloat::from(0.0))), "pragma roqoqo PragmaStopParallelBlock [] 0e0;"; "PragmaStopParallelBlock")]
 #[test_case(Operation::from(PragmaSetNumberOfMeasurements::new(20, "ro".to_string())), "pragma roqoqo PragmaSetNumberOfMeasurements 20 ro;"; "PragmaSetNumberOfMeasurements")]
 #[test_case(Operation::from(PragmaStartDecompositionBlock::new(vec![0,1], HashMap::new())), "pragma roqoqo PragmaStartDecompositionBlock [0, 1] {};"; "PragmaStartDecompositionBlock")]
-#[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), "pragma roqoqo PragmaGetDensityMatrix test ;"; "PragmaGetDensityMatrix")]
-#[test_case(Operation::from(PragmaGetOccupationProbability::new("test".into(), None)), "pragma roqoqo PragmaGetOccupationProbability test ;"; "PragmaGetOccupationProbability")]
-#[test_case(Operation::from(PragmaGetPauliProduct::new(HashMap::new(), "test".into(), Circuit::new())), "pragma roqoqo PragmaGetPauliProduct {} test ;"; "PragmaGetPauliProduct")]
-#[test_case(Operation::from(PragmaGetStateVector::new("test".into(), None)), "pragma roqoqo PragmaGetStateVector test ;"; "PragmaGetStateVector")]
+#[test_case(Operation::from(PragmaGetDensityMatrix::new("test".into(), None)), "pragma roqoqo PragmaGetDensityMatrix test;"; "PragmaGetDensityMatrix")]
+#[test_case(Operation::from(PragmaGetOccupationProbability::new("test".into(), None)), "pragma roqoqo PragmaGetOccupationProbability test;"; "PragmaGetOccupationProbability")]
+#[test_case(Operation::from(PragmaGetPauliProduct::new(HashMap::new(), "test".into(), Circuit::new())), "pragma roqoqo PragmaGetPauliProduct {} test;"; "PragmaGetPauliProduct")]
+#[test_case(Operation::from(PragmaGetStateVector::new("test".into(), None)), "pragma roqoqo PragmaGetStateVector test;"; "PragmaGetStateVector")]
 #[test_case(Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)), "measure q -> ro;\npragma roqoqo PragmaSetNumberOfMeasurements 1 ro;"; "PragmaRepeatedMeasurement")]
 #[test_case(Operation::from(CNOT::new(0, 1)), "cx q[0],q[1];"; "CNOT")]
 #[test_case(Operation::from(ControlledPhaseShift::new(0, 1, CalculatorFloat::from(PI/4.0))), "cp(7.853981633974483e-1) q[0],q[1];"; "ControlledPhaseShift")]
@@ -383,7 +388,7 @@ fn test_call_operation_roqoqo_error(operation: Operation) {
 #[test_case(Operation::from(PhaseShiftedControlledPhase::new(0, 1, CalculatorFloat::from(0.1), CalculatorFloat::from(0.2))), "pscp(1e-1,2e-1) q[0],q[1];"; "PhaseShiftedControlledPhase")]
 #[test_case(Operation::from(SingleQubitGate::new(0, CalculatorFloat::from(1.0), CalculatorFloat::from(0.0), CalculatorFloat::from(0.0), CalculatorFloat::from(0.0), CalculatorFloat::from(0.0))), "u3(0.000000000000000,0.000000000000000,-0.000000000000000) q[0];"; "SingleQubitGate")]
 #[test_case(Operation::from(PragmaActiveReset::new(0)), "reset q[0];"; "PragmaActiveReset")]
-#[test_case(Operation::from(MeasureQubit::new(0, "ro".to_string(), 0)), "measure q[0] -> ro[0];"; "MeasureQubit")]
+#[test_case(Operation::from(MeasureQubit::new(0, "ro".to_string(), 0)), "ro[0] = measure q[0];"; "MeasureQubit")]
 #[test_case(Operation::from(ControlledControlledPauliZ::new(0, 1, 2)), "ccz q[0],q[1],q[2];"; "ControlledControlledPauliZ")]
 #[test_case(Operation::from(ControlledControlledPhaseShift::new(0, 1, 2, 0.3.into())), "ccp(3e-1) q[0],q[1],q[2];"; "ControlledControlledPhaseShift")]
 #[test_case(Operation::from(Toffoli::new(0, 1, 2)), "ccx q[0],q[1],q[2];"; "Toffoli")]
@@ -401,7 +406,7 @@ fn test_call_operation_roqoqo_error(operation: Operation) {
 #[test_case(Operation::from(PragmaGetOccupationProbability::new("test".into(), None)), ""; "PragmaGetOccupationProbability")]
 #[test_case(Operation::from(PragmaGetPauliProduct::new(HashMap::new(), "test".into(), Circuit::new())), ""; "PragmaGetPauliProduct")]
 #[test_case(Operation::from(PragmaGetStateVector::new("test".into(), None)), ""; "PragmaGetStateVector")]
-#[test_case(Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)), "measure q -> ro;"; "PragmaRepeatedMeasurement")]
+#[test_case(Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)), "ro = measure q;"; "PragmaRepeatedMeasurement")]
 #[test_case(Operation::from(CNOT::new(0, 1)), "cnot q[0],q[1];"; "CNOT")]
 #[test_case(Operation::from(ControlledPhaseShift::new(0, 1, CalculatorFloat::from(PI/4.0))), "cphaseshift(7.853981633974483e-1) q[0],q[1];"; "ControlledPhaseShift")]
 #[test_case(Operation::from(MolmerSorensenXX::new(0, 1)), "xx(pi/2) q[0],q[1];"; "MolmerSorensenXX")]
@@ -628,6 +633,40 @@ fn test_pragma_conditional() {
     );
 }
 
+/// Test that symbolic repetitions and nested PragmaLoops are written as OpenQASM 3.0 for loops
+#[test]
+fn test_pragma_loop_vanilla() {
+    let mut inner_circuit = Circuit::new();
+    inner_circuit += PauliX::new(0);
+    inner_circuit += RotateZ::new(1, "theta".into());
+    let mut circuit = Circuit::new();
+    circuit += Hadamard::new(0);
+    circuit += PragmaLoop::new(3.0.into(), inner_circuit);
+    let mut outer_circuit = Circuit::new();
+    outer_circuit += PragmaLoop::new(2.0.into(), circuit);
+    let nested = PragmaLoop::new("n".into(), outer_circuit);
+
+    let mut variable_gatherer = VariableGatherer::new();
+    assert_eq!(
+        call_operation(
+            &Operation::from(nested),
+            "q",
+            QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+            &mut Some(&mut variable_gatherer)
+        )
+        .unwrap(),
+        "for uint i2 in [0:n-1] {\n    for uint i1 in [0:1] {\n        h q[0];\n        for uint i in [0:2] {\n            x q[0];\n            rz(theta) q[1];\n        }\n    }\n}"
+    );
+    assert_eq!(
+        variable_gatherer.integer_variables,
+        HashSet::from(["n".to_string()])
+    );
+    assert_eq!(
+        variable_gatherer.variables,
+        HashSet::from(["theta".to_string()])
+    );
+}
+
 /// Test PragmaLoop correct behaviour
 #[test]
 fn test_pragma_loop() {
@@ -657,7 +696,7 @@ fn test_pragma_loop() {
         data_3
     );
 
-    let data_3_roqoqo = "pragma roqoqo PragmaLoop 2e0 Hadamard(Hadamard { qubit: 0 })\n;";
+    let data_3_roqoqo = "pragma roqoqo PragmaLoop 2e0 {\n    h q[0];\n}";
     assert_eq!(
         call_operation(
             &Operation::from(pcond),
@@ -670,10 +709,6 @@ fn test_pragma_loop() {
     );
 
     let pcond_error = PragmaLoop::new("test".into(), circuit.clone());
-    assert_eq!(
-        call_operation(&Operation::from(pcond_error.clone()), "q", QasmVersion::V3point0(Qasm3Dialect::Vanilla), &mut None),
-        Err(RoqoqoBackendError::GenericError { msg: "Used PragmaLoop with a string test for repetitions and a qasm-version that is incompatible: V3point0(Vanilla)".into() })
-    );
     assert_eq!(
         call_operation(&Operation::from(pcond_error.clone()), "q", QasmVersion::V3point0(Qasm3Dialect::Braket), &mut None),
         Err(RoqoqoBackendError::GenericError { msg: "Used PragmaLoop with a string test for repetitions and a qasm-version that is incompatible: V3point0(Braket)".into() })
@@ -684,7 +719,7 @@ fn test_pragma_loop() {
     );
 
     let pcond = PragmaLoop::new(2.0.into(), circuit);
-    let data_3 = "for uint i in [0:2] {\n    h q[0];\n}";
+    let data_3 = "for uint i in [0:1] {\n    h q[0];\n}";
     assert_eq!(
         call_operation(
             &Operation::from(pcond),
@@ -760,7 +795,7 @@ fn test_pragma_sleep() {
             &mut None
         )
         .unwrap(),
-        ""
+        "delay[1s] q[0],q[1];"
     );
 
     assert_eq!(
@@ -771,7 +806,68 @@ fn test_pragma_sleep() {
             &mut None
         )
         .unwrap(),
-        ""
+        "delay[1s] q[0],q[1];"
+    );
+}
+
+/// Test that PragmaSleep is written as a delay in the configured time unit
+#[test_case(TimeUnit::Seconds, None, "delay[0.0000025s] q[1];"; "seconds")]
+#[test_case(TimeUnit::Milliseconds, None, "delay[0.0025ms] q[1];"; "milliseconds")]
+#[test_case(TimeUnit::Microseconds, None, "delay[2.5us] q[1];"; "microseconds")]
+#[test_case(TimeUnit::Nanoseconds, None, "delay[2500ns] q[1];"; "nanoseconds")]
+#[test_case(TimeUnit::Dt, Some(0.5e-9), "delay[5000dt] q[1];"; "dt")]
+fn test_pragma_sleep_time_unit(time_unit: TimeUnit, dt: Option<f64>, converted: &str) {
+    let mut options = EmitterOptions::new().with_time_unit(time_unit);
+    if let Some(dt) = dt {
+        options = options.with_dt(dt);
+    }
+    let operation = Operation::from(PragmaSleep::new(vec![1], 2.5e-6.into()));
+    assert_eq!(
+        call_operation_with_options(
+            &operation,
+            "q",
+            QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+            &options,
+            &mut None
+        )
+        .unwrap(),
+        converted
+    );
+}
+
+/// Test the errors of PragmaSleep written as a delay
+#[test]
+fn test_pragma_sleep_time_unit_errors() {
+    let operation = Operation::from(PragmaSleep::new(vec![0], 1e-6.into()));
+    let options = EmitterOptions::new().with_time_unit(TimeUnit::Dt);
+    assert!(call_operation_with_options(
+        &operation,
+        "q",
+        QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+        &options,
+        &mut None
+    )
+    .is_err());
+
+    let operation = Operation::from(PragmaSleep::new(vec![0], "t".into()));
+    assert!(call_operation(
+        &operation,
+        "q",
+        QasmVersion::V3point0(Qasm3Dialect::Braket),
+        &mut None
+    )
+    .is_err());
+
+    let operation = Operation::from(PragmaSleep::new(vec![], 1e-6.into()));
+    assert_eq!(
+        call_operation(
+            &operation,
+            "q",
+            QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+            &mut None
+        )
+        .unwrap(),
+        "delay[0.000001s];"
     );
 }
 
@@ -799,8 +895,8 @@ fn test_pragma_repeated_operation_mapping() {
         &mut None,
     )
     .unwrap();
-    assert!(qasm_string.contains("measure q[0] -> ro[1];\n"));
-    assert!(qasm_string.contains("measure q[1] -> ro[0];\n"));
+    assert!(qasm_string.contains("ro[1] = measure q[0];\n"));
+    assert!(qasm_string.contains("ro[0] = measure q[1];\n"));
     let qasm_string = call_operation(
         &operation,
         "q",
@@ -821,6 +917,136 @@ fn test_pragma_repeated_operation_mapping() {
     assert!(qasm_string.contains("measure q[1] -> ro[0];\n"));
 }
 
+/// Test that the measurement form can be chosen with the emitter settings
+#[test_case(QasmVersion::V2point0(Qasm2Dialect::Vanilla), true, "measure q[0] -> ro[1];", "measure q -> ro;"; "2.0 ignores setting")]
+#[test_case(QasmVersion::V3point0(Qasm3Dialect::Vanilla), true, "ro[1] = measure q[0];", "ro = measure q;"; "3.0Vanilla assignment")]
+#[test_case(QasmVersion::V3point0(Qasm3Dialect::Roqoqo), true, "ro[1] = measure q[0];", "ro = measure q;\npragma roqoqo PragmaSetNumberOfMeasurements 1 ro;"; "3.0Roqoqo assignment")]
+#[test_case(QasmVersion::V3point0(Qasm3Dialect::Braket), false, "measure q[0] -> ro[1];", "measure q -> ro;"; "3.0Braket arrow")]
+fn test_call_operation_measurement_assignment(
+    qasm_version: QasmVersion,
+    measurement_assignment: bool,
+    measure_qubit: &str,
+    repeated_measurement: &str,
+) {
+    let options = EmitterOptions::new().with_measurement_assignment(measurement_assignment);
+    assert_eq!(
+        call_operation_with_options(
+            &Operation::from(MeasureQubit::new(0, "ro".to_string(), 1)),
+            "q",
+            qasm_version,
+            &options,
+            &mut None
+        )
+        .unwrap(),
+        measure_qubit
+    );
+    assert_eq!(
+        call_operation_with_options(
+            &Operation::from(PragmaRepeatedMeasurement::new("ro".to_string(), 1, None)),
+            "q",
+            qasm_version,
+            &options,
+            &mut None
+        )
+        .unwrap(),
+        repeated_measurement
+    );
+
+    let mut circuit = Circuit::new();
+    circuit += PragmaLoop::new(
+        CalculatorFloat::from(1.0),
+        vec![Operation::from(MeasureQubit::new(0, "ro".to_string(), 1))]
+            .into_iter()
+            .collect(),
+    );
+    if qasm_version == QasmVersion::V3point0(Qasm3Dialect::Vanilla) {
+        assert!(
+            call_circuit_with_options(&circuit, "q", qasm_version, &options).unwrap()[0]
+                .contains(measure_qubit)
+        );
+    }
+}
+
+/// Test that controlled and inverse gates are written with gate modifiers when enabled
+#[test_case(Operation::from(InvSGate::new(0)), "inv @ s q[0];"; "InvSGate")]
+#[test_case(Operation::from(InvSqrtPauliX::new(1)), "inv @ sx q[1];"; "InvSqrtPauliX")]
+#[test_case(Operation::from(InvSqrtISwap::new(0, 1)), "inv @ siswap q[0],q[1];"; "InvSqrtISwap")]
+#[test_case(Operation::from(ControlledPauliY::new(1, 0)), "ctrl @ y q[1],q[0];"; "ControlledPauliY")]
+#[test_case(Operation::from(ControlledPhaseShift::new(0, 1, 0.5.into())), "ctrl @ p(5e-1) q[0],q[1];"; "ControlledPhaseShift")]
+#[test_case(Operation::from(ControlledRotateXY::new(0, 1, "theta".into(), 0.5.into())), "ctrl @ rxy(theta,5e-1) q[0],q[1];"; "ControlledRotateXY")]
+#[test_case(Operation::from(ControlledSWAP::new(0, 1, 2)), "ctrl @ swap q[0],q[1],q[2];"; "ControlledSWAP")]
+#[test_case(Operation::from(Toffoli::new(0, 1, 2)), "ctrl @ ctrl @ x q[0],q[1],q[2];"; "Toffoli")]
+#[test_case(Operation::from(TripleControlledPhaseShift::new(0, 1, 2, 3, 0.5.into())), "ctrl @ ctrl @ ctrl @ p(5e-1) q[0],q[1],q[2],q[3];"; "TripleControlledPhaseShift")]
+#[test_case(Operation::from(PragmaControlledCircuit::new(2, [Operation::from(Hadamard::new(0)), Operation::from(ControlledPauliZ::new(0, 1))].into_iter().collect())), "ctrl @ h q[2],q[0];\nctrl @ ctrl @ z q[2],q[0],q[1];"; "PragmaControlledCircuit")]
+fn test_call_operation_gate_modifiers(operation: Operation, converted: &str) {
+    let options = EmitterOptions::new().with_gate_modifiers(true);
+    assert_eq!(
+        call_operation_with_options(
+            &operation,
+            "q",
+            QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+            &options,
+            &mut None
+        )
+        .unwrap(),
+        converted
+    );
+    // OpenQASM 2.0 has no gate modifiers
+    assert_ne!(
+        call_operation_with_options(
+            &operation,
+            "q",
+            QasmVersion::V2point0(Qasm2Dialect::Vanilla),
+            &options,
+            &mut None
+        )
+        .ok(),
+        Some(converted.to_string())
+    );
+}
+
+/// Test that PragmaControlledCircuit is written with gate modifiers in all OpenQASM 3.0 dialects
+#[test]
+fn test_call_operation_controlled_circuit() {
+    let operation = Operation::from(PragmaControlledCircuit::new(
+        0,
+        [Operation::from(RotateZ::new(1, 0.5.into()))]
+            .into_iter()
+            .collect(),
+    ));
+    for qasm_version in [
+        QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+        QasmVersion::V3point0(Qasm3Dialect::Roqoqo),
+        QasmVersion::V3point0(Qasm3Dialect::Braket),
+    ] {
+        assert_eq!(
+            call_operation(&operation, "q", qasm_version, &mut None).unwrap(),
+            "ctrl @ rz(5e-1) q[0],q[1];"
+        );
+    }
+    assert!(call_operation(
+        &operation,
+        "q",
+        QasmVersion::V2point0(Qasm2Dialect::Vanilla),
+        &mut None
+    )
+    .is_err());
+
+    let measurement = Operation::from(PragmaControlledCircuit::new(
+        0,
+        [Operation::from(MeasureQubit::new(1, "ro".into(), 0))]
+            .into_iter()
+            .collect(),
+    ));
+    assert!(call_operation(
+        &measurement,
+        "q",
+        QasmVersion::V3point0(Qasm3Dialect::Vanilla),
+        &mut None
+    )
+    .is_err());
+}
+
 /// Test that non-included gates return an error
 #[test_case(Operation::from(Bogoliubov::new(
     0,
@@ -902,9 +1128,14 @@ fn test_call_circuit() {
         "x qr[0];".to_string(),
         "measure qr[0] -> ro[0];".to_string(),
     ];
+    let qasm_circ_braket: Vec<String> = vec![
+        "bit[1] ro;".to_string(),
+        "x qr[0];".to_string(),
+        "ro[0] = measure qr[0];".to_string(),
+    ];
     assert_eq!(
         call_circuit(&circuit, "qr", QasmVersion::V3point0(Qasm3Dialect::Braket)).unwrap(),
-        qasm_circ_3_0
+        qasm_circ_braket
     );
     assert_eq!(
         call_circuit(&circuit, "qr", QasmVersion::V3point0(Qasm3Dialect::Vanilla)).unwrap(),
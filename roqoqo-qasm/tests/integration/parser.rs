@@ -12,15 +12,30 @@
 //
 //! Testing the roqoqo-qasm Parser
 
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
 use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
 
+use ndarray::array;
 use num_complex::Complex64;
-use qoqo_calculator::CalculatorFloat;
+use qoqo_calculator::{Calculator, CalculatorFloat};
+use roqoqo::measurements::ClassicalRegister;
 use roqoqo::operations::*;
-use roqoqo::Circuit;
+use roqoqo::prelude::*;
+use roqoqo::{Circuit, QuantumProgram};
 
-use roqoqo_qasm::file_to_circuit;
+use roqoqo_qasm::{
+    file_to_circuit, file_to_circuit_with_registers, file_to_qasm_program, file_to_quantum_program,
+    path_to_qasm_program_with_options, reader_for_each_operation, reader_to_circuit,
+    string_to_circuit, string_to_circuit_with_registers, string_to_qasm_program,
+    string_to_qasm_program_with_options, string_to_quantum_program, Backend, EmitterOptions,
+    ParserOptions, Qasm2Dialect, Qasm3Dialect, QasmParseErrorKind, QasmStreamParser, QasmVersion,
+    TimeUnit, UnknownGatePolicy,
+};
+use test_case::test_case;
 
 // helper function
 fn is_close(a: Complex64, b: Complex64) -> bool {
@@ -171,7 +186,7 @@ fn test_gate_definitions() {
     circuit_gate.add_operation(RotateX::new(1, CalculatorFloat::from("theta")));
     circuit_gate.add_operation(RotateX::new(
         0,
-        CalculatorFloat::from("phi*1.5707963267948966"),
+        CalculatorFloat::from("((phi * 3.141592653589793e0) / 2e0)"),
     ));
     let mut circuit_qoqo = Circuit::new();
     circuit_qoqo.add_operation(GateDefinition::new(
@@ -207,6 +222,42 @@ fn test_include_line_skip() {
     assert_eq!(circuit_from_file, circuit_qoqo);
 }
 
+/// Test that the includes of an opened file, whose directory is unknown, are skipped with a
+/// warning when they are not found, and resolved next to the file when reading from its path
+#[test]
+fn test_include_opened_file() {
+    let file = File::open("tests/include.qasm").unwrap();
+    let program = file_to_qasm_program(file).unwrap();
+    assert!(program.includes().is_empty());
+    assert_eq!(
+        program
+            .warnings()
+            .iter()
+            .map(|warning| (warning.message(), warning.line()))
+            .collect::<Vec<(&str, usize)>>(),
+        vec![
+            (
+                "Included file custom_lib.inc could not be found and is skipped",
+                2
+            ),
+            (
+                "Included file other_lib.inc could not be found and is skipped",
+                3
+            ),
+        ]
+    );
+
+    let program =
+        path_to_qasm_program_with_options(Path::new("tests/include.qasm"), &ParserOptions::new())
+            .unwrap();
+    assert_eq!(program.includes(), ["custom_lib.inc", "other_lib.inc"]);
+    assert!(program.warnings().is_empty());
+    assert_eq!(
+        program.circuit(),
+        &file_to_circuit(File::open("tests/include.qasm").unwrap()).unwrap()
+    );
+}
+
 #[allow(clippy::approx_constant)]
 #[test]
 fn test_symbols() {
@@ -235,6 +286,338 @@ fn test_symbols() {
     assert_eq!(circuit_from_file, circuit_qoqo);
 }
 
+/// Test that parameters with free identifiers are kept as symbolic expressions
+#[test]
+fn test_symbolic_parameters() {
+    let input = "OPENQASM 3.0;\ninput float theta;\ninput float phi_pi;\nqubit[2] q;\nrx(theta) q[0];\nrz(2*theta + pi) q[1];\nry(phi_pi) q[0];\ncp(sin(theta)) q[0], q[1];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += InputSymbolic::new("theta".into(), 0.0);
+    circuit_qoqo += InputSymbolic::new("phi_pi".into(), 0.0);
+    circuit_qoqo += RotateX::new(0, CalculatorFloat::from("theta"));
+    circuit_qoqo += RotateZ::new(
+        1,
+        CalculatorFloat::from("((2e0 * theta) + 3.141592653589793e0)"),
+    );
+    circuit_qoqo += RotateY::new(0, CalculatorFloat::from("phi_pi"));
+    circuit_qoqo += ControlledPhaseShift::new(0, 1, CalculatorFloat::from("sin(theta)"));
+    assert_eq!(circuit, circuit_qoqo);
+
+    // The values of the inputs are substituted from the InputSymbolic definitions
+    let options = ParserOptions::new()
+        .with_input_value("theta", 0.5)
+        .with_input_value("phi_pi", 0.25);
+    let circuit = string_to_qasm_program_with_options(input, &options)
+        .unwrap()
+        .into_circuit();
+    let numeric = string_to_circuit("OPENQASM 3.0;\nqubit[2] q;\nrx(0.5) q[0];\nrz(2*0.5 + pi) q[1];\nry(0.25) q[0];\ncp(sin(0.5)) q[0], q[1];\n").unwrap();
+    assert_eq!(
+        circuit
+            .substitute_parameters(&Calculator::new())
+            .unwrap()
+            .operations(),
+        numeric.operations()
+    );
+}
+
+/// Test the operators, constants and functions of parameter expressions
+#[test_case("2.0", "1 + 2 * 3", 7.0; "precedence of multiplication")]
+#[test_case("2.0", "(1 + 2) * 3", 9.0; "parentheses")]
+#[test_case("2.0", "8 - 2 - 1", 5.0; "left associative subtraction")]
+#[test_case("2.0", "2 ^ 3 ^ 2", 512.0; "right associative power")]
+#[test_case("3.0", "2 ** 3 ** 2", 512.0; "right associative double star")]
+#[test_case("3.0", "-2 ** 2", -4.0; "power binds tighter than minus")]
+#[test_case("3.0", "2 ** -1", 0.5; "negative exponent")]
+#[test_case("3.0", "2 * -3", -6.0; "minus after operator")]
+#[test_case("2.0", "1 / 2", 0.5; "real division in 2.0")]
+#[test_case("3.0", "7 / 2", 3.0; "integer division in 3.0")]
+#[test_case("3.0", "-7 / 2", -3.0; "integer division truncates")]
+#[test_case("3.0", "7.0 / 2", 3.5; "real division in 3.0")]
+#[test_case("3.0", "pi / 2", FRAC_PI_2; "constant division")]
+#[test_case("3.0", "7 % 3", 1.0; "modulo")]
+#[test_case("3.0", "-7 % 3", -1.0; "modulo sign of dividend")]
+#[test_case("3.0", "mod(7.5, 2)", 1.5; "mod function")]
+#[test_case("3.0", "pow(2, 10)", 1024.0; "pow function")]
+#[test_case("3.0", "π", PI; "unicode pi")]
+#[test_case("3.0", "tau", 2.0 * PI; "tau")]
+#[test_case("3.0", "τ / 2", PI; "unicode tau")]
+#[test_case("3.0", "euler", std::f64::consts::E; "euler")]
+#[test_case("3.0", "ln(ℇ)", 1.0; "unicode euler")]
+#[test_case("2.0", "asin(1)", FRAC_PI_2; "asin")]
+#[test_case("2.0", "acos(0)", FRAC_PI_2; "acos")]
+#[test_case("2.0", "atan(1) * 4", PI; "atan")]
+#[test_case("3.0", "arccos(0)", FRAC_PI_2; "arccos")]
+#[test_case("2.0", "tan(0)", 0.0; "tan")]
+#[test_case("3.0", "log(exp(2))", 2.0; "log")]
+#[test_case("3.0", "floor(2.5) + ceiling(2.5)", 5.0; "floor and ceiling")]
+#[test_case("2.0", "sqrt(4)^2", 4.0; "function in power")]
+fn test_parameter_expressions(qasm_version: &str, expression: &str, value: f64) {
+    let input = format!(
+        "OPENQASM {qasm_version};\ninclude \"qelib1.inc\";\nqreg q[1];\nrz({expression}) q[0];\n"
+    );
+
+    let circuit = string_to_circuit(&input).unwrap();
+
+    match circuit.operations().first() {
+        Some(Operation::RotateZ(operation)) => {
+            assert!(
+                (operation.theta().float().unwrap() - value).abs() < 1e-12,
+                "{expression} evaluated to {}",
+                operation.theta()
+            );
+        }
+        operation => panic!("Unexpected operation {operation:?}"),
+    }
+}
+
+/// Test that symbolic expressions with all operators evaluate to the numeric value
+#[test]
+fn test_symbolic_parameter_expressions() {
+    let input = "OPENQASM 3.0;\ninput float a;\ninput float b;\nqubit[1] q;\nrx(-a ** 2 + asin(b) * tau) q[0];\nry(a % b - atan(a) / euler) q[0];\nrz(pow(a, b) + floor(a) + mod(a, -b)) q[0];\n";
+    let options = ParserOptions::new()
+        .with_input_value("a", 2.5)
+        .with_input_value("b", 0.75);
+
+    let circuit = string_to_qasm_program_with_options(input, &options)
+        .unwrap()
+        .into_circuit();
+    let substituted = circuit.substitute_parameters(&Calculator::new()).unwrap();
+
+    let a: f64 = 2.5;
+    let b: f64 = 0.75;
+    let expected = [
+        -a.powi(2) + b.asin() * 2.0 * PI,
+        a % b - a.atan() / std::f64::consts::E,
+        a.powf(b) + a.floor() + a % -b,
+    ];
+    for (operation, value) in substituted.operations().iter().zip(expected) {
+        let theta = match operation {
+            Operation::RotateX(operation) => operation.theta(),
+            Operation::RotateY(operation) => operation.theta(),
+            Operation::RotateZ(operation) => operation.theta(),
+            operation => panic!("Unexpected operation {operation:?}"),
+        };
+        assert!((theta.float().unwrap() - value).abs() < 1e-12);
+    }
+}
+
+/// Test that identifiers containing constant names are kept
+#[test]
+fn test_identifiers_containing_constants() {
+    let input = "OPENQASM 3.0;\ninput float pi_half;\ninput float tau2;\nqubit[1] q;\nrx(pi_half + tau2) q[0];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    assert_eq!(
+        circuit.operations().first(),
+        Some(&Operation::from(RotateX::new(
+            0,
+            CalculatorFloat::from("(pi_half + tau2)")
+        )))
+    );
+}
+
+/// Test that symbolic parameters are passed to calls of defined gates
+#[test]
+fn test_symbolic_parameters_defined_gate() {
+    let input = "OPENQASM 2.0;\nqreg q[1];\ngate g(alpha) a { rx(alpha) a; }\ng(spin*pi) q[0];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    assert_eq!(
+        circuit.operations().last(),
+        Some(&Operation::from(CallDefinedGate::new(
+            "g".to_owned(),
+            vec![0],
+            vec![CalculatorFloat::from("(spin * 3.141592653589793e0)")],
+        )))
+    );
+}
+
+/// Test that the standard gates with a roqoqo equivalent are translated into it
+#[test_case("sdg q[0];", Operation::from(InvSGate::new(0)); "sdg")]
+#[test_case("tdg q[0];", Operation::from(InvTGate::new(0)); "tdg")]
+#[test_case("id q[0];", Operation::from(Identity::new(0)); "id")]
+#[test_case("u0(2) q[0];", Operation::from(Identity::new(0)); "u0")]
+#[test_case("sy q[0];", Operation::from(SqrtPauliY::new(0)); "sy")]
+#[test_case("sydg q[0];", Operation::from(InvSqrtPauliY::new(0)); "sydg")]
+#[test_case("phase(0.5) q[0];", Operation::from(PhaseShiftState1::new(0, 0.5.into())); "phase")]
+#[test_case("CX q[0], q[1];", Operation::from(CNOT::new(0, 1)); "CX")]
+#[test_case("cu1(0.5) q[0], q[1];", Operation::from(ControlledPhaseShift::new(0, 1, 0.5.into())); "cu1")]
+#[test_case("cphase(0.5) q[0], q[1];", Operation::from(ControlledPhaseShift::new(0, 1, 0.5.into())); "cphase")]
+#[test_case("cswap q[0], q[1], q[2];", Operation::from(ControlledSWAP::new(0, 1, 2)); "cswap")]
+#[test_case("c3x q[0], q[1], q[2], q[3];", Operation::from(TripleControlledPauliX::new(0, 1, 2, 3)); "c3x")]
+fn test_standard_gates(gate: &str, operation: Operation) {
+    for header in ["OPENQASM 2.0;\nqreg q[4];", "OPENQASM 3.0;\nqubit[4] q;"] {
+        let circuit = string_to_circuit(&format!("{header}\n{gate}\n")).unwrap();
+        assert_eq!(circuit.operations(), &vec![operation.clone()]);
+    }
+}
+
+/// Test that u and U are translated like u3
+#[test]
+fn test_standard_gates_u() {
+    let u3 = string_to_circuit("OPENQASM 2.0;\nqreg q[1];\nu3(0.1,0.2,0.3) q[0];\n").unwrap();
+    let u = string_to_circuit("OPENQASM 2.0;\nqreg q[1];\nu(0.1,0.2,0.3) q[0];\n").unwrap();
+    let upper_u = string_to_circuit("OPENQASM 2.0;\nqreg q[1];\nU(0.1,0.2,0.3) q[0];\n").unwrap();
+    assert_eq!(u3.operations().len(), 1);
+    assert_eq!(u, u3);
+    assert_eq!(upper_u, u3);
+}
+
+/// Test that the standard gates without a roqoqo equivalent are decomposed
+#[test]
+fn test_standard_gate_decompositions() {
+    let circuit = string_to_circuit("OPENQASM 2.0;\nqreg q[2];\ncrz(0.5) q[0], q[1];\ncry(0.5) q[1], q[0];\nrzz(0.5) q[0], q[1];\nch q[0], q[1];\n").unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += RotateZ::new(1, 0.25.into());
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += RotateZ::new(1, (-0.25).into());
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += RotateY::new(0, 0.25.into());
+    circuit_qoqo += CNOT::new(1, 0);
+    circuit_qoqo += RotateY::new(0, (-0.25).into());
+    circuit_qoqo += CNOT::new(1, 0);
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += PhaseShiftState1::new(1, 0.5.into());
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += SGate::new(1);
+    circuit_qoqo += Hadamard::new(1);
+    circuit_qoqo += TGate::new(1);
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += InvTGate::new(1);
+    circuit_qoqo += Hadamard::new(1);
+    circuit_qoqo += InvSGate::new(1);
+    assert_eq!(circuit, circuit_qoqo);
+
+    for (gate, number_operations) in [
+        ("cu3(0.1,0.2,0.3) q[0], q[1];", 6),
+        ("cu(0.1,0.2,0.3,0.4) q[0], q[1];", 7),
+        ("csx q[0], q[1];", 3),
+        ("rccx q[0], q[1], q[2];", 9),
+        ("rc3x q[0], q[1], q[2], q[3];", 18),
+        ("c3sqrtx q[0], q[1], q[2], q[3];", 27),
+        ("c4x q[0], q[1], q[2], q[3], q[4];", 69),
+    ] {
+        let circuit = string_to_circuit(&format!("OPENQASM 2.0;\nqreg q[5];\n{gate}\n")).unwrap();
+        assert_eq!(circuit.operations().len(), number_operations, "{gate}");
+    }
+
+    // Decompositions are also used in gate definitions
+    let circuit = string_to_circuit(
+        "OPENQASM 2.0;\nqreg q[2];\ngate g a, b { crz(0.5) a, b; }\ng q[0], q[1];\n",
+    )
+    .unwrap();
+    match &circuit.definitions()[0] {
+        Operation::GateDefinition(definition) => assert_eq!(definition.circuit().len(), 4),
+        _ => panic!("Expected a GateDefinition"),
+    }
+}
+
+/// Test the translation of unknown gates with the different policies
+#[test]
+fn test_unknown_gate_policy() {
+    let input =
+        "OPENQASM 2.0;\nqreg q[2];\nfoo(0.5) q[0], q[1];\nx q[0];\ngate g a { bar a; }\ng q[1];\n";
+
+    let lenient = ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Lenient);
+    let program = string_to_qasm_program_with_options(input, &lenient).unwrap();
+    let (circuit, warnings) = (program.circuit(), program.warnings());
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += PauliX::new(0);
+    circuit_qoqo += GateDefinition::new(Circuit::new(), "g".to_owned(), vec![0], vec![]);
+    circuit_qoqo += CallDefinedGate::new("g".to_owned(), vec![1], vec![]);
+    assert_eq!(circuit, &circuit_qoqo);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        warnings[0].to_string(),
+        "Warning at line 3, column 1: Gate foo has not been declared and is dropped\n    foo(0.5) q[0], q[1];"
+    );
+    assert_eq!(
+        (
+            warnings[1].line(),
+            warnings[1].column(),
+            warnings[1].snippet()
+        ),
+        (5, 12, "bar a;")
+    );
+
+    let strict = ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Strict);
+    let error = string_to_qasm_program_with_options(input, &strict).unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Undeclared);
+    assert_eq!((error.line(), error.column()), (3, 1));
+    assert_eq!(error.message(), "Gate foo has not been declared");
+
+    // Opaque is the default policy
+    let program = string_to_qasm_program_with_options(input, &ParserOptions::new()).unwrap();
+    let mut bar_call = Circuit::new();
+    bar_call += CallDefinedGate::new("bar".to_owned(), vec![0], vec![]);
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += GateDefinition::new(
+        Circuit::new(),
+        "foo".to_owned(),
+        vec![0, 1],
+        vec!["param_0".to_owned()],
+    );
+    circuit_qoqo += CallDefinedGate::new("foo".to_owned(), vec![0, 1], vec![0.5.into()]);
+    circuit_qoqo += PauliX::new(0);
+    circuit_qoqo += GateDefinition::new(Circuit::new(), "bar".to_owned(), vec![0], vec![]);
+    circuit_qoqo += GateDefinition::new(bar_call, "g".to_owned(), vec![0], vec![]);
+    circuit_qoqo += CallDefinedGate::new("g".to_owned(), vec![1], vec![]);
+    assert_eq!(program.circuit(), &circuit_qoqo);
+    assert!(program.warnings().is_empty());
+    assert_eq!(string_to_circuit(input).unwrap(), circuit_qoqo);
+}
+
+/// Test that opaque declarations of unknown gates in conditionals are added to the circuit
+#[test]
+fn test_unknown_gate_policy_conditional() {
+    let input = "OPENQASM 2.0;\nqreg q[1];\ncreg c[1];\nif(c[0]==1) foo q[0];\n";
+    let opaque = ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Opaque);
+
+    let circuit = string_to_qasm_program_with_options(input, &opaque)
+        .unwrap()
+        .into_circuit();
+
+    let mut conditional_circuit = Circuit::new();
+    conditional_circuit += CallDefinedGate::new("foo".to_owned(), vec![0], vec![]);
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("c".to_owned(), 1, true);
+    circuit_qoqo += GateDefinition::new(Circuit::new(), "foo".to_owned(), vec![0], vec![]);
+    circuit_qoqo += PragmaConditional::new("c".to_owned(), 0, conditional_circuit);
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that calls of custom gates in the 3.0Braket output, which has no gate definitions, are kept
+#[test]
+fn test_unknown_gate_braket_call_kept() {
+    let backend = Backend::new(None, Some("3.0Braket".to_string())).unwrap();
+    let mut gate_body = Circuit::new();
+    gate_body += RotateZ::new(0, "theta".into());
+    let mut circuit = Circuit::new();
+    circuit += GateDefinition::new(
+        gate_body,
+        "my_gate".to_owned(),
+        vec![0],
+        vec!["theta".to_owned()],
+    );
+    circuit += CallDefinedGate::new("my_gate".to_owned(), vec![1], vec![0.5.into()]);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    let program = string_to_qasm_program_with_options(&qasm, &ParserOptions::new()).unwrap();
+
+    assert!(program.warnings().is_empty());
+    let call = Operation::from(CallDefinedGate::new(
+        "my_gate".to_owned(),
+        vec![1],
+        vec![0.5.into()],
+    ));
+    assert!(program.circuit().iter().any(|operation| *operation == call));
+}
+
 #[test]
 fn test_acceptance_test() {
     let path = std::env::current_dir()
@@ -243,3 +626,1508 @@ fn test_acceptance_test() {
     let file = File::open(path).unwrap();
     assert!(file_to_circuit(file).is_ok());
 }
+
+#[test]
+fn test_qasm3_file() {
+    let file = File::open(std::env::current_dir().unwrap().join("tests/qasm3.qasm")).unwrap();
+
+    let circuit_from_file = file_to_circuit(file).unwrap();
+
+    let mut loop_circuit = Circuit::new();
+    loop_circuit += PauliX::new(2);
+    let mut conditional_circuit = Circuit::new();
+    conditional_circuit += RotateX::new(1, FRAC_PI_2.into());
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += InputSymbolic::new("theta".into(), 0.0);
+    circuit_qoqo += DefinitionBit::new("ro".into(), 2, true);
+    circuit_qoqo += Hadamard::new(0);
+    circuit_qoqo += CNOT::new(0, 1);
+    circuit_qoqo += PragmaLoop::new(3.0.into(), loop_circuit);
+    circuit_qoqo += PragmaConditional::new("ro".into(), 0, conditional_circuit);
+    circuit_qoqo += PragmaGlobalPhase::new(0.5.into());
+    circuit_qoqo += MeasureQubit::new(0, "ro".into(), 0);
+
+    assert_eq!(circuit_from_file, circuit_qoqo);
+}
+
+/// Test that input declarations are read as InputSymbolic with the given values
+#[test]
+fn test_input_declarations() {
+    let input = "OPENQASM 3.0;\ninput float theta;\ninput angle[32] phi;\nqubit[1] q;\nrx(theta + phi) q[0];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += InputSymbolic::new("theta".into(), 0.0);
+    circuit_qoqo += InputSymbolic::new("phi".into(), 0.0);
+    circuit_qoqo += RotateX::new(0, CalculatorFloat::from("(theta + phi)"));
+    assert_eq!(circuit, circuit_qoqo);
+
+    let options = ParserOptions::new().with_input_value("theta", 0.5);
+    let circuit = string_to_qasm_program_with_options(input, &options)
+        .unwrap()
+        .into_circuit();
+    assert_eq!(
+        circuit.definitions()[0],
+        Operation::from(InputSymbolic::new("theta".into(), 0.5))
+    );
+    assert_eq!(
+        circuit.definitions()[1],
+        Operation::from(InputSymbolic::new("phi".into(), 0.0))
+    );
+
+    let error = string_to_qasm_program_with_options(
+        "OPENQASM 3.0;\ninput float theta;\ninput float theta;\n",
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Redeclaration);
+    assert_eq!(error.line(), 3);
+}
+
+/// Test that assignments to single bits are read as InputBit
+#[test]
+fn test_bit_assignments() {
+    let input = "OPENQASM 3.0;\nbit[3] ro;\nro[0] = 1;\nro[1] = false;\nro[2] = true;\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("ro".into(), 3, true);
+    circuit_qoqo += InputBit::new("ro".into(), 0, true);
+    circuit_qoqo += InputBit::new("ro".into(), 1, false);
+    circuit_qoqo += InputBit::new("ro".into(), 2, true);
+    assert_eq!(circuit, circuit_qoqo);
+
+    let error = string_to_circuit("OPENQASM 3.0;\nbit[3] ro;\nro[3] = 1;\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::IndexOutOfRange);
+    let error = string_to_circuit("OPENQASM 3.0;\nbit[3] ro;\nout[0] = 1;\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Undeclared);
+}
+
+/// Test that InputSymbolic and InputBit written by the QASM 3.0 backend can be read back
+#[test]
+fn test_roundtrip_inputs() {
+    let mut circuit = Circuit::new();
+    circuit += InputSymbolic::new("theta".into(), 0.0);
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += InputBit::new("ro".into(), 1, true);
+    circuit += RotateX::new(0, CalculatorFloat::from("theta"));
+    circuit += RotateZ::new(0, CalculatorFloat::from("alpha"));
+    let backend = Backend::new(None, Some("3.0Vanilla".to_string())).unwrap();
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert_eq!(qasm.matches("theta;").count(), 1);
+
+    let read_back = string_to_circuit(&qasm).unwrap();
+    assert!(read_back
+        .definitions()
+        .contains(&Operation::from(InputSymbolic::new("theta".into(), 0.0))));
+    assert!(read_back
+        .definitions()
+        .contains(&Operation::from(InputSymbolic::new("alpha".into(), 0.0))));
+    assert!(read_back
+        .operations()
+        .contains(&Operation::from(InputBit::new("ro".into(), 1, true))));
+    assert!(read_back
+        .operations()
+        .contains(&Operation::from(RotateX::new(
+            0,
+            CalculatorFloat::from("theta")
+        ))));
+}
+
+/// Test that the classical declarations written by the QASM 3.0 backends can be read back
+#[test_case("3.0Vanilla"; "3.0Vanilla")]
+#[test_case("3.0Roqoqo"; "3.0Roqoqo")]
+fn test_roundtrip_classical_declarations(qasm_version: &str) {
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += DefinitionBit::new("flags".into(), 1, false);
+    circuit += DefinitionFloat::new("values".into(), 3, true);
+    circuit += DefinitionUsize::new("counts".into(), 2, false);
+    circuit += DefinitionComplex::new("amplitudes".into(), 4, true);
+    circuit += DefinitionComplex::new("scratch".into(), 1, false);
+    circuit += PauliX::new(0);
+    let backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that all classical registers are outputs when the file declares no output
+#[test]
+fn test_classical_declarations_without_output() {
+    let input = "OPENQASM 3.0;\nbit b;\nfloat[2] values;\nuint[3] counts;\nfloat[2] z_re;\nfloat[2] z_im;\nfloat[2] w_re;\nfloat[1] w_im;\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("b".into(), 1, true);
+    circuit_qoqo += DefinitionFloat::new("values".into(), 2, true);
+    circuit_qoqo += DefinitionUsize::new("counts".into(), 3, true);
+    circuit_qoqo += DefinitionComplex::new("z".into(), 2, true);
+    circuit_qoqo += DefinitionFloat::new("w_re".into(), 2, true);
+    circuit_qoqo += DefinitionFloat::new("w_im".into(), 1, true);
+    assert_eq!(circuit, circuit_qoqo);
+
+    let error = string_to_circuit("OPENQASM 3.0;\nbit[2] ro;\nfloat[2] ro;\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Redeclaration);
+    assert_eq!(error.line(), 3);
+}
+
+/// Test that the output of the QASM 3.0 backends can be read back
+#[test_case("3.0Vanilla"; "3.0Vanilla")]
+#[test_case("3.0Roqoqo"; "3.0Roqoqo")]
+#[test_case("3.0Braket"; "3.0Braket")]
+fn test_qasm3_roundtrip(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+
+    let mut conditional_circuit = Circuit::new();
+    conditional_circuit += PauliX::new(1);
+    conditional_circuit += RotateZ::new(0, 0.3.into());
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += Hadamard::new(0);
+    circuit += RotateX::new(1, (-PI).into());
+    circuit += CNOT::new(0, 1);
+    circuit += PhaseShiftState1::new(1, 0.6.into());
+    circuit += SqrtPauliX::new(0);
+    circuit += MolmerSorensenXX::new(0, 1);
+    circuit += VariableMSXX::new(1, 0, 0.7.into());
+    circuit += ControlledPhaseShift::new(0, 1, 0.5.into());
+    circuit += PragmaConditional::new("ro".into(), 1, conditional_circuit);
+    circuit += MeasureQubit::new(0, "ro".into(), 0);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test the number of repetitions of loops over integer ranges
+#[test_case("[0:3]", 4.0.into(); "from zero")]
+#[test_case("[2:3]", 2.0.into(); "from two")]
+#[test_case("[3:2]", 0.0.into(); "empty")]
+#[test_case("[0:2:5]", 3.0.into(); "step")]
+#[test_case("[5:-2:0]", 3.0.into(); "negative step")]
+#[test_case("[-1:2*2]", 6.0.into(); "expression")]
+#[test_case("[0:n-1]", "n".into(); "symbolic")]
+#[test_case("[0:2*n-1]", "(2e0 * n)".into(); "symbolic expression")]
+#[test_case("[1:n]", "((n - 1e0) + 1e0)".into(); "symbolic stop")]
+fn test_loop_ranges(range: &str, repetitions: CalculatorFloat) {
+    let input =
+        format!("OPENQASM 3.0;\nqubit[1] q;\nfor int[32] j in {range} {{\n    x q[0];\n}}\n");
+
+    let mut body = Circuit::new();
+    body += PauliX::new(0);
+    let mut circuit = Circuit::new();
+    circuit += PragmaLoop::new(repetitions, body);
+    assert_eq!(string_to_circuit(&input).unwrap(), circuit);
+}
+
+/// Test that nested and symbolic loops written by the QASM 3.0 backend can be read back
+#[test]
+fn test_qasm3_roundtrip_nested_loops() {
+    let backend = Backend::new(None, Some("3.0Vanilla".to_string())).unwrap();
+
+    let mut inner_circuit = Circuit::new();
+    inner_circuit += RotateX::new(1, "theta".into());
+    let mut loop_circuit = Circuit::new();
+    loop_circuit += Hadamard::new(0);
+    loop_circuit += PragmaLoop::new(2.0.into(), inner_circuit);
+    let mut circuit = Circuit::new();
+    circuit += PragmaLoop::new("n".into(), loop_circuit);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm.contains("input uint n;\ninput angle[32] theta;\n"));
+    assert!(qasm.contains("for uint i1 in [0:n-1] {\n    h q[0];\n    for uint i in [0:1] {\n"));
+
+    let parsed = string_to_circuit(&qasm).unwrap();
+    let mut expected = Circuit::new();
+    expected += InputSymbolic::new("n".to_string(), 0.0);
+    expected += InputSymbolic::new("theta".to_string(), 0.0);
+    assert_eq!(parsed, expected + circuit);
+}
+
+/// Test that loops and global phases written by the QASM 3.0 backend can be read back
+#[test]
+fn test_qasm3_roundtrip_loop() {
+    let backend = Backend::new(None, Some("3.0Vanilla".to_string())).unwrap();
+
+    let mut loop_circuit = Circuit::new();
+    loop_circuit += Hadamard::new(0);
+    loop_circuit += PauliY::new(1);
+    let mut circuit = Circuit::new();
+    circuit += PragmaLoop::new(4.0.into(), loop_circuit);
+    circuit += PragmaGlobalPhase::new(0.25.into());
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+#[test]
+fn test_conditionals() {
+    let file = File::open(
+        std::env::current_dir()
+            .unwrap()
+            .join("tests/conditional.qasm"),
+    )
+    .unwrap();
+
+    let circuit_from_file = file_to_circuit(file).unwrap();
+
+    let mut c0_circuit = Circuit::new();
+    c0_circuit += PauliX::new(0);
+    c0_circuit += Hadamard::new(1);
+    let mut d0_circuit = Circuit::new();
+    d0_circuit += PauliX::new(1);
+    let mut c1_circuit = Circuit::new();
+    c1_circuit += PauliY::new(0);
+    c1_circuit += MeasureQubit::new(1, "d".into(), 0);
+    let mut c_circuit = Circuit::new();
+    c_circuit += PragmaConditional::new("c".into(), 1, c1_circuit);
+    let mut c1_after_measurement = Circuit::new();
+    c1_after_measurement += PauliZ::new(1);
+    let mut c_after_measurement = Circuit::new();
+    c_after_measurement += PragmaConditional::new("c".into(), 1, c1_after_measurement);
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("c".into(), 2, true);
+    circuit_qoqo += DefinitionBit::new("d".into(), 1, true);
+    circuit_qoqo += MeasureQubit::new(0, "c".into(), 0);
+    circuit_qoqo += PragmaConditional::new("c".into(), 0, c0_circuit);
+    circuit_qoqo += PragmaConditional::new("d".into(), 0, d0_circuit);
+    circuit_qoqo += PragmaConditional::new("c".into(), 0, c_circuit);
+    circuit_qoqo += PragmaConditional::new("c".into(), 0, c_after_measurement);
+
+    assert_eq!(circuit_from_file, circuit_qoqo);
+}
+
+#[test_case("OPENQASM 2.0;\nqreg q[1];\ncreg c[2];\nif(c==2) x q[0];\n", "c==2 cannot be expressed"; "register value")]
+#[test_case("OPENQASM 2.0;\nqreg q[1];\ncreg c[2];\nif(c[0]==0) x q[0];\n", "single bit can only be checked for the value 1"; "bit value")]
+#[test_case("OPENQASM 2.0;\nqreg q[1];\nif(c==1) x q[0];\n", "register c has not been declared"; "undeclared register")]
+#[test_case("OPENQASM 2.0;\nqreg q[1];\nif(c[0]==1) x q[0];\n", "register c has not been declared"; "undeclared bit register")]
+#[test_case("OPENQASM 2.0;\nqreg q[1];\ncreg c[2];\nif(c[2]==1) x q[0];\n", "Bit c[2] is out of range"; "bit out of range")]
+fn test_conditional_errors(input: &str, message: &str) {
+    let error = string_to_circuit(input).unwrap_err();
+    assert!(error.to_string().contains(message));
+}
+
+/// Test that the conditionals written by the QASM 2.0 backend can be read back
+#[test]
+fn test_qasm2_roundtrip_conditional() {
+    let backend = Backend::new(None, None).unwrap();
+
+    let mut conditional_circuit = Circuit::new();
+    conditional_circuit += PauliX::new(1);
+    conditional_circuit += RotateZ::new(0, 0.3.into());
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += MeasureQubit::new(0, "ro".into(), 1);
+    circuit += PragmaConditional::new("ro".into(), 1, conditional_circuit);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that qubits of several quantum registers are mapped to distinct roqoqo qubits
+#[test]
+fn test_multiple_registers() {
+    let file = File::open(
+        std::env::current_dir()
+            .unwrap()
+            .join("tests/registers.qasm"),
+    )
+    .unwrap();
+
+    let (circuit_from_file, registers) = file_to_circuit_with_registers(file).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("c".into(), 2, true);
+    circuit_qoqo += Hadamard::new(1);
+    circuit_qoqo += CNOT::new(0, 2);
+    circuit_qoqo += RotateZ::new(4, 0.5.into());
+    circuit_qoqo += MeasureQubit::new(3, "c".into(), 0);
+    circuit_qoqo += PragmaActiveReset::new(0);
+
+    assert_eq!(circuit_from_file, circuit_qoqo);
+    assert_eq!(registers.number_qubits(), 5);
+    assert_eq!(registers.register_offset("b"), Some(2));
+    assert_eq!(registers.register_size("b"), Some(3));
+    assert_eq!(registers.qubit_index("a", 1), Some(1));
+    assert_eq!(registers.qubit_index("b", 2), Some(4));
+    assert_eq!(registers.qubit_index("b", 3), None);
+    assert_eq!(registers.qubit_index("c", 0), None);
+    assert_eq!(
+        registers.iter().collect::<Vec<(&str, usize, usize)>>(),
+        vec![("a", 0, 2), ("b", 2, 3)]
+    );
+}
+
+/// Test the register table of an OpenQASM 3.0 file
+#[test]
+fn test_qasm3_registers() {
+    let input = "OPENQASM 3.0;\nqubit[2] q;\nqubit r;\nx r[0];\ncx q[1],r[0];\n";
+
+    let (circuit, registers) = string_to_circuit_with_registers(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += PauliX::new(2);
+    circuit_qoqo += CNOT::new(1, 2);
+    assert_eq!(circuit, circuit_qoqo);
+    assert_eq!(registers.qubit_index("r", 0), Some(2));
+}
+
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nx r[0];\n", "register r has not been declared"; "undeclared register")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nx q[2];\n", "q[2] is out of range for quantum register q of size 2"; "index out of range")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nqreg q[1];\n", "register q has already been declared"; "duplicate register")]
+fn test_register_errors(input: &str, message: &str) {
+    let error = string_to_circuit(input).unwrap_err();
+    assert!(error.to_string().contains(message));
+}
+
+/// Test that statements on whole registers are applied to each qubit of the registers
+#[test]
+fn test_broadcast() {
+    let file = File::open(
+        std::env::current_dir()
+            .unwrap()
+            .join("tests/broadcast.qasm"),
+    )
+    .unwrap();
+
+    let circuit_from_file = file_to_circuit(file).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("c".into(), 2, true);
+    circuit_qoqo += DefinitionBit::new("d".into(), 2, true);
+    circuit_qoqo += Hadamard::new(0);
+    circuit_qoqo += Hadamard::new(1);
+    circuit_qoqo += CNOT::new(0, 2);
+    circuit_qoqo += CNOT::new(1, 3);
+    circuit_qoqo += CNOT::new(0, 2);
+    circuit_qoqo += CNOT::new(0, 3);
+    circuit_qoqo += RotateZ::new(2, 0.5.into());
+    circuit_qoqo += RotateZ::new(3, 0.5.into());
+    circuit_qoqo += PragmaActiveReset::new(2);
+    circuit_qoqo += PragmaActiveReset::new(3);
+    circuit_qoqo +=
+        PragmaRepeatedMeasurement::new("c".into(), 1, Some(HashMap::from([(0, 0), (1, 1)])));
+    circuit_qoqo +=
+        PragmaRepeatedMeasurement::new("d".into(), 1, Some(HashMap::from([(2, 0), (3, 1)])));
+
+    assert_eq!(circuit_from_file, circuit_qoqo);
+}
+
+/// Test that the measurement of all qubits written by the backend is read back
+#[test_case("2.0", 2; "2.0")]
+#[test_case("3.0Vanilla", 2; "3.0Vanilla")]
+#[test_case("3.0Braket", 2; "3.0Braket")]
+#[test_case("2.0", 3; "2.0 longer readout")]
+#[test_case("3.0Vanilla", 3; "3.0Vanilla longer readout")]
+#[test_case("3.0Braket", 3; "3.0Braket longer readout")]
+fn test_roundtrip_repeated_measurement(qasm_version: &str, readout_length: usize) {
+    let backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), readout_length, true);
+    circuit += Hadamard::new(0);
+    circuit += CNOT::new(0, 1);
+    circuit += PragmaRepeatedMeasurement::new("ro".into(), 1, None);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that a whole-register measurement keeps its mapping when registers are declared after it
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncreg c[2];\nmeasure q -> c;\nqreg r[1];\nx r[0];\n"; "2.0")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nbit[2] c;\nc = measure q;\nqubit[1] r;\nx r[0];\n"; "3.0")]
+fn test_measurement_before_later_register(input: &str) {
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("c".into(), 2, true);
+    circuit_qoqo +=
+        PragmaRepeatedMeasurement::new("c".into(), 1, Some(HashMap::from([(0, 0), (1, 1)])));
+    circuit_qoqo += PauliX::new(2);
+
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that the assignment form of OpenQASM 3.0 measurements is read
+#[test]
+fn test_measurement_assignment() {
+    let input = "OPENQASM 3.0;\nqubit[2] q;\nqubit[1] r;\nbit[2] ro;\nbit[1] c;\nro[1] = measure q[0];\nro = measure q;\nc = measure r;\nif (ro[0] == 1) c[0] = measure r[0];\n";
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut conditional_circuit = Circuit::new();
+    conditional_circuit += MeasureQubit::new(2, "c".into(), 0);
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += DefinitionBit::new("ro".into(), 2, true);
+    circuit_qoqo += DefinitionBit::new("c".into(), 1, true);
+    circuit_qoqo += MeasureQubit::new(0, "ro".into(), 1);
+    circuit_qoqo +=
+        PragmaRepeatedMeasurement::new("ro".into(), 1, Some(HashMap::from([(0, 0), (1, 1)])));
+    circuit_qoqo += PragmaRepeatedMeasurement::new("c".into(), 1, Some(HashMap::from([(2, 0)])));
+    circuit_qoqo += PragmaConditional::new("ro".into(), 0, conditional_circuit);
+
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that measurements written in the assignment form are read back
+#[test_case("3.0Vanilla"; "3.0Vanilla")]
+#[test_case("3.0Roqoqo"; "3.0Roqoqo")]
+fn test_roundtrip_measurement_assignment(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string()))
+        .unwrap()
+        .with_emitter_options(EmitterOptions::new().with_measurement_assignment(true));
+
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += Hadamard::new(0);
+    circuit += CNOT::new(0, 1);
+    circuit += MeasureQubit::new(0, "ro".into(), 1);
+    circuit += PragmaRepeatedMeasurement::new("ro".into(), 1, None);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm.contains("ro[1] = measure q[0];"));
+    assert!(qasm.contains("ro = measure q;"));
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that gate modifiers are translated to the controlled, inverse or repeated operations
+#[test_case("ctrl @ x q[0], q[1];", vec![Operation::from(CNOT::new(0, 1))]; "ctrl x")]
+#[test_case("ctrl(2) @ x q[0], q[1], q[2];", vec![Operation::from(Toffoli::new(0, 1, 2))]; "ctrl 2 x")]
+#[test_case("ctrl @ ctrl @ x q[0], q[1], q[2];", vec![Operation::from(Toffoli::new(0, 1, 2))]; "nested ctrl x")]
+#[test_case("ctrl @ cx q[2], q[0], q[1];", vec![Operation::from(Toffoli::new(2, 0, 1))]; "ctrl cx")]
+#[test_case("ctrl(3) @ z q[0], q[1], q[2], q[3];", vec![Operation::from(TripleControlledPauliZ::new(0, 1, 2, 3))]; "ctrl 3 z")]
+#[test_case("ctrl @ p(0.1) q[0], q[1];", vec![Operation::from(ControlledPhaseShift::new(0, 1, 0.1.into()))]; "ctrl p")]
+#[test_case("ctrl @ rx(0.1) q[1], q[0];", vec![Operation::from(ControlledRotateX::new(1, 0, 0.1.into()))]; "ctrl rx")]
+#[test_case("ctrl @ swap q[0], q[1], q[2];", vec![Operation::from(ControlledSWAP::new(0, 1, 2))]; "ctrl swap")]
+#[test_case("ctrl @ h q[0], q[1];", vec![Operation::from(PragmaControlledCircuit::new(0, [Operation::from(Hadamard::new(1))].into_iter().collect()))]; "ctrl h")]
+#[test_case("ctrl @ sx q[0], q[1];", vec![Operation::from(PragmaControlledCircuit::new(0, [Operation::from(SqrtPauliX::new(1))].into_iter().collect())), Operation::from(PhaseShiftState1::new(0, CalculatorFloat::FRAC_PI_4))]; "ctrl sx with phase")]
+#[test_case("negctrl @ x q[0], q[1];", vec![Operation::from(PauliX::new(0)), Operation::from(CNOT::new(0, 1)), Operation::from(PauliX::new(0))]; "negctrl x")]
+#[test_case("inv @ s q[0];", vec![Operation::from(InvSGate::new(0))]; "inv s")]
+#[test_case("inv @ inv @ t q[0];", vec![Operation::from(TGate::new(0))]; "inv inv t")]
+#[test_case("inv @ rx(0.1) q[0];", vec![Operation::from(RotateX::new(0, (-0.1).into()))]; "inv rx")]
+#[test_case("inv @ ctrl @ rx(0.1) q[0], q[1];", vec![Operation::from(ControlledRotateX::new(0, 1, (-0.1).into()))]; "inv ctrl rx")]
+#[test_case("ctrl @ inv @ p(0.1) q[0], q[1];", vec![Operation::from(ControlledPhaseShift::new(0, 1, (-0.1).into()))]; "ctrl inv p")]
+#[test_case("pow(2) @ s q[0];", vec![Operation::from(SGate::new(0)), Operation::from(SGate::new(0))]; "pow 2 s")]
+#[test_case("pow(-1) @ t q[0];", vec![Operation::from(InvTGate::new(0))]; "pow minus 1 t")]
+#[test_case("pow(0) @ h q[0];", vec![]; "pow 0 h")]
+#[test_case("pow(0.5) @ rz(0.2) q[0];", vec![Operation::from(RotateZ::new(0, 0.1.into()))]; "pow half rz")]
+#[test_case("ctrl @ x q[0], r;", vec![Operation::from(CNOT::new(0, 4)), Operation::from(CNOT::new(0, 5))]; "broadcast")]
+fn test_gate_modifiers(statement: &str, operations: Vec<Operation>) {
+    let input = format!("OPENQASM 3.0;\nqubit[4] q;\nqubit[2] r;\n{statement}\n");
+    let circuit = string_to_circuit(&input).unwrap();
+
+    assert_eq!(
+        circuit.iter().cloned().collect::<Vec<Operation>>(),
+        operations
+    );
+}
+
+/// Test that gate modifiers of user-defined gates generate inverse definitions
+#[test]
+fn test_gate_modifiers_defined_gates() {
+    let input = "OPENQASM 3.0;\nqubit[3] q;\ngate g(theta) a, b {\n    rx(theta) a;\n    ctrl @ s a, b;\n}\ngate h2 a, b {\n    inv @ g(0.5) b, a;\n}\ninv @ g(0.1) q[0], q[1];\nctrl @ g(0.2) q[2], q[0], q[1];\npow(-2) @ g(0.3) q[0], q[1];\n";
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut definition = Circuit::new();
+    definition += RotateX::new(0, "theta".into());
+    definition +=
+        PragmaControlledCircuit::new(0, [Operation::from(SGate::new(1))].into_iter().collect());
+    let mut inverse = Circuit::new();
+    inverse +=
+        PragmaControlledCircuit::new(0, [Operation::from(InvSGate::new(1))].into_iter().collect());
+    inverse += RotateX::new(
+        0,
+        CalculatorFloat::from(-1.0) * CalculatorFloat::from("theta"),
+    );
+    let mut h2 = Circuit::new();
+    h2 += CallDefinedGate::new("g_inv".into(), vec![1, 0], vec![0.5.into()]);
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += GateDefinition::new(definition, "g".into(), vec![0, 1], vec!["theta".into()]);
+    circuit_qoqo += GateDefinition::new(inverse, "g_inv".into(), vec![0, 1], vec!["theta".into()]);
+    circuit_qoqo += GateDefinition::new(h2, "h2".into(), vec![0, 1], vec![]);
+    circuit_qoqo += CallDefinedGate::new("g_inv".into(), vec![0, 1], vec![0.1.into()]);
+    circuit_qoqo += PragmaControlledCircuit::new(
+        2,
+        [Operation::from(CallDefinedGate::new(
+            "g".into(),
+            vec![0, 1],
+            vec![0.2.into()],
+        ))]
+        .into_iter()
+        .collect(),
+    );
+    circuit_qoqo += CallDefinedGate::new("g_inv".into(), vec![0, 1], vec![0.3.into()]);
+    circuit_qoqo += CallDefinedGate::new("g_inv".into(), vec![0, 1], vec![0.3.into()]);
+
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that controlled and inverse gates written with gate modifiers are read back
+#[test_case("3.0"; "3.0")]
+#[test_case("3.0Roqoqo"; "3.0Roqoqo")]
+fn test_roundtrip_gate_modifiers(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string()))
+        .unwrap()
+        .with_emitter_options(EmitterOptions::new().with_gate_modifiers(true));
+
+    let mut circuit = Circuit::new();
+    circuit += InvSGate::new(0);
+    circuit += InvTGate::new(1);
+    circuit += ControlledPauliY::new(0, 1);
+    circuit += ControlledPhaseShift::new(1, 2, 0.25.into());
+    circuit += Toffoli::new(0, 1, 2);
+    circuit += ControlledSWAP::new(2, 0, 1);
+    circuit += TripleControlledPauliZ::new(0, 1, 2, 3);
+    circuit +=
+        PragmaControlledCircuit::new(3, [Operation::from(Hadamard::new(0))].into_iter().collect());
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that barriers are read as PragmaStopParallelBlock
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nqreg r[2];\nx q[0];\nbarrier q[0],r[1];\nx r[1];\n", vec![0, 3]; "arguments 2.0")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nqreg r[2];\nx q[0];\nbarrier q, r[1];\nx r[1];\n", vec![0, 1, 3]; "register 2.0")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nqubit[2] r;\nx q[0];\nbarrier;\nx r[1];\n", vec![0, 1, 2, 3]; "all qubits 3.0")]
+fn test_barrier(input: &str, qubits: Vec<usize>) {
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += PauliX::new(0);
+    circuit_qoqo += PragmaStopParallelBlock::new(qubits, CalculatorFloat::ZERO);
+    circuit_qoqo += PauliX::new(3);
+
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that the barriers written by the backend are read back
+#[test_case("2.0"; "2.0")]
+#[test_case("3.0Vanilla"; "3.0Vanilla")]
+fn test_roundtrip_barrier(qasm_version: &str) {
+    let backend = Backend::new(None, Some(qasm_version.to_string())).unwrap();
+
+    let mut circuit = Circuit::new();
+    circuit += Hadamard::new(0);
+    circuit += PragmaStopParallelBlock::new(vec![0, 1], CalculatorFloat::ZERO);
+    circuit += CNOT::new(0, 1);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(qasm.contains("\nbarrier q[0],q[1];\ncx q[0],q[1];\n"));
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that the pragmas written by the 3.0Roqoqo dialect are read back into the same operations
+#[test]
+fn test_roundtrip_roqoqo_pragmas() {
+    let backend = Backend::new(None, Some("3.0Roqoqo".to_string())).unwrap();
+
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += PragmaSetNumberOfMeasurements::new(20, "ro".into());
+    circuit += PragmaDamping::new(0, CalculatorFloat::from(0.1), CalculatorFloat::from(0.01));
+    circuit += PragmaDephasing::new(
+        1,
+        CalculatorFloat::from("gate_time"),
+        CalculatorFloat::from(0.02),
+    );
+    circuit += PragmaDepolarising::new(0, CalculatorFloat::from(0.1), CalculatorFloat::from(0.3));
+    circuit += PragmaGeneralNoise::new(
+        0,
+        1.0.into(),
+        array![[0.1, 0.0, 0.2], [0.0, 0.5, 0.0], [0.3, 0.0, 0.0]],
+    );
+    circuit += PragmaOverrotation::new("Hadamard".into(), vec![0, 1], 0.4, 0.5);
+    circuit += PragmaRandomNoise::new(1, 0.1.into(), 0.2.into(), 0.3.into());
+    circuit += PragmaBoostNoise::new(1.5.into());
+    circuit += PragmaRepeatGate::new(3);
+    circuit += PragmaSleep::new(vec![0, 1], 0.3.into());
+    circuit += PragmaStartDecompositionBlock::new(vec![0, 1], HashMap::from([(0, 1), (1, 0)]));
+    circuit += Hadamard::new(0);
+    circuit += PragmaStopDecompositionBlock::new(vec![0, 1]);
+    circuit += PragmaStopParallelBlock::new(vec![0, 1], 0.5.into());
+    circuit += PragmaSetStateVector::new(array![
+        Complex64::new(FRAC_1_SQRT_2, 0.0),
+        Complex64::new(0.0, -FRAC_1_SQRT_2),
+        Complex64::new(0.0, 0.0),
+        Complex64::new(-0.0, 0.0)
+    ]);
+    circuit += PragmaSetDensityMatrix::new(array![
+        [Complex64::new(0.5, 0.0), Complex64::new(0.0, 0.5)],
+        [Complex64::new(0.0, -0.5), Complex64::new(0.5, 0.0)]
+    ]);
+    circuit += PragmaGetStateVector::new("ro".into(), None);
+    circuit += PragmaGetDensityMatrix::new("ro".into(), None);
+    circuit += PragmaGetOccupationProbability::new("ro".into(), None);
+    circuit += PragmaGetPauliProduct::new(HashMap::from([(0, 3)]), "ro".into(), Circuit::new());
+    circuit += PragmaRepeatedMeasurement::new("ro".into(), 100, None);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+}
+
+/// Test that the circuits contained in roqoqo pragmas are written as a block and read back
+#[test]
+fn test_roundtrip_roqoqo_pragma_circuits() {
+    let backend = Backend::new(None, Some("3.0Roqoqo".to_string())).unwrap();
+
+    let mut body = Circuit::new();
+    body += Hadamard::new(0);
+    body += CNOT::new(0, 1);
+    let mut loop_body = Circuit::new();
+    loop_body += RotateX::new(1, 0.5.into());
+    loop_body += PragmaLoop::new(2.0.into(), body.clone());
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, true);
+    circuit += PragmaLoop::new(3.0.into(), loop_body);
+    circuit += PragmaGetStateVector::new("ro".into(), Some(body.clone()));
+    circuit += PragmaGetDensityMatrix::new("ro".into(), Some(body.clone()));
+    circuit += PragmaGetOccupationProbability::new("ro".into(), Some(Circuit::new()));
+    circuit += PragmaGetPauliProduct::new(HashMap::from([(0, 3), (1, 1)]), "ro".into(), body);
+    circuit += PragmaLoop::new(2.0.into(), Circuit::new());
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(
+        qasm.contains("pragma roqoqo PragmaGetStateVector ro {\n    h q[0];\n    cx q[0],q[1];\n}")
+    );
+    assert!(qasm.contains("pragma roqoqo PragmaLoop 2e0;"));
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit);
+    assert_eq!(
+        reader_to_circuit(qasm.as_bytes(), &ParserOptions::new()).unwrap(),
+        circuit
+    );
+}
+
+/// Test that the pragmasleep calls written by the 2.0 backend are read back as PragmaSleep
+#[test]
+fn test_roundtrip_pragmasleep() {
+    let backend = Backend::new(None, Some("2.0".to_string())).unwrap();
+
+    let mut circuit = Circuit::new();
+    circuit += PragmaSleep::new(vec![0, 1], 0.3.into());
+    circuit += PauliX::new(1);
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += PragmaSleep::new(vec![0], 0.3.into());
+    circuit_qoqo += PragmaSleep::new(vec![1], 0.3.into());
+    circuit_qoqo += PauliX::new(1);
+
+    assert_eq!(string_to_circuit(&qasm).unwrap(), circuit_qoqo);
+}
+
+/// Test that delays are read as PragmaSleep with the duration in seconds
+#[test_case("delay[100ns] q[0];", vec![0], 1e-7; "nanoseconds")]
+#[test_case("delay[2.5us] q[1], q[0];", vec![1, 0], 2.5e-6; "microseconds")]
+#[test_case("delay[2.5µs] q[1];", vec![1], 2.5e-6; "micro sign")]
+#[test_case("delay[3ms] q;", vec![0, 1], 3e-3; "milliseconds on register")]
+#[test_case("delay[1.5e-1s];", vec![0, 1], 0.15; "seconds on all qubits")]
+#[test_case("delay[40dt] q[1];", vec![1], 1e-8; "dt")]
+fn test_delay(statement: &str, qubits: Vec<usize>, sleep_time: f64) {
+    let input = format!("OPENQASM 3.0;\nqubit[2] q;\n{statement}\n");
+    let options = ParserOptions::new().with_dt(0.25e-9);
+
+    let mut circuit = Circuit::new();
+    circuit += PragmaSleep::new(qubits, sleep_time.into());
+    assert_eq!(
+        string_to_qasm_program_with_options(&input, &options)
+            .unwrap()
+            .into_circuit(),
+        circuit
+    );
+}
+
+/// Test that the duration of a box is read as the execution time of a parallel block
+#[test]
+fn test_box() {
+    let input = "OPENQASM 3.0;\nqubit[3] q;\nbox[200ns] {\n    x q[2];\n    delay[50ns] q[0];\n}\nbox {\n    h q[1];\n}\n";
+
+    let mut circuit = Circuit::new();
+    circuit += PauliX::new(2);
+    circuit += PragmaSleep::new(vec![0], 5e-8.into());
+    circuit += PragmaStopParallelBlock::new(vec![0, 2], 2e-7.into());
+    circuit += Hadamard::new(1);
+    assert_eq!(string_to_circuit(input).unwrap(), circuit);
+}
+
+/// Test that PragmaSleep written as a delay by the QASM 3.0 backend is read back
+#[test_case(TimeUnit::Seconds; "seconds")]
+#[test_case(TimeUnit::Microseconds; "microseconds")]
+#[test_case(TimeUnit::Nanoseconds; "nanoseconds")]
+fn test_roundtrip_delay(time_unit: TimeUnit) {
+    let backend = Backend::new(None, Some("3.0".to_string()))
+        .unwrap()
+        .with_emitter_options(
+            EmitterOptions::new()
+                .with_time_unit(time_unit)
+                .with_dt(1e-9),
+        );
+
+    let mut circuit = Circuit::new();
+    circuit += PragmaSleep::new(vec![0, 1], 3e-6.into());
+    circuit += PauliX::new(1);
+    circuit += PragmaSleep::new(vec![1], 2.5e-8.into());
+
+    let qasm = backend.circuit_to_qasm_str(&circuit).unwrap();
+    assert!(!qasm.contains("pragmasleep"));
+
+    let options = ParserOptions::new().with_dt(1e-9);
+    assert_eq!(
+        string_to_qasm_program_with_options(&qasm, &options)
+            .unwrap()
+            .into_circuit(),
+        circuit
+    );
+}
+
+/// Test that roqoqo pragmas that cannot be read back are reported
+#[test_case("pragma roqoqo PragmaLoop 2e0 PauliX(PauliX { qubit: 0 })\n;", QasmParseErrorKind::Unsupported; "nested circuit")]
+#[test_case("pragma roqoqo PragmaUnknown 1;", QasmParseErrorKind::Unsupported; "unknown pragma")]
+#[test_case("pragma roqoqo PragmaRepeatGate 3 {\nx q[0];\n}", QasmParseErrorKind::Syntax; "circuit of pragma without circuit")]
+#[test_case("pragma roqoqo PragmaDamping 0 1e0;", QasmParseErrorKind::Syntax; "missing argument")]
+#[test_case("pragma roqoqo PragmaRepeatGate 3 4;", QasmParseErrorKind::Syntax; "extra argument")]
+#[test_case("pragma roqoqo PragmaRepeatGate x;", QasmParseErrorKind::InvalidExpression; "invalid number")]
+fn test_roqoqo_pragma_errors(pragma: &str, kind: QasmParseErrorKind) {
+    let input = format!("OPENQASM 3.0;\nqubit[2] q;\n{pragma}\n");
+
+    let error = string_to_circuit(&input).unwrap_err();
+
+    assert_eq!(error.kind(), kind);
+    assert_eq!(error.line(), 3);
+}
+
+/// Test that pragmas of other tools are ignored
+#[test]
+fn test_other_pragmas_ignored() {
+    let input =
+        "OPENQASM 3.0;\nqubit[1] q;\npragma braket noise depolarizing(0.1) q[0];\nx q[0];\n";
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += PauliX::new(0);
+
+    assert_eq!(string_to_circuit(input).unwrap(), circuit_qoqo);
+}
+
+/// Test that opaque gates are defined by an empty placeholder definition
+#[test_case("2.0"; "2.0")]
+#[test_case("3.0"; "3.0")]
+fn test_opaque(version: &str) {
+    let input = format!("OPENQASM {version};\nqreg q[2];\nopaque mygate(theta, phi) a, b;\nmygate(0.5, pi) q[1], q[0];\n");
+
+    let circuit = string_to_circuit(&input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += GateDefinition::new(
+        Circuit::new(),
+        "mygate".to_owned(),
+        vec![0, 1],
+        vec!["theta".to_owned(), "phi".to_owned()],
+    );
+    circuit_qoqo += CallDefinedGate::new(
+        "mygate".to_owned(),
+        vec![1, 0],
+        vec![CalculatorFloat::from(0.5), CalculatorFloat::PI],
+    );
+
+    assert_eq!(circuit, circuit_qoqo);
+
+    let error = string_to_circuit(&format!(
+        "OPENQASM {version};\nqreg q[2];\nopaque mygate a, b;\nmygate q[0];\n"
+    ))
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::InvalidGateCall);
+}
+
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nqreg r[3];\ncx q,r;\n", "must have the same size"; "gate sizes")]
+#[test_case("OPENQASM 2.0;\nqreg q[3];\ncreg c[2];\nmeasure q -> c;\n", "Cannot measure 3 qubits into classical register c of length 2"; "measure sizes")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncreg c[2];\nmeasure q -> c[0];\n", "whole quantum register into a whole classical register"; "measure mixed")]
+fn test_broadcast_errors(input: &str, message: &str) {
+    let error = string_to_circuit(input).unwrap_err();
+    assert!(error.to_string().contains(message));
+}
+
+/// Test that parsing errors report the position and text of the offending statement
+#[test]
+fn test_parse_error_position() {
+    let input = "OPENQASM 2.0;\nqreg q[2];\n\nh q[0];\ncx q[0],  r[1];\n";
+
+    let error = string_to_circuit(input).unwrap_err();
+
+    assert_eq!(error.kind(), QasmParseErrorKind::Undeclared);
+    assert_eq!(error.line(), 5);
+    assert_eq!(error.column(), 11);
+    assert_eq!(error.snippet(), "r[1]");
+    assert_eq!(error.message(), "Quantum register r has not been declared");
+    assert_eq!(
+        error.to_string(),
+        "Undeclared identifier at line 5, column 11: Quantum register r has not been declared\n    r[1]"
+    );
+}
+
+/// Test that the gates of the bundled qelib1.inc are translated without definitions
+#[test]
+fn test_include_qelib1() {
+    let input =
+        "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[2];\ncu1(0.5) q[0], q[1];\nx q[1];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += ControlledPhaseShift::new(0, 1, 0.5.into());
+    circuit_qoqo += PauliX::new(1);
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that the gates of the bundled stdgates.inc can be called in OpenQASM 3.0
+#[test]
+fn test_include_stdgates() {
+    let input = "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[3] q;\ncswap q[0], q[1], q[2];\nid q[2];\n";
+
+    let circuit = string_to_circuit(input).unwrap();
+
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo += ControlledSWAP::new(0, 1, 2);
+    circuit_qoqo += Identity::new(2);
+
+    assert_eq!(circuit, circuit_qoqo);
+}
+
+/// Test that included files are looked up in the include search paths
+#[test]
+fn test_include_search_path() {
+    let path = Path::new("tests/includes/search_path.qasm");
+
+    let error = path_to_qasm_program_with_options(path, &ParserOptions::new()).unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Include);
+    assert_eq!(error.line(), 3);
+    assert_eq!(
+        error.message(),
+        "Included file search_lib.inc could not be found"
+    );
+
+    let options = ParserOptions::new().with_include_path("lib");
+    let circuit = path_to_qasm_program_with_options(path, &options)
+        .unwrap()
+        .into_circuit();
+
+    let mut entangle_circuit = Circuit::new();
+    entangle_circuit += Hadamard::new(0);
+    entangle_circuit += CNOT::new(0, 1);
+    let mut entangle_all_circuit = Circuit::new();
+    entangle_all_circuit += CallDefinedGate::new("entangle".to_owned(), vec![0, 1], vec![]);
+    entangle_all_circuit += CallDefinedGate::new("entangle".to_owned(), vec![1, 2], vec![]);
+    let mut circuit_qoqo = Circuit::new();
+    circuit_qoqo +=
+        GateDefinition::new(entangle_circuit, "entangle".to_owned(), vec![0, 1], vec![]);
+    circuit_qoqo += GateDefinition::new(
+        entangle_all_circuit,
+        "entangle_all".to_owned(),
+        vec![0, 1, 2],
+        vec![],
+    );
+    circuit_qoqo += CallDefinedGate::new("entangle_all".to_owned(), vec![0, 1, 2], vec![]);
+    circuit_qoqo += InvSGate::new(0);
+
+    assert_eq!(circuit, circuit_qoqo);
+
+    let absolute_options = ParserOptions::new()
+        .with_include_path(std::env::current_dir().unwrap().join("tests/includes/lib"));
+    let input = "OPENQASM 2.0;\ninclude \"search_lib.inc\";\nqreg q[2];\nentangle q[0], q[1];\n";
+    assert!(string_to_qasm_program_with_options(input, &absolute_options).is_ok());
+}
+
+/// Test that missing included files and include cycles are reported
+#[test]
+fn test_include_errors() {
+    let error =
+        string_to_circuit("OPENQASM 2.0;\ninclude \"missing.inc\";\nqreg q[1];\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Include);
+    assert_eq!(error.line(), 2);
+    assert_eq!(
+        error.message(),
+        "Included file missing.inc could not be found"
+    );
+
+    let error = path_to_qasm_program_with_options(
+        Path::new("tests/includes/include_cycle.qasm"),
+        &ParserOptions::new(),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Include);
+    assert_eq!(error.line(), 2);
+    assert!(error
+        .message()
+        .starts_with("Error in included file cycle_a.inc"));
+    assert!(error.message().contains("Include cycle: "));
+    assert!(error.message().contains("cycle_a.inc -> "));
+}
+
+/// Test that input not following the grammar is reported as a syntax error
+#[test]
+fn test_syntax_error_position() {
+    let input = "OPENQASM 2.0;\nqreg q[2];\nh q[0]\nx q[1];\n";
+
+    let error = string_to_circuit(input).unwrap_err();
+
+    assert_eq!(error.kind(), QasmParseErrorKind::Syntax);
+    assert_eq!(error.line(), 3);
+    assert_eq!(error.column(), 3);
+    assert_eq!(error.snippet(), "q[0]");
+}
+
+/// Test that invalid input is reported as an error instead of panicking
+#[test_case("OPENQASM 2.0;\nqreg q[99999999999999999999999];\n", QasmParseErrorKind::InvalidExpression; "large register")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nx q[99999999999999999999999];\n", QasmParseErrorKind::IndexOutOfRange; "large index")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncx q[0];\n", QasmParseErrorKind::InvalidGateCall; "missing qubit")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nrz q[0];\n", QasmParseErrorKind::InvalidGateCall; "missing parameter")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nrz(0.1, 0.2) q[0];\n", QasmParseErrorKind::InvalidGateCall; "extra parameter")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ngate g a, b {\ncx a,b;\n}\ng q[0];\n", QasmParseErrorKind::InvalidGateCall; "custom gate arity")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ngate g a {\ncx a,b;\n}\n", QasmParseErrorKind::Undeclared; "unknown qubit in definition")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nrx(sin) q[0];\n", QasmParseErrorKind::Syntax; "function without argument")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nrx(1/0 +) q[0];\n", QasmParseErrorKind::Syntax; "dangling operator")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nrx(1/0) q[0];\n", QasmParseErrorKind::InvalidExpression; "division by zero")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nrx(2 % 0) q[0];\n", QasmParseErrorKind::InvalidExpression; "modulo by zero")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nrx(pow(2)) q[0];\n", QasmParseErrorKind::InvalidExpression; "function arity")]
+#[test_case("OPENQASM 2.0;\ncreg c[2];\ncreg c[1];\n", QasmParseErrorKind::Redeclaration; "duplicate classical register")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nmeasure q[0] -> c[99999999999999999999999];\n", QasmParseErrorKind::InvalidExpression; "large bit index")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\nmeasure q[0] -> c[0];\n", QasmParseErrorKind::Undeclared; "measure into undeclared bit")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncreg c[2];\nmeasure q[0] -> c[2];\n", QasmParseErrorKind::IndexOutOfRange; "measure into bit out of range")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nc[0] = measure q[0];\n", QasmParseErrorKind::Undeclared; "measure assignment to undeclared bit")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nbit[1] c;\nc[1] = measure q[0];\n", QasmParseErrorKind::IndexOutOfRange; "measure assignment to bit out of range")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:99999999999999999999999] {\nx q[0];\n}\n", QasmParseErrorKind::InvalidExpression; "loop bound overflow")]
+#[test_case("OPENQASM 3.0;\nqubit[3] q;\nctrl @ ch q[0], q[1], q[2];\n", QasmParseErrorKind::Unsupported; "ctrl decomposed gate")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npow(0.5) @ h q[0];\n", QasmParseErrorKind::Unsupported; "fractional power")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npow(1e20) @ x q[0];\n", QasmParseErrorKind::Unsupported; "huge power")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npow(1e19) @ rzz(0.1) q[0],q[1];\n", QasmParseErrorKind::Unsupported; "huge power of decomposed gate")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npow(-1e19) @ rzz(0.1) q[0],q[1];\n", QasmParseErrorKind::Unsupported; "huge negative power")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\ninv @ iswap q[0], q[1];\n", QasmParseErrorKind::Unsupported; "unknown inverse")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nctrl(2) @ x q[0], q[1];\n", QasmParseErrorKind::InvalidGateCall; "missing control qubit")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nctrl(0) @ x q[0], q[1];\n", QasmParseErrorKind::InvalidExpression; "zero controls")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\nopaque g a;\ninv @ g q[0];\n", QasmParseErrorKind::Unsupported; "inverse of opaque gate")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:0:3] {\nx q[0];\n}\n", QasmParseErrorKind::InvalidExpression; "zero loop step")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:2.5] {\nx q[0];\n}\n", QasmParseErrorKind::InvalidExpression; "fractional loop bound")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:2:n] {\nx q[0];\n}\n", QasmParseErrorKind::Unsupported; "symbolic loop with step")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:3] {\nrx(i) q[0];\n}\n", QasmParseErrorKind::Unsupported; "loop variable in body")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nfor uint i in [0:3] {\nx q[0];\nrz(pi/(i+1)) q[0];\n}\n", QasmParseErrorKind::Unsupported; "loop variable in nested expression")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\ndelay[10dt] q[0];\n", QasmParseErrorKind::Unsupported; "dt without length")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\ndelay[-1.0ns] q[0];\n", QasmParseErrorKind::InvalidExpression; "negative delay")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\ndelay[10] q[0];\n", QasmParseErrorKind::Syntax; "delay without unit")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\ndef m(qubit a) -> bit {\n    bit b;\n    b = measure a;\n    return b;\n}\nm(q[0]);\n", QasmParseErrorKind::Unsupported; "unassigned subroutine result")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nbit[2] c;\ndef m(qubit a) -> bit {\n    bit b;\n    b = measure a;\n    return b;\n}\nc[0] = m(q[0]);\nc[1] = m(q[0]);\n", QasmParseErrorKind::Unsupported; "subroutine result assigned to different bits")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\ndef m(qubit a) -> bit {\n    bit b;\n    b = measure a;\n    return b;\n}\nc[0] = m(q[0]);\n", QasmParseErrorKind::Undeclared; "subroutine result assigned to undeclared bit")]
+#[test_case("OPENQASM 3.0;\nqubit[1] q;\nbit[1] c;\ndef r(qubit a) {\n    reset a;\n}\nc[0] = r(q[0]);\n", QasmParseErrorKind::InvalidGateCall; "assigned subroutine without result")]
+#[test_case("", QasmParseErrorKind::Syntax; "empty input")]
+#[test_case("OPENQASM", QasmParseErrorKind::Syntax; "truncated header")]
+fn test_parser_errors_without_panic(input: &str, kind: QasmParseErrorKind) {
+    let error = string_to_circuit(input).unwrap_err();
+    assert_eq!(error.kind(), kind);
+}
+
+/// Test that gate calls using a qubit more than once are rejected at the call
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncx q[0],q[0];\n", 3; "2.0 gate")]
+#[test_case("OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[2] q;\nctrl @ x q[0], q[0];\n", 4; "controlled gate")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ncx q,q[1];\n", 3; "broadcast gate")]
+#[test_case("OPENQASM 2.0;\nqreg q[2];\ngate g a,b {\n  cx a,a;\n}\n", 4; "gate definition")]
+fn test_duplicate_qubits(input: &str, line: usize) {
+    let error = string_to_circuit(input).unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::InvalidGateCall);
+    assert_eq!(error.line(), line);
+}
+
+/// Test that registers larger than the maximal register size are rejected at their declaration
+#[test]
+fn test_max_register_size() {
+    let error = string_to_circuit("OPENQASM 2.0;\nqreg q[100000000000];\nh q;\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Unsupported);
+    assert_eq!(error.line(), 2);
+    let error = string_to_circuit("OPENQASM 2.0;\ncreg c[100000000000];\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Unsupported);
+
+    let options = ParserOptions::new().with_max_register_size(4);
+    assert!(
+        string_to_qasm_program_with_options("OPENQASM 2.0;\nqreg q[4];\nh q;\n", &options).is_ok()
+    );
+    let error =
+        string_to_qasm_program_with_options("OPENQASM 3.0;\nqubit[5] q;\n", &options).unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Unsupported);
+
+    // Without a limit the registers must still fit into the roqoqo qubit indices
+    let options = ParserOptions::new().with_max_register_size(usize::MAX);
+    let error = string_to_qasm_program_with_options(
+        "OPENQASM 2.0;\nqreg q[18446744073709551615];\nqreg r[2];\nh r;\n",
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Unsupported);
+    assert_eq!(error.line(), 3);
+}
+
+/// Test that a QASM file is wrapped in a QuantumProgram with a ClassicalRegister measurement
+#[test]
+fn test_file_to_quantum_program() {
+    let file = File::open(std::env::current_dir().unwrap().join("tests/input.qasm")).unwrap();
+
+    let program = file_to_quantum_program(file).unwrap();
+
+    let file = File::open(std::env::current_dir().unwrap().join("tests/input.qasm")).unwrap();
+    let expected = QuantumProgram::ClassicalRegister {
+        measurement: ClassicalRegister {
+            constant_circuit: None,
+            circuits: vec![file_to_circuit(file).unwrap()],
+        },
+        input_parameter_names: vec![],
+    };
+    assert_eq!(program, expected);
+}
+
+/// Test that the input parameters of a QASM string become the parameters of the QuantumProgram
+#[test]
+fn test_string_to_quantum_program() {
+    let input = "OPENQASM 3.0;\ninput float phi;\ninput angle[32] theta;\nqubit[2] q;\nbit[2] ro;\noutput bit[1] result;\nrx(theta) q[0];\nrz(2*phi) q[1];\nro = measure q;\nresult[0] = measure q[1];\n";
+
+    let program = string_to_quantum_program(input).unwrap();
+
+    let mut circuit = Circuit::new();
+    circuit += DefinitionBit::new("ro".into(), 2, false);
+    circuit += DefinitionBit::new("result".into(), 1, true);
+    circuit += RotateX::new(0, "theta".into());
+    circuit += RotateZ::new(1, CalculatorFloat::from(2.0) * CalculatorFloat::from("phi"));
+    circuit += PragmaRepeatedMeasurement::new("ro".into(), 1, None);
+    circuit += MeasureQubit::new(1, "result".into(), 0);
+    let expected = QuantumProgram::ClassicalRegister {
+        measurement: ClassicalRegister {
+            constant_circuit: None,
+            circuits: vec![circuit],
+        },
+        input_parameter_names: vec!["phi".to_string(), "theta".to_string()],
+    };
+    assert_eq!(program, expected);
+
+    // The InputSymbolic definitions are removed, so that the parameters given to the program
+    // are used
+    if let QuantumProgram::ClassicalRegister { measurement, .. } = program {
+        let substituted = measurement
+            .substitute_parameters(HashMap::from([
+                ("phi".to_string(), 0.25),
+                ("theta".to_string(), 0.5),
+            ]))
+            .unwrap();
+        assert_eq!(
+            substituted.circuits[0].operations()[0],
+            Operation::from(RotateX::new(0, 0.5.into()))
+        );
+        assert_eq!(
+            substituted.circuits[0].operations()[1],
+            Operation::from(RotateZ::new(1, 0.5.into()))
+        );
+    }
+
+    let options = ParserOptions::new().with_dt(1e-9);
+    let program = string_to_qasm_program_with_options(
+        "OPENQASM 3.0;\nqubit[1] q;\nbit[1] ro;\ndelay[10dt] q[0];\nro[0] = measure q[0];\n",
+        &options,
+    )
+    .unwrap()
+    .into_quantum_program()
+    .unwrap();
+    assert!(matches!(
+        program,
+        QuantumProgram::ClassicalRegister { input_parameter_names, .. } if input_parameter_names.is_empty()
+    ));
+}
+
+/// Test that a QuantumProgram is only built from QASM input containing a measurement
+#[test]
+fn test_quantum_program_without_measurement() {
+    let error = string_to_quantum_program("OPENQASM 2.0;\nqreg q[1];\nx q[0];\n").unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::MissingMeasurement);
+    // The error concerns the whole input and does not claim a position
+    assert_eq!((error.line(), error.column()), (0, 0));
+    assert_eq!(
+        error.to_string(),
+        "Missing measurement: A QuantumProgram needs a measurement, but the QASM input contains none"
+    );
+
+    let error = path_to_qasm_program_with_options(
+        Path::new("tests/does_not_exist.qasm"),
+        &ParserOptions::new(),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Io);
+}
+
+/// Test that the QasmProgram holds the information of the file next to the Circuit
+#[test]
+fn test_string_to_qasm_program() {
+    let input = "OPENQASM 3.0;\n// Bell state\n// with a custom gate\ninclude \"stdgates.inc\";\nqubit[1] a;\nqubit[2] b;\nbit[2] c;\nbit d;\ngate bell(theta) x, y {\nh x;\ncx x, y;\n}\nopaque noisy x;\n// not part of the header\nbell(0.1) b[0], b[1];\nc = measure b;\n";
+    let program = string_to_qasm_program(input).unwrap();
+
+    assert_eq!(
+        program.version(),
+        QasmVersion::V3point0(Qasm3Dialect::Vanilla)
+    );
+    assert_eq!(
+        program.header_comments(),
+        ["Bell state".to_owned(), "with a custom gate".to_owned()]
+    );
+    assert_eq!(program.includes(), ["stdgates.inc".to_owned()]);
+    assert_eq!(
+        program.qubit_registers().iter().collect::<Vec<_>>(),
+        vec![("a", 0, 1), ("b", 1, 2)]
+    );
+    assert_eq!(
+        program.classical_registers(),
+        [("c".to_owned(), 2), ("d".to_owned(), 1)]
+    );
+    assert_eq!(
+        program.gate_signatures(),
+        [("bell".to_owned(), 2, 1), ("noisy".to_owned(), 1, 0)]
+    );
+    assert!(program.warnings().is_empty());
+    assert_eq!(program.circuit(), &string_to_circuit(input).unwrap());
+    assert_eq!(program.into_circuit(), string_to_circuit(input).unwrap());
+}
+
+/// Test the detection of the dialect of a file
+#[test_case("OPENQASM 2.0;\nqreg q[1];\nx q[0];", QasmVersion::V2point0(Qasm2Dialect::Vanilla); "2.0 without include")]
+#[test_case("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[1];\nx q[0];", QasmVersion::V2point0(Qasm2Dialect::Qulacs); "2.0 with qelib1")]
+#[test_case("OPENQASM 2.0;\ninclude \"qelib1.inc\";\ngate g a { x a; }\nqreg q[1];\ng q[0];", QasmVersion::V2point0(Qasm2Dialect::Vanilla); "2.0 with definitions")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\ncx q[0], q[1];", QasmVersion::V3point0(Qasm3Dialect::Vanilla); "3.0 vanilla")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\ncnot q[0], q[1];", QasmVersion::V3point0(Qasm3Dialect::Braket); "3.0 braket gate name")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npragma braket noise depolarizing(0.1) q[0];", QasmVersion::V3point0(Qasm3Dialect::Braket); "3.0 braket pragma")]
+#[test_case("OPENQASM 3.0;\nqubit[2] q;\npragma roqoqo PragmaRepeatGate 3;\ncnot q[0], q[1];", QasmVersion::V3point0(Qasm3Dialect::Roqoqo); "3.0 roqoqo pragma")]
+fn test_qasm_program_version(input: &str, version: QasmVersion) {
+    assert_eq!(
+        string_to_qasm_program_with_options(input, &ParserOptions::new())
+            .unwrap()
+            .version(),
+        version
+    );
+}
+
+/// Test that the QasmProgram of a file lists its included files and their gates
+#[test]
+fn test_path_to_qasm_program() {
+    let program =
+        path_to_qasm_program_with_options(Path::new("tests/include.qasm"), &ParserOptions::new())
+            .unwrap();
+    assert_eq!(
+        program.version(),
+        QasmVersion::V2point0(Qasm2Dialect::Vanilla)
+    );
+    assert_eq!(
+        program.includes(),
+        ["custom_lib.inc".to_owned(), "other_lib.inc".to_owned()]
+    );
+    assert!(program
+        .gate_signatures()
+        .contains(&("custom_bell".to_owned(), 2, 0)));
+    assert_eq!(
+        program.circuit(),
+        &path_to_qasm_program_with_options(Path::new("tests/include.qasm"), &ParserOptions::new())
+            .unwrap()
+            .into_circuit()
+    );
+
+    let program = file_to_qasm_program(File::open("tests/comments.qasm").unwrap()).unwrap();
+    assert!(program.header_comments().is_empty());
+    assert!(program.includes().is_empty());
+    assert!(program.gate_signatures().is_empty());
+}
+
+/// Test that the warnings of the parser are kept in the QasmProgram
+#[test]
+fn test_qasm_program_warnings() {
+    let program = string_to_qasm_program_with_options(
+        "OPENQASM 2.0;\nqreg q[1];\nunknown q[0];",
+        &ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Lenient),
+    )
+    .unwrap();
+    assert_eq!(program.warnings().len(), 1);
+    // Gates declared by the parser are not part of the declared gates
+    let program = string_to_qasm_program_with_options(
+        "OPENQASM 2.0;\nqreg q[1];\nunknown q[0];",
+        &ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Opaque),
+    )
+    .unwrap();
+    assert!(program.gate_signatures().is_empty());
+}
+
+/// Test that reading QASM files statement by statement gives the same circuit as reading them at once
+#[test_case("tests/input.qasm"; "input")]
+#[test_case("tests/gates.qasm"; "gates")]
+#[test_case("tests/sqg.qasm"; "single qubit gates")]
+#[test_case("tests/comments.qasm"; "comments")]
+#[test_case("tests/gate_defs.qasm"; "gate definitions")]
+#[test_case("tests/symbols_math_expr.qasm"; "expressions")]
+#[test_case("tests/qasm3.qasm"; "qasm 3.0")]
+#[test_case("tests/conditional.qasm"; "conditional")]
+#[test_case("tests/registers.qasm"; "registers")]
+#[test_case("tests/broadcast.qasm"; "broadcast")]
+fn test_reader_to_circuit(path: &str) {
+    let expected = file_to_circuit(File::open(path).unwrap()).unwrap();
+    let circuit = reader_to_circuit(
+        BufReader::new(File::open(path).unwrap()),
+        &ParserOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(circuit, expected);
+}
+
+/// Test that statements spanning several lines or sharing lines are read correctly
+#[test]
+fn test_reader_to_circuit_statements() {
+    let input = "// leading comment\nOPENQASM 3.0;\nqubit[2] q;\nbit[2] c;\nfloat[1] z_re;\nfloat[1] z_im;\ngate g(theta) a, b\n{\n  rx(theta) a; // {\n  cx a, b;\n}\ng(0.1) q[0], q[1]; x q[0];\nfor uint i in [0:2]\n{\n  h q[0];\n}\nc[0] = measure q[0];\nif (c[0] == 1) x q[1];\nif (c[0] == 1) {\n  z q[1];\n}\n";
+    let circuit = reader_to_circuit(input.as_bytes(), &ParserOptions::new()).unwrap();
+    assert_eq!(
+        circuit,
+        string_to_circuit(input.trim_start_matches("// leading comment\n")).unwrap()
+    );
+    assert_eq!(
+        circuit.definitions()[1],
+        Operation::from(DefinitionComplex::new("z".to_owned(), 1, true))
+    );
+}
+
+/// Test that the operations are passed to the callback in the order of the input
+#[test]
+fn test_reader_for_each_operation() {
+    let input = "OPENQASM 2.0;\nqreg q[2];\ncreg c[1];\nx q[0];\nif(c==1) x q[1];\nif(c==1) y q[1];\nh q[1];\n";
+    let mut operations: Vec<Operation> = vec![];
+    reader_for_each_operation(input.as_bytes(), &ParserOptions::new(), |operation| {
+        operations.push(operation)
+    })
+    .unwrap();
+    let mut body = Circuit::new();
+    body += PauliX::new(1);
+    body += PauliY::new(1);
+    assert_eq!(
+        operations,
+        vec![
+            Operation::from(DefinitionBit::new("c".to_owned(), 1, true)),
+            Operation::from(PauliX::new(0)),
+            Operation::from(PragmaConditional::new("c".to_owned(), 0, body)),
+            Operation::from(Hadamard::new(1)),
+        ]
+    );
+}
+
+/// Test that errors and warnings of the streaming parser give the line in the whole input
+#[test]
+fn test_stream_parser_positions() {
+    let input =
+        "OPENQASM 2.0;\nqreg q[2];\n\ngate g a\n{\n  x a;\n}\ng q[0];\nunknown q[0];\nx q[5];\nh q[0];\n";
+    let mut parser = QasmStreamParser::new(
+        input.as_bytes(),
+        &ParserOptions::new().with_unknown_gate_policy(UnknownGatePolicy::Lenient),
+    );
+    assert_eq!(parser.next().unwrap().unwrap().hqslang(), "GateDefinition");
+    assert_eq!(
+        parser.next().unwrap().unwrap(),
+        Operation::from(CallDefinedGate::new("g".to_owned(), vec![0], vec![]))
+    );
+    let error = parser.next().unwrap().unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::IndexOutOfRange);
+    assert_eq!(error.line(), 10);
+    assert_eq!(parser.warnings().len(), 1);
+    assert_eq!(parser.warnings()[0].line(), 9);
+    assert_eq!(parser.qubit_registers().unwrap().number_qubits(), 2);
+    assert!(parser.next().is_none());
+
+    let error = reader_to_circuit(
+        "OPENQASM 2.0;\nqreg q[1];\n\nx q[0]\n".as_bytes(),
+        &ParserOptions::new(),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Syntax);
+    assert_eq!(error.line(), 4);
+}
+
+/// Test that the streaming parser requires a single OPENQASM header
+#[test_case("qreg q[1];\nx q[0];\n"; "missing header")]
+#[test_case(""; "empty input")]
+#[test_case("// only a comment\n"; "only comments")]
+#[test_case("OPENQASM 2.0;\nqreg q[1];\nOPENQASM 2.0;\n"; "repeated header")]
+fn test_stream_parser_header_errors(input: &str) {
+    let error = reader_to_circuit(input.as_bytes(), &ParserOptions::new()).unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Syntax);
+}
+
+/// Reader of an endless QASM file repeating the same statement.
+struct EndlessQasm {
+    /// The part of the input that has not been read yet.
+    pending: Vec<u8>,
+}
+
+impl std::io::Read for EndlessQasm {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            self.pending = b"x q[0];\n".to_vec();
+        }
+        let length = buffer.len().min(self.pending.len());
+        buffer[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+        Ok(length)
+    }
+}
+
+/// Test that operations are yielded before the end of the input has been read
+#[test]
+fn test_stream_parser_endless_input() {
+    let reader = BufReader::new(EndlessQasm {
+        pending: b"OPENQASM 2.0;\nqreg q[1];\n".to_vec(),
+    });
+    let operations = QasmStreamParser::new(reader, &ParserOptions::new())
+        .take(1000)
+        .collect::<Result<Vec<Operation>, _>>()
+        .unwrap();
+    assert_eq!(operations.len(), 1000);
+    assert!(operations
+        .iter()
+        .all(|operation| *operation == Operation::from(PauliX::new(0))));
+}
+
+/// Test that calls of user-defined gates are replaced by their bodies when inlining
+#[test]
+fn test_inline_gates() {
+    let input = "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[3] q;\ngate rot(a, b) x { rx(a) x; rz(a * b) x; }\ngate pair(theta) x, y { rot(theta / 2, 2) y; cx x, y; }\npair(pi) q[2], q[0];\nrot(0.5, 1) q[1];\n";
+    let options = ParserOptions::new().with_inline_gates(true);
+    let circuit = string_to_qasm_program_with_options(input, &options)
+        .unwrap()
+        .into_circuit();
+
+    let mut expected = Circuit::new();
+    expected += RotateX::new(0, FRAC_PI_2.into());
+    expected += RotateZ::new(0, PI.into());
+    expected += CNOT::new(2, 0);
+    expected += RotateX::new(1, 0.5.into());
+    expected += RotateZ::new(1, 0.5.into());
+    assert_eq!(circuit, expected);
+
+    let circuit = string_to_qasm_program_with_options(input, &ParserOptions::new())
+        .unwrap()
+        .into_circuit();
+    assert_eq!(circuit.definitions().len(), 2);
+    assert!(circuit.operations()[0].hqslang() == "CallDefinedGate");
+}
+
+/// Test that symbolic parameters are substituted into the bodies of inlined gates
+#[test]
+fn test_inline_gates_symbolic() {
+    let options = ParserOptions::new().with_inline_gates(true);
+    let circuit = string_to_qasm_program_with_options(
+        "OPENQASM 3.0;\ninput float theta;\nqubit[1] q;\ngate g(a) x { rx(a / 2) x; }\ng(theta + 1) q[0];\n",
+        &options,
+    )
+    .unwrap().into_circuit();
+    let expected = string_to_qasm_program_with_options(
+        "OPENQASM 3.0;\ninput float theta;\nqubit[1] q;\nrx((theta + 1) / 2) q[0];\n",
+        &options,
+    )
+    .unwrap()
+    .into_circuit();
+    assert_eq!(circuit, expected);
+}
+
+/// Test that gate modifiers are applied to the inlined bodies
+#[test_case("inv @ g q[0], q[1];", vec![Operation::from(CNOT::new(0, 1)), Operation::from(InvSGate::new(0))]; "inverse")]
+#[test_case("ctrl @ g q[2], q[0], q[1];", vec![Operation::from(PragmaControlledCircuit::new(2, [Operation::from(SGate::new(0)), Operation::from(CNOT::new(0, 1))].into_iter().collect()))]; "controlled")]
+#[test_case("pow(2) @ g q[0], q[1];", vec![Operation::from(SGate::new(0)), Operation::from(CNOT::new(0, 1)), Operation::from(SGate::new(0)), Operation::from(CNOT::new(0, 1))]; "power")]
+fn test_inline_gates_modifiers(call: &str, operations: Vec<Operation>) {
+    let input = format!("OPENQASM 3.0;\nqubit[3] q;\ngate g a, b {{ s a; cx a, b; }}\n{call}\n");
+    let circuit =
+        string_to_qasm_program_with_options(&input, &ParserOptions::new().with_inline_gates(true))
+            .unwrap()
+            .into_circuit();
+    assert!(circuit.definitions().is_empty());
+    assert_eq!(circuit.operations(), &operations);
+}
+
+/// Test that calls of opaque gates and of gates from included files are handled when inlining
+#[test]
+fn test_inline_gates_opaque_and_included() {
+    let options = ParserOptions::new()
+        .with_inline_gates(true)
+        .with_include_path("tests/includes/lib");
+    let circuit = string_to_qasm_program_with_options(
+        "OPENQASM 2.0;\ninclude \"search_lib.inc\";\nopaque noisy a;\nqreg q[3];\nentangle_all q[0], q[1], q[2];\nnoisy q[1];\n",
+        &options,
+    )
+    .unwrap().into_circuit();
+    let mut expected = Circuit::new();
+    expected += GateDefinition::new(Circuit::new(), "noisy".to_owned(), vec![0], vec![]);
+    expected += Hadamard::new(0);
+    expected += CNOT::new(0, 1);
+    expected += Hadamard::new(1);
+    expected += CNOT::new(1, 2);
+    expected += CallDefinedGate::new("noisy".to_owned(), vec![1], vec![]);
+    assert_eq!(circuit, expected);
+}
+
+/// Test that a gate calling a previous definition of itself cannot be inlined
+#[test]
+fn test_inline_gates_recursive() {
+    let error = string_to_qasm_program_with_options(
+        "OPENQASM 2.0;\nqreg q[1];\ngate g a { x a; }\ngate g a { g a; }\ng q[0];\n",
+        &ParserOptions::new().with_inline_gates(true),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Unsupported);
+}
+
+/// Test that `def` subroutines are translated into GateDefinitions
+#[test]
+fn test_subroutine_def() {
+    let input = "OPENQASM 3.0;\ndef check(float[64] theta, qubit a, qubit b) -> bit {\n    bit result;\n    rx(theta) a;\n    cx a, b;\n    result = measure b;\n    reset b;\n    return result;\n}\nqubit[3] q;\nbit[1] ro;\nro[0] = check(0.5, q[2], q[0]);\n";
+    let circuit = string_to_circuit(input).unwrap();
+    let mut definition_circuit = Circuit::new();
+    definition_circuit += RotateX::new(0, "theta".into());
+    definition_circuit += CNOT::new(0, 1);
+    definition_circuit += MeasureQubit::new(1, "ro".to_owned(), 0);
+    definition_circuit += PragmaActiveReset::new(1);
+    // The definition is added at the first call, where the returned bit is known
+    let mut expected = Circuit::new();
+    expected += DefinitionBit::new("ro".to_owned(), 1, true);
+    expected += GateDefinition::new(
+        definition_circuit,
+        "check".to_owned(),
+        vec![0, 1],
+        vec!["theta".to_owned()],
+    );
+    expected += CallDefinedGate::new("check".to_owned(), vec![2, 0], vec![0.5.into()]);
+    assert_eq!(circuit, expected);
+
+    let circuit =
+        string_to_qasm_program_with_options(input, &ParserOptions::new().with_inline_gates(true))
+            .unwrap()
+            .into_circuit();
+    assert_eq!(
+        circuit.operations(),
+        &vec![
+            Operation::from(RotateX::new(2, 0.5.into())),
+            Operation::from(CNOT::new(2, 0)),
+            Operation::from(MeasureQubit::new(0, "ro".to_owned(), 0)),
+            Operation::from(PragmaActiveReset::new(0)),
+        ]
+    );
+}
+
+/// Test that subroutines only use their own qubit arguments
+#[test]
+fn test_subroutine_def_undeclared_qubit() {
+    let error =
+        string_to_circuit("OPENQASM 3.0;\nqubit[1] q;\ndef f(qubit a) {\n    reset q;\n}\n")
+            .unwrap_err();
+    assert_eq!(error.kind(), QasmParseErrorKind::Undeclared);
+    assert_eq!(error.line(), 4);
+}
@@ -0,0 +1,17 @@
+OPENQASM 3.0;
+include "stdgates.inc";
+
+input angle[32] theta;
+qubit[3] q;
+output bit[2] ro;
+
+h q[0];
+cx q[0],q[1];
+for uint i in [0:2] {
+    x q[2];
+}
+if(ro[0]==1) {
+    rx(pi/2) q[1];
+}
+gphase 0.5;
+measure q[0] -> ro[0];
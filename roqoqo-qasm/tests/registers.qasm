@@ -0,0 +1,11 @@
+OPENQASM 2.0;
+include "qelib1.inc";
+qreg a[2];
+qreg b[3];
+creg c[2];
+
+h a[1];
+cx a[0],b[0];
+rz(0.5) b[2];
+measure b[1] -> c[0];
+reset a[0];
@@ -1,5 +1,5 @@
 OPENQASM 2.0;
 creg c[2];
-qreg q[2];
+qreg q[3];
 
 u3(3.14159265359,0.0,3.14159265359) q[2];